@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use super::card_enums::Card;
+use super::coms::*;
+use super::player::Play;
+
+
+// Dealer side of the Annual Computer Poker Competition protocol, so ACPC agents can take a seat.
+// Agents connect over TCP, send "VERSION:2.0.0" and then receive MATCHSTATE lines:
+//     MATCHSTATE:<position>:<hand number>:<betting>:<cards>
+// and answer the states where they act with the same line followed by ":<action>".
+// Positions are seats, seat 0 posts the small blind and acts first in every round.
+// Antes are dead money, raise amounts count the chips put in the hand besides the ante.
+
+pub const ACPC_VERSION: &str = "VERSION:2.";
+
+#[derive(Clone, Debug)]
+pub struct AcpcSeat {
+    pub name: String,
+    // 0 picks an ephemeral port
+    pub port: u16,
+}
+
+#[derive(Clone, Debug)]
+pub struct AcpcConfig {
+    pub host: String,
+    pub seats: Vec<AcpcSeat>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AcpcAction {
+    FOLD,
+    // call or check
+    CALL,
+    // raise to the total put in the hand
+    RAISE(u32),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AcpcHand {
+    pub hand_number: u64,
+    pub position: usize,
+    pub n_players: usize,
+    pub hole_cards: Option<Vec<u8>>,
+    pub ante: u32,
+    // (player, action, amount, turn) of every action in the hand, in order
+    actions: Vec<(usize, Action, u32, Turn)>,
+}
+
+fn card_names(cards: &[u8]) -> String {
+    return cards.iter().map(|card| Card::card_from_int(card).short_name()).collect();
+}
+
+impl AcpcHand {
+
+    pub fn record(&mut self, player: usize, action: Action, amount: u32, turn: Turn) {
+        self.actions.push((player, action, amount, turn));
+    }
+
+    pub fn committed(&self, player: usize) -> u32 {
+        // chips the player put in the hand besides the ante
+        let total: u32 = self.actions.iter().filter(|(idx, ..)| *idx == player).map(|(_, _, amount, _)| amount).sum();
+        return total.saturating_sub(self.ante);
+    }
+
+    fn betting(&self, n_rounds: usize) -> String {
+        // checks and calls are "c", bets and raises "r" with the total put in the hand, folds "f"
+        let mut rounds: Vec<String> = vec![String::new(); n_rounds];
+        let mut totals: Vec<u32> = vec![0; self.n_players];
+        for (player, action, amount, turn) in self.actions.iter() {
+            totals[*player] += amount;
+            let text: String = match action {
+                Action::POST => continue,
+                Action::CHECK | Action::CALL => "c".to_string(),
+                Action::BET | Action::RAISE => format!("r{}", totals[*player].saturating_sub(self.ante)),
+                Action::FOLD => "f".to_string(),
+            };
+            rounds[turn.clone() as usize].push_str(&text);
+        }
+        return rounds.join("/");
+    }
+
+    pub fn matchstate(&self, board: &[u8], shown: &[(usize, Vec<u8>)]) -> String {
+        // hole cards of other players are only included once they're shown
+        let holes: Vec<String> = (0..self.n_players).map(|idx| {
+            match (idx == self.position, &self.hole_cards, shown.iter().find(|(player, _)| *player == idx)) {
+                (_, _, Some((_, cards))) => card_names(cards),
+                (true, Some(cards), None) => card_names(cards),
+                _ => String::new(),
+            }
+        }).collect();
+        let mut cards: String = holes.join("|");
+        let n_rounds: usize = match board.len() {
+            0 => 1,
+            3 => 2,
+            4 => 3,
+            _ => 4,
+        };
+        let n_rounds: usize = self.actions.iter().map(|(.., turn)| turn.clone() as usize + 1).fold(n_rounds, usize::max);
+        for (start, end) in [(0, 3), (3, 4), (4, 5)].iter().take(n_rounds - 1) {
+            cards.push('/');
+            cards.push_str(&card_names(&board[*start..*end]));
+        }
+        return format!("MATCHSTATE:{}:{}:{}:{}", self.position, self.hand_number, self.betting(n_rounds), cards);
+    }
+
+    pub fn to_play(&self, action: &AcpcAction, min_bet: u32) -> Play {
+        // raises too small to be a raise are played as calls
+        match action {
+            AcpcAction::FOLD => return Play::Fold,
+            AcpcAction::CALL => return Play::Bet(min_bet),
+            AcpcAction::RAISE(total) => {
+                let bet: u32 = total.saturating_sub(self.committed(self.position));
+                return Play::Bet(bet.max(min_bet));
+            },
+        }
+    }
+}
+
+pub fn parse_response(state: &str, line: &str) -> Option<AcpcAction> {
+    // responses repeat the state they answer, responses to other states are ignored
+    let action: &str = line.trim_end().strip_prefix(state)?.strip_prefix(':')?;
+    match action {
+        "f" => return Some(AcpcAction::FOLD),
+        "c" | "k" => return Some(AcpcAction::CALL),
+        _ => return action.strip_prefix('r').and_then(|total| total.parse().ok()).map(AcpcAction::RAISE),
+    }
+}
+
+pub fn game_definition(stacks: &[u32], blinds: [u32; 2]) -> String {
+    // ACPC game definition matching how pokersim deals holdem_nl, for configuring agents
+    let n_players: usize = stacks.len();
+    let mut blind_sizes: Vec<u32> = vec![0; n_players];
+    blind_sizes[0] = blinds[0];
+    blind_sizes[1] = blinds[1];
+    let join = |values: &[u32]| values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(" ");
+    return [
+        "GAMEDEF".to_string(),
+        "nolimit".to_string(),
+        format!("numPlayers = {}", n_players),
+        "numRounds = 4".to_string(),
+        format!("stack = {}", join(stacks)),
+        format!("blind = {}", join(&blind_sizes)),
+        "firstPlayer = 1 1 1 1".to_string(),
+        "numSuits = 4".to_string(),
+        "numRanks = 13".to_string(),
+        "numHoleCards = 2".to_string(),
+        "numBoardCards = 0 3 1 1".to_string(),
+        "END GAMEDEF".to_string(),
+    ].join("\n") + "\n";
+}
+
+pub struct AcpcConnection {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+    pub hand: AcpcHand,
+    n_hands: u64,
+}
+
+impl AcpcConnection {
+
+    pub fn accept(listener: &TcpListener, timeout: Option<Duration>) -> Result<AcpcConnection, ComsError> {
+        let (stream, addr) = listener.accept().map_err(|err| ComsError::Disconnected(err.to_string()))?;
+        stream.set_nodelay(true).map_err(|err| ComsError::Disconnected(err.to_string()))?;
+        let mut conn = AcpcConnection {
+            reader: BufReader::new(stream.try_clone().map_err(|err| ComsError::Disconnected(err.to_string()))?),
+            stream,
+            hand: AcpcHand::default(),
+            n_hands: 0,
+        };
+        let version: String = conn.read_line(timeout)?;
+        if !version.starts_with(ACPC_VERSION) {
+            return Err(ComsError::Handshake(format!("ACPC agent at {} sent {:?} instead of {}x.x", addr, version.trim_end(), ACPC_VERSION)));
+        }
+        return Ok(conn);
+    }
+
+    fn read_line(&mut self, timeout: Option<Duration>) -> Result<String, ComsError> {
+        self.stream.set_read_timeout(timeout).map_err(|err| ComsError::Disconnected(err.to_string()))?;
+        let mut line: String = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => return Err(ComsError::Disconnected("ACPC agent closed the connection".to_string())),
+            Ok(_) => return Ok(line),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => return Err(ComsError::Timeout(self.hand.position.to_string())),
+            Err(err) => return Err(ComsError::Disconnected(err.to_string())),
+        }
+    }
+
+    pub fn send_state(&mut self, state: &str) -> Result<(), ComsError> {
+        return write!(self.stream, "{}\r\n", state).map_err(|err| ComsError::Disconnected(err.to_string()));
+    }
+
+    pub fn new_hand(&mut self, cards: &[u8]) {
+        self.hand = AcpcHand { hand_number: self.n_hands, hole_cards: Some(cards.to_vec()), ..Default::default() };
+        self.n_hands += 1;
+    }
+
+    pub fn assign_position(&mut self, player_position: usize, n_players: usize) {
+        self.hand.position = player_position - 1;
+        self.hand.n_players = n_players;
+    }
+
+    pub fn play(&mut self, board: &[u8], min_bet: u32, timeout: Option<Duration>) -> Result<Play, ComsError> {
+        // sends the state and waits for the agent to answer it
+        let state: String = self.hand.matchstate(board, &[]);
+        self.send_state(&state)?;
+        let deadline: Option<Instant> = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let remaining: Option<Duration> = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1)));
+            let line: String = self.read_line(remaining)?;
+            if let Some(action) = parse_response(&state, &line) {
+                return Ok(self.hand.to_play(&action, min_bet));
+            }
+        }
+    }
+
+    pub fn notify(&mut self, msg: &Message) {
+        // actions are kept for the betting string, the final state goes out when the hand ends
+        match msg {
+            Message::ActionMessage { player, action, amount, turn, .. } => {
+                self.hand.record(*player, action.clone(), *amount, turn.clone());
+            },
+            Message::EndOfRoundMessage { board, showdown, .. } => {
+                let shown: Vec<(usize, Vec<u8>)> = showdown.iter().map(|hand| (hand.player, hand.cards.clone())).collect();
+                let state: String = self.hand.matchstate(board, &shown);
+                let _ = self.send_state(&state);
+            },
+            _ => (),
+        }
+    }
+}
+
+pub fn accept_acpc_players(config: &AcpcConfig, timeout: Option<Duration>) -> Result<HashMap<String, AcpcConnection>, ComsError> {
+    // listens on a port for each seat, like the ACPC dealer, and waits for every agent to connect
+    let mut listeners: Vec<(String, TcpListener)> = vec![];
+    for seat in config.seats.iter() {
+        let listener = TcpListener::bind((config.host.as_str(), seat.port))
+            .map_err(|_| ComsError::InvalidEndpoint(format!("{}:{}", config.host, seat.port)))?;
+        let port: u16 = listener.local_addr().map_err(|err| ComsError::Disconnected(err.to_string()))?.port();
+        println!("ACPC seat {} listening on {}:{}", seat.name, config.host, port);
+        listeners.push((seat.name.clone(), listener));
+    }
+    let mut connections: HashMap<String, AcpcConnection> = HashMap::new();
+    for (name, listener) in listeners.iter() {
+        connections.insert(name.clone(), AcpcConnection::accept(listener, timeout)?);
+    }
+    return Ok(connections);
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pokersim::player::BlindType;
+    use std::thread;
+
+    fn card(name: &str) -> u8 {
+        return Card::card_from_short_name(name).unwrap().to_int();
+    }
+
+    pub fn spawn_calling_agent(port: u16) -> thread::JoinHandle<Vec<String>> {
+        // ACPC agent that answers every state with a call and returns the states it saw
+        thread::spawn(move || {
+            let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            write!(writer, "VERSION:2.0.0\r\n").unwrap();
+            let mut states: Vec<String> = vec![];
+            // the dealer hangs up after the last hand, answers to final states can fail
+            for line in BufReader::new(stream).lines() {
+                let Ok(state) = line else {break};
+                let _ = write!(writer, "{}:c\r\n", state);
+                states.push(state);
+            }
+            states
+        })
+    }
+
+    fn example_hand() -> AcpcHand {
+        let mut hand = AcpcHand { hand_number: 3, position: 1, n_players: 3, hole_cards: Some(vec![card("Ah"), card("Kd")]), ante: 1, ..Default::default() };
+        for player in 0..3 {
+            hand.record(player, Action::POST, 1, Turn::HOLE);
+        }
+        hand.record(0, Action::POST, 50, Turn::HOLE);
+        hand.record(1, Action::POST, 100, Turn::HOLE);
+        hand.record(2, Action::RAISE, 300, Turn::HOLE);
+        hand.record(0, Action::FOLD, 0, Turn::HOLE);
+        hand.record(1, Action::CALL, 200, Turn::HOLE);
+        hand.record(1, Action::CHECK, 0, Turn::FLOP);
+        hand.record(2, Action::BET, 500, Turn::FLOP);
+        return hand;
+    }
+
+    #[test]
+    pub fn test_matchstate() {
+        let hand = example_hand();
+        let board: Vec<u8> = vec![card("2c"), card("7d"), card("Ts")];
+        assert_eq!(hand.matchstate(&board, &[]), "MATCHSTATE:1:3:r300fc/cr800:|AhKd|/2c7dTs");
+
+        let board: Vec<u8> = vec![card("2c"), card("7d"), card("Ts"), card("Jh"), card("3s")];
+        let shown = [(2, vec![card("Qs"), card("Qh")]), (1, vec![card("Ah"), card("Kd")])];
+        assert_eq!(hand.matchstate(&board, &shown), "MATCHSTATE:1:3:r300fc/cr800//:|AhKd|QsQh/2c7dTs/Jh/3s");
+    }
+
+    #[test]
+    pub fn test_parse_response() {
+        let state: &str = "MATCHSTATE:1:3:r300fc/cr800:|AhKd|/2c7dTs";
+        assert_eq!(parse_response(state, &format!("{}:c\r\n", state)), Some(AcpcAction::CALL));
+        assert_eq!(parse_response(state, &format!("{}:f", state)), Some(AcpcAction::FOLD));
+        assert_eq!(parse_response(state, &format!("{}:r2000", state)), Some(AcpcAction::RAISE(2000)));
+        assert_eq!(parse_response(state, "MATCHSTATE:1:2:cc:|AhKd|:c"), None);
+        assert_eq!(parse_response(state, &format!("{}:x", state)), None);
+
+        // raises are to the total put in the hand
+        let hand = example_hand();
+        assert_eq!(hand.committed(1), 300);
+        assert!(matches!(hand.to_play(&AcpcAction::RAISE(2000), 500), Play::Bet(1700)));
+        assert!(matches!(hand.to_play(&AcpcAction::RAISE(400), 500), Play::Bet(500)));
+        assert!(matches!(hand.to_play(&AcpcAction::CALL, 500), Play::Bet(500)));
+    }
+
+    #[test]
+    pub fn test_game_definition() {
+        let definition: String = game_definition(&[20000, 20000], [50, 100]);
+        assert!(definition.contains("numPlayers = 2\n"));
+        assert!(definition.contains("stack = 20000 20000\n"));
+        assert!(definition.contains("blind = 50 100\n"));
+    }
+
+    #[test]
+    pub fn test_accept_agent() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let agent = spawn_calling_agent(listener.local_addr().unwrap().port());
+        let mut conn = AcpcConnection::accept(&listener, Some(Duration::from_secs(5))).unwrap();
+        conn.new_hand(&[card("Ah"), card("Kd")]);
+        conn.assign_position(2, 2);
+        conn.notify(&Message::ActionMessage { player: 0, action: Action::POST, amount: 50, all_in: false, current_pot: 50, turn: Turn::HOLE, blind: Some(BlindType::Little) });
+        conn.notify(&Message::ActionMessage { player: 1, action: Action::POST, amount: 100, all_in: false, current_pot: 150, turn: Turn::HOLE, blind: Some(BlindType::Big) });
+        conn.notify(&Message::ActionMessage { player: 0, action: Action::CALL, amount: 50, all_in: false, current_pot: 200, turn: Turn::HOLE, blind: None });
+        assert!(matches!(conn.play(&[], 0, Some(Duration::from_secs(5))).unwrap(), Play::Bet(0)));
+        drop(conn);
+        assert_eq!(agent.join().unwrap(), vec!["MATCHSTATE:1:0:c:|AhKd"]);
+    }
+}
@@ -27,6 +27,10 @@ enum CardSuit {
     CLUB = 4,
 }
 
+// ranks and suits in card value and suit order
+const SHORT_VALUES: &str = "23456789TJQKA";
+const SHORT_SUITS: &str = "shdc";
+
 pub struct Card {
     card_value: CardValue, 
     card_suit: CardSuit
@@ -97,6 +101,22 @@ impl Card {
         }
         return card_val + ((suit_val - 1) * 13)
     }
+
+    pub fn short_name(&self) -> String {
+        // two character name used in hand histories, e.g. "Ah" or "Tc"
+        let card: usize = self.to_int() as usize;
+        let value: char = SHORT_VALUES.as_bytes()[(card - 1) % 13] as char;
+        let suit: char = SHORT_SUITS.as_bytes()[(card - 1) / 13] as char;
+        return format!("{}{}", value, suit);
+    }
+
+    pub fn card_from_short_name(name: &str) -> Option<Card> {
+        let mut chars = name.chars();
+        let value: u8 = SHORT_VALUES.find(chars.next()?)? as u8;
+        let suit: u8 = SHORT_SUITS.find(chars.next()?)? as u8;
+        if chars.next().is_some() {return None}
+        return Some(Card::card_from_int(&(value + 1 + suit * 13)))
+    }
 }
 
 impl fmt::Display for Card {
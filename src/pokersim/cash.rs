@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use super::coms::CashResult;
+use super::dealer::Dealer;
+use super::games::{HandResult, TableEvents};
+use super::mtt::Table;
+use super::player::{CashRequest, HoldemPlayer};
+use super::rules::GameRules;
+
+
+// Cash games: players buy in within a range and play a set number of hands at one table with a moving button.
+// Between hands busted players rebuy, short stacks top up and players sit out or come back. A player who
+// missed the big blind while sitting out waits for the big blind to reach their seat before being dealt in,
+// or posts the missed blinds to come back at once when the rules post missed blinds.
+// Results are tracked in big blinds per 100 hands, and again with every all in before the river
+// settled at the players' equity when the rules report or pay all in equity
+
+#[derive(Clone, Debug, Default)]
+pub struct CashConfig {
+    // smallest and largest stack a player can buy in for, top ups can't go past the largest
+    pub buy_in: [u32; 2],
+    pub hands: usize,
+    // busted players buy in again for the largest buy in
+    pub auto_rebuy: bool,
+    // stacks below the largest buy in are topped up to it between hands
+    pub auto_top_up: bool,
+    // players are sent a CashOptionsMessage between hands to rebuy, top up, sit out or come back
+    pub requests: bool,
+}
+
+impl CashConfig {
+    pub fn validate(&self, rules: &GameRules) -> Result<(), String> {
+        if self.buy_in[0] > self.buy_in[1] {
+            return Err(format!("the smallest buy in {} is larger than the largest {}", self.buy_in[0], self.buy_in[1]));
+        }
+        if self.buy_in[0] == 0 || self.buy_in[0] < rules.forced_bets() {
+            return Err(format!("a buy in of {} doesn't cover the forced bets of {}", self.buy_in[0], rules.forced_bets()));
+        }
+        if self.hands == 0 {
+            return Err("a cash game needs at least one hand".to_string());
+        }
+        return Ok(());
+    }
+}
+
+struct Account {
+    player: String,
+    hands: usize,
+    bought_in: u32,
+    // expected minus actual winnings of the all ins before the river
+    all_in_adjustment: f64,
+}
+
+pub struct CashGame<T> {
+    pub table: Table<T>,
+    // players sitting out keep their seat, true once the big blind passed them
+    sitting_out: Vec<Option<(T, bool)>>,
+    // busted players who didn't rebuy
+    left: Vec<T>,
+    // seats of returning players who post their missed blinds in the next hand
+    missed_blinds: Vec<usize>,
+    config: CashConfig,
+    rules: GameRules,
+    // in the order players sat down
+    accounts: Vec<Account>,
+    hands: usize,
+}
+
+impl<T: HoldemPlayer> CashGame<T> {
+
+    pub fn new(players: Vec<T>, rules: &GameRules, config: &CashConfig, dealer: Dealer) -> CashGame<T> {
+        // players sit in order, the first seat posts the small blind of the first hand
+        let n_seats: usize = players.len();
+        let mut table: Table<T> = Table::new(1, n_seats, dealer);
+        let accounts: Vec<Account> = players.iter().map(|player| Account { player: player.name(), hands: 0, bought_in: player.chips(), all_in_adjustment: 0.0 }).collect();
+        for (seat, player) in players.into_iter().enumerate() {
+            table.seats[seat] = Some(player);
+        }
+        table.button = n_seats.saturating_sub(1);
+        return CashGame {
+            table,
+            sitting_out: (0..n_seats).map(|_| None).collect(),
+            left: vec![],
+            missed_blinds: vec![],
+            config: config.clone(),
+            rules: rules.clone(),
+            accounts,
+            hands: 0,
+        }
+    }
+
+    pub fn hands(&self) -> usize {
+        return self.hands;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.hands >= self.config.hands || self.table.n_players() < 2;
+    }
+
+    pub fn is_sitting_out(&self, seat: usize) -> bool {
+        return self.sitting_out[seat].is_some();
+    }
+
+    pub fn play_hand(&mut self, events: Option<&dyn TableEvents>) {
+        for player in self.table.players() {
+            let name: String = player.name();
+            if let Some(account) = self.accounts.iter_mut().find(|account| account.player == name) {
+                account.hands += 1;
+            }
+        }
+        let result: HandResult = self.table.play_hand_with_missed_blinds(&self.rules, &self.missed_blinds, events);
+        if let Some(all_in_ev) = result.all_in_ev {
+            for (seat, player) in self.table.seats.iter().enumerate() {
+                let Some(player) = player else {continue};
+                let name: String = player.name();
+                if let Some(account) = self.accounts.iter_mut().find(|account| account.player == name) {
+                    account.all_in_adjustment += all_in_ev[seat] - result.winnings[seat] as f64;
+                }
+            }
+        }
+        self.missed_blinds.clear();
+        self.hands += 1;
+        if !self.is_finished() {
+            self.between_hands();
+        }
+    }
+
+    fn between_hands(&mut self) {
+        let forced_bets: u32 = self.rules.forced_bets();
+        let [min_buy_in, max_buy_in] = self.config.buy_in;
+        let n_seats: usize = self.table.seats.len();
+
+        let mut sit_out: Vec<bool> = vec![false; n_seats];
+        for (seat, sits_out) in sit_out.iter_mut().enumerate() {
+            let (player, sitting_out): (&mut T, bool) = match (&mut self.table.seats[seat], &mut self.sitting_out[seat]) {
+                (Some(player), _) => (player, false),
+                (None, Some((player, _))) => (player, true),
+                (None, None) => continue,
+            };
+            let request: CashRequest = match self.config.requests {
+                true => player.cash_request(player.chips(), self.config.buy_in, sitting_out),
+                false => CashRequest { add_chips: 0, sit_out: sitting_out },
+            };
+            *sits_out = request.sit_out;
+
+            // top ups stop at the largest buy in, a rebuy has to reach the smallest
+            let stack: u32 = player.chips();
+            let busted: bool = stack < forced_bets;
+            let mut add_chips: u32 = request.add_chips.min(max_buy_in.saturating_sub(stack));
+            if busted && stack + add_chips < min_buy_in {
+                add_chips = 0;
+            }
+            if self.config.auto_top_up || (self.config.auto_rebuy && busted) {
+                add_chips = max_buy_in.saturating_sub(stack);
+            }
+            if add_chips > 0 {
+                player.add_chips(add_chips);
+                let name: String = player.name();
+                if let Some(account) = self.accounts.iter_mut().find(|account| account.player == name) {
+                    account.bought_in += add_chips;
+                }
+            }
+        }
+
+        // busted players who didn't rebuy leave the table
+        for seat in 0..n_seats {
+            if self.table.seats[seat].as_ref().is_some_and(|player| player.chips() < forced_bets) {
+                self.left.push(self.table.seats[seat].take().unwrap());
+            }
+            if self.sitting_out[seat].as_ref().is_some_and(|(player, _)| player.chips() < forced_bets) {
+                self.left.push(self.sitting_out[seat].take().unwrap().0);
+            }
+        }
+
+        for (seat, sits_out) in sit_out.iter().enumerate() {
+            if *sits_out && let Some(player) = self.table.seats[seat].take() {
+                self.sitting_out[seat] = Some((player, false));
+            }
+        }
+        // returning players are dealt in unless they missed the big blind, then they wait for it or post it
+        let returning: Vec<usize> = (0..n_seats).filter(|seat| !sit_out[*seat] && self.sitting_out[*seat].is_some()).collect();
+        for seat in returning.iter() {
+            let missed: bool = self.sitting_out[*seat].as_ref().unwrap().1;
+            let big_blind: bool = self.table.hands_until_big_blind(*seat) == 0;
+            if missed && !big_blind && self.rules.post_missed_blinds {
+                self.missed_blinds.push(*seat);
+            }
+            if !missed || big_blind || self.rules.post_missed_blinds {
+                self.sit_in(*seat);
+            }
+        }
+        // nobody waits when there aren't enough players for a hand
+        if self.table.n_players() < 2 {
+            for seat in returning {
+                self.sit_in(seat);
+            }
+        }
+        // the next hand's big blind passes the players still sitting out in its seat
+        for seat in 0..n_seats {
+            if self.sitting_out[seat].is_some() && self.table.hands_until_big_blind(seat) == 0 {
+                self.sitting_out[seat].as_mut().unwrap().1 = true;
+            }
+        }
+    }
+
+    fn sit_in(&mut self, seat: usize) {
+        if let Some((player, _)) = self.sitting_out[seat].take() {
+            self.table.seats[seat] = Some(player);
+        }
+    }
+
+    pub fn results(&self) -> Vec<CashResult> {
+        // games without a big blind are measured in their forced bets
+        let big_blind: u32 = match self.rules.blinds[1] {
+            0 => self.rules.forced_bets().max(1),
+            big_blind => big_blind,
+        };
+        let chips: HashMap<String, u32> = self.table.players()
+            .chain(self.sitting_out.iter().flatten().map(|(player, _)| player))
+            .chain(self.left.iter())
+            .map(|player| (player.name(), player.chips()))
+            .collect();
+        let per_100 = |net: f64, hands: usize| match hands {
+            0 => 0.0,
+            hands => net / big_blind as f64 * 100.0 / hands as f64,
+        };
+        return self.accounts.iter().map(|account| {
+            let net: i32 = chips[&account.player] as i32 - account.bought_in as i32;
+            let all_in_net: f64 = net as f64 + account.all_in_adjustment;
+            CashResult {
+                player: account.player.clone(),
+                hands: account.hands,
+                bought_in: account.bought_in,
+                chips: chips[&account.player],
+                net,
+                bb_per_100: per_100(net as f64, account.hands),
+                all_in_net,
+                all_in_bb_per_100: per_100(all_in_net, account.hands),
+            }
+        }).collect();
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pokersim::coms::{Action, Message};
+    use crate::pokersim::dealer::standard_deck;
+    use crate::pokersim::equity::{equity, unseen_cards};
+    use crate::pokersim::games::{AllIn, HandEvaluation, HOLDEM_NL};
+    use crate::pokersim::player::BlindType;
+    use crate::pokersim::player::{Play, ScriptedPlayer};
+
+    fn test_game(chips: Vec<u32>, config: &CashConfig) -> CashGame<ScriptedPlayer> {
+        let rules = GameRules { blinds: [5, 10], ante: 0, ..GameRules::new(HOLDEM_NL).unwrap() };
+        let players: Vec<ScriptedPlayer> = chips.into_iter().enumerate()
+            .map(|(idx, chips)| ScriptedPlayer { name: format!("player_{}", idx), ..ScriptedPlayer::new(chips, vec![]) })
+            .collect();
+        return CashGame::new(players, &rules, config, Dealer::with_seed(2));
+    }
+
+    fn stacks(game: &CashGame<ScriptedPlayer>) -> Vec<Option<u32>> {
+        return game.table.seats.iter().map(|seat| seat.as_ref().map(|player| player.chips)).collect();
+    }
+
+    fn request(add_chips: u32, sit_out: bool) -> CashRequest {
+        return CashRequest { add_chips, sit_out };
+    }
+
+    #[test]
+    pub fn test_rebuys_and_top_ups() {
+        let config = CashConfig { buy_in: [50, 200], hands: 10, requests: true, ..Default::default() };
+        let rules = GameRules::new(HOLDEM_NL).unwrap();
+        assert_eq!(config.validate(&rules), Ok(()));
+        assert!(CashConfig { buy_in: [200, 50], ..config.clone() }.validate(&rules).is_err());
+        assert!(config.validate(&GameRules { blinds: [50, 100], ..rules.clone() }).is_err());
+        assert!(CashConfig { hands: 0, ..config.clone() }.validate(&rules).is_err());
+
+        let mut game = test_game(vec![100, 100, 100, 100], &config);
+        for (player, chips) in game.table.seats.iter_mut().flatten().zip([0, 150, 0, 3]) {
+            player.chips = chips;
+        }
+        // a rebuy for 100, a top up capped at the largest buy in, a rebuy below the smallest and no rebuy
+        let requests: [CashRequest; 4] = [request(100, false), request(100, false), request(20, false), request(0, false)];
+        for (player, request) in game.table.seats.iter_mut().flatten().zip(requests) {
+            player.cash_requests.push_back(request);
+        }
+        game.between_hands();
+        assert_eq!(stacks(&game), vec![Some(100), Some(200), None, None]);
+
+        let results: Vec<CashResult> = game.results();
+        assert_eq!(results.iter().map(|result| (result.bought_in, result.chips)).collect::<Vec<_>>(), vec![(200, 100), (150, 200), (100, 0), (100, 3)]);
+
+        // without asking, busted players rebuy and short stacks top up to the largest buy in
+        let mut game = test_game(vec![100, 100, 100], &CashConfig { buy_in: [50, 200], hands: 10, auto_rebuy: true, ..Default::default() });
+        game.table.seats[0].as_mut().unwrap().chips = 0;
+        game.between_hands();
+        assert_eq!(stacks(&game), vec![Some(200), Some(100), Some(100)]);
+        let mut game = test_game(vec![100, 100, 100], &CashConfig { buy_in: [50, 200], hands: 10, auto_top_up: true, ..Default::default() });
+        game.between_hands();
+        assert_eq!(stacks(&game), vec![Some(200), Some(200), Some(200)]);
+    }
+
+    #[test]
+    pub fn test_sit_out() {
+        let config = CashConfig { buy_in: [50, 1000], hands: 100, requests: true, ..Default::default() };
+        let mut game = test_game(vec![500; 4], &config);
+        // seat 1 posts the big blind of the first hand, seat 2 the big blind of the second
+        game.table.seats[2].as_mut().unwrap().cash_requests.extend([request(0, true), request(0, false), request(0, false), request(0, false)]);
+        game.play_hand(None);
+        assert!(game.is_sitting_out(2));
+        assert_eq!(game.table.n_players(), 3);
+
+        // the big blind passed seat 2, it can't come back before the big blind is its turn again
+        game.play_hand(None);
+        assert!(game.is_sitting_out(2));
+        let mut n_hands: usize = 0;
+        while game.is_sitting_out(2) {
+            game.play_hand(None);
+            n_hands += 1;
+        }
+        assert_eq!(n_hands, 2);
+        assert_eq!(game.table.hands_until_big_blind(2), 0);
+
+        // a player sitting out for a hand before their blinds comes straight back
+        game.table.seats[0].as_mut().unwrap().cash_requests.extend([request(0, true), request(0, false)]);
+        game.play_hand(None);
+        assert!(game.is_sitting_out(0));
+        game.play_hand(None);
+        assert!(!game.is_sitting_out(0));
+
+        let hands: Vec<usize> = game.results().iter().map(|result| result.hands).collect();
+        assert_eq!(hands, vec![game.hands() - 1, game.hands(), game.hands() - 3, game.hands()]);
+    }
+
+    #[test]
+    pub fn test_post_missed_blinds() {
+        let config = CashConfig { buy_in: [50, 1000], hands: 100, requests: true, ..Default::default() };
+        let mut game = test_game(vec![500; 4], &config);
+        game.rules.post_missed_blinds = true;
+        // seat 2 misses the big blind of the second hand and comes back for the fourth, under the gun
+        game.table.seats[2].as_mut().unwrap().cash_requests.extend([request(0, true), request(0, true), request(0, false)]);
+        for _ in 0..2 {
+            game.play_hand(None);
+            assert!(game.is_sitting_out(2));
+        }
+        game.play_hand(None);
+        assert!(!game.is_sitting_out(2));
+        game.play_hand(None);
+
+        // it posts a live big blind and a dead small blind as the third player of the hand
+        let posts: Vec<(usize, u32, Option<BlindType>)> = game.table.seats[2].as_ref().unwrap().notifications.iter().rev()
+            .skip_while(|msg| !matches!(msg, Message::EndOfRoundMessage { .. })).skip(1)
+            .take_while(|msg| !matches!(msg, Message::EndOfRoundMessage { .. }))
+            .filter_map(|msg| match msg {
+                Message::ActionMessage { player, action: Action::POST, amount, blind, .. } => Some((*player, *amount, *blind)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(posts.into_iter().rev().collect::<Vec<_>>(), vec![
+            (0, 5, Some(BlindType::Little)),
+            (1, 10, Some(BlindType::Big)),
+            (2, 10, Some(BlindType::Big)),
+            (2, 5, Some(BlindType::Dead)),
+        ]);
+        assert_eq!(game.table.players().map(|player| player.chips).sum::<u32>(), 2000);
+    }
+
+    #[test]
+    pub fn test_results() {
+        let config = CashConfig { buy_in: [100, 500], hands: 50, auto_rebuy: true, ..Default::default() };
+        let mut game = test_game(vec![200, 300, 500], &config);
+        while !game.is_finished() {
+            game.play_hand(None);
+        }
+        assert_eq!(game.hands(), 50);
+        let results: Vec<CashResult> = game.results();
+        // chips only change hands
+        assert_eq!(results.iter().map(|result| result.net).sum::<i32>(), 0);
+        for result in results {
+            assert_eq!(result.hands, 50);
+            assert_eq!(result.net, result.chips as i32 - result.bought_in as i32);
+            assert_eq!(result.bb_per_100, result.net as f64 / 10.0 * 100.0 / 50.0);
+            // the board is run once without equity, the all in results are the actual results
+            assert_eq!(result.all_in_net, result.net as f64);
+        }
+    }
+
+    #[test]
+    pub fn test_all_in_results() {
+        // aces are all in on the flop against kings and lose to a set on the river
+        let top: Vec<u8> = vec![13, 26, 12, 25, 27, 45, 34, 42, 38];
+        let mut dealer = Dealer::new();
+        dealer.stack_deck(0, top.iter().copied().chain((1..=52).filter(|card| !top.contains(card))).collect());
+        let rules = GameRules { blinds: [5, 10], all_in: AllIn::ReportEquity, ..GameRules::new(HOLDEM_NL).unwrap() };
+        let players: Vec<ScriptedPlayer> = vec![
+            ScriptedPlayer { name: "aces".to_string(), ..ScriptedPlayer::new(100, vec![Play::Bet(5), Play::Bet(90)]) },
+            ScriptedPlayer { name: "kings".to_string(), ..ScriptedPlayer::new(100, vec![Play::Bet(0), Play::Bet(90)]) },
+        ];
+        let mut game = CashGame::new(players, &rules, &CashConfig { buy_in: [100, 100], hands: 1, ..Default::default() }, dealer);
+        game.play_hand(None);
+
+        let unseen: Vec<u8> = unseen_cards(&standard_deck(), &top[..7]);
+        let shares: Vec<f64> = equity(&[vec![13, 26], vec![12, 25]], &top[4..7], &unseen, &HandEvaluation::Holdem);
+        let results: Vec<CashResult> = game.results();
+        assert_eq!(results.iter().map(|result| result.net).collect::<Vec<i32>>(), vec![-100, 100]);
+        for (result, share) in results.iter().zip(shares) {
+            assert!((result.all_in_net - (200.0 * share - 100.0)).abs() < 1e-9);
+            assert!((result.all_in_bb_per_100 - result.all_in_net * 10.0).abs() < 1e-9);
+        }
+        assert!(results[0].all_in_net > 0.0);
+    }
+}
@@ -1,7 +1,22 @@
 use serde::{Serialize, Deserialize};
 use serde_json;
 use zmq;
-// TODO: protobufs would be better for python/rust interop
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use std::cell::Cell;
+use super::hand_eval::HandCategory;
+use super::observer::{ObserverConfig, ZmqPublisher};
+use super::history::HandHistoryWriter;
+use super::hand_log::HandLogWriter;
+use super::recorder::HandRecorder;
+use super::rules::GameRules;
+use super::games::{AllIn, Rake, TableEvents};
+use super::player::BlindType;
+// JSON is the default encoding, clients can negotiate MessagePack for a compact binary encoding.
+// schema/message.schema.json describes the messages for generating bindings in other languages
 
 pub trait SendMessage {
     fn send();
@@ -11,19 +26,77 @@ pub trait RecvMessage {
     fn recieve(msg: &str) -> Self;
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Plays {
     BET,
     FOLD,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    // forced bets: antes and blinds
+    POST,
+    CHECK,
+    CALL,
+    BET,
+    RAISE,
+    FOLD,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlayerHand {
-    cards: [u8; 2],
+    cards: Vec<u8>,
     player_name: String,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShowdownHand {
+    pub player: usize,
+    pub cards: Vec<u8>,
+    pub hand_category: HandCategory,
+    pub hand_score: f64,
+    // 1 for the best hand shown, tied hands share a rank
+    pub hand_rank: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PotResult {
+    pub amount: u32,
+    pub winners: Vec<usize>,
+    // chips each winner takes from this pot, in the same order as winners
+    pub winnings: Vec<u32>,
+    // chips the house took from this pot, amount includes them
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub rake: u32,
+}
+
+fn is_zero(value: &u32) -> bool {
+    return *value == 0;
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Standing {
+    pub player: String,
+    // players knocked out in the same hand with the same stack share a finishing position
+    pub finishing_position: usize,
+    pub payout: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CashResult {
+    pub player: String,
+    pub hands: usize,
+    // chips brought to the table with the first buy in, rebuys and top ups
+    pub bought_in: u32,
+    pub chips: u32,
+    pub net: i32,
+    pub bb_per_100: f64,
+    // net with every all in before the river settled at the players' equity
+    pub all_in_net: f64,
+    pub all_in_bb_per_100: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Status {
     OK,
     ERROR,
@@ -38,15 +111,93 @@ impl Status {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum Turn {
     HOLE=0,
     FLOP=1,
     TURN=2,
     RIVER=3,
+    // seven card stud streets
+    THIRD=4,
+    FOURTH=5,
+    FIFTH=6,
+    SIXTH=7,
+    SEVENTH=8,
+    // betting rounds after each draw in draw games
+    DRAW1=9,
+    DRAW2=10,
+    DRAW3=11,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Encoding {
+    JSON,
+    // MessagePack with the same field names as the JSON encoding
+    MSGPACK,
+}
+
+impl Encoding {
+    pub fn encode(&self, msg: &Message) -> Vec<u8> {
+        return self.encode_any(msg);
+    }
+
+    fn encode_any<T: Serialize>(&self, msg: &T) -> Vec<u8> {
+        match self {
+            Encoding::JSON => return serde_json::to_vec(msg).unwrap(),
+            Encoding::MSGPACK => return rmp_serde::to_vec_named(msg).unwrap(),
+        }
+    }
+
+    pub fn encode_for_version(&self, msg: &Message, protocol_version: u32) -> Vec<u8> {
+        // messages that changed shape are sent the way the client's protocol version expects them
+        match msg {
+            Message::EndOfRoundMessage { winnings, .. } if protocol_version < 2 => return self.encode_any(&LegacyMessage::EndOfRoundMessage { winnings }),
+            Message::TurnMessage { current_players, current_position, current_pot, min_bet, max_bet, shared_cards, turn, .. } if protocol_version < 4 => {
+                return self.encode_any(&LegacyMessage::TurnMessage { current_players, current_position, current_pot, min_bet, max_bet, shared_cards, turn });
+            },
+            Message::ActionMessage { player, action, amount, all_in, current_pot, turn, .. } if protocol_version < 10 => {
+                return self.encode_any(&LegacyMessage::ActionMessage { player, action, amount, all_in, current_pot, turn });
+            },
+            _ => return self.encode(msg),
+        }
+    }
+
+    pub fn decode(&self, payload: &[u8]) -> Result<Message, ComsError> {
+        match self {
+            Encoding::JSON => return serde_json::from_slice(payload).map_err(|e| ComsError::Decode(e.to_string())),
+            Encoding::MSGPACK => return rmp_serde::from_slice(payload).map_err(|e| ComsError::Decode(e.to_string())),
+        }
+    }
+
+    pub fn decode_or_json(&self, payload: &[u8]) -> Result<Message, ComsError> {
+        // handshake messages are always JSON, so fall back to JSON for them
+        match self.decode(payload) {
+            Ok(msg) => return Ok(msg),
+            Err(err) => return Encoding::JSON.decode(payload).map_err(|_| err),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// version of the Message protocol, bumped whenever messages change
+// 1: EndOfRoundMessage only has the winnings
+// 2: EndOfRoundMessage has showdown details and is sent to every player
+// 3: ActionMessage is sent to every player after each action
+// 4: TurnMessage has the smallest legal raise
+// 5: UpCardsMessage shows the face up cards of stud games
+// 6: DrawMessage, DiscardMessage and DrawResultMessage play draw games
+// 7: LevelMessage and StandingsMessage report tournaments
+// 8: TableMoveMessage moves players between the tables of a multi-table tournament
+// 9: CashOptionsMessage, CashRequestMessage and CashResultsMessage play cash games
+// 10: ActionMessage names the forced bet a POST is for
+pub const PROTOCOL_VERSION: u32 = 10;
+// oldest protocol version pokersim can still talk to
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+// encodings pokersim can speak, the client's order of preference decides which is used
+pub const SUPPORTED_ENCODINGS: [Encoding; 2] = [Encoding::JSON, Encoding::MSGPACK];
+// time clients have to send their HelloMessage
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "message_type", content = "message")]
 pub enum Message {
 
@@ -56,7 +207,13 @@ pub enum Message {
 
     // Message for Table
     // Recv
-    StartGameMessage { players: Vec<String>, chips: Vec<u32> },
+    // rules replace the game pokersim was configured to deal, tables that leave them out get the configured game
+    StartGameMessage {
+        players: Vec<String>,
+        chips: Vec<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rules: Option<GameRules>,
+    },
 
     // pokersim sends RoundInitMessage and recieves a StatusMessage
     // Message for Table
@@ -66,11 +223,13 @@ pub enum Message {
     // pokersim sends a TurnMessage and recieves a PlayMessage
     // Message for a given player
     // Send
+    // a bet between min_bet and min_raise only calls, min_raise is max_bet when the player can't raise
     TurnMessage { 
         current_players: Vec<usize>, 
         current_position: usize, 
         current_pot: u32, 
         min_bet: u32, 
+        min_raise: u32,
         max_bet: u32,
         shared_cards: Vec<u8>, 
         turn: Turn 
@@ -79,11 +238,45 @@ pub enum Message {
     // Message for a given player
     // Recv
     PlayMessage { play_type: Plays, bet_amount: u32},
+
+    // pokersim sends an ActionMessage to every player after each action, including their own
+    // amount is the chips the player put in with this action, current_pot the pot after it
+    // Message for all players
+    // Send
+    ActionMessage {
+        player: usize,
+        action: Action,
+        amount: u32,
+        all_in: bool,
+        current_pot: u32,
+        turn: Turn,
+        // the forced bet a POST is for
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blind: Option<BlindType>,
+    },
     
-    // pokersim sends an EndOfRoundMesssge to send information about
+    // pokersim sends an EndOfRoundMesssge to every player and the table at the end of a hand
+    // winnings are each player's net chips for the hand, showdown holds the hands that were shown,
+    // mucked the players that reached showdown without showing, stacks are the chips after the hand
     // Message for a given player
     // Send
-    EndOfRoundMessage { winnings: Vec<i32> },
+    EndOfRoundMessage {
+        winnings: Vec<i32>,
+        board: Vec<u8>,
+        showdown: Vec<ShowdownHand>,
+        mucked: Vec<usize>,
+        pots: Vec<PotResult>,
+        stacks: Vec<u32>,
+        // taken from every player before the hand, stacks are after it
+        #[serde(default, skip_serializing_if = "is_zero")]
+        time_charge: u32,
+        // every board when the board was run more than once, board is the first run
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        runs: Vec<Vec<u8>>,
+        // each player's net expected winnings when players were all in before the river and the rules report or pay equity
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        all_in_ev: Vec<f64>,
+    },
     
     // Messages for Table
     // Recv
@@ -92,43 +285,785 @@ pub enum Message {
     // pokersim sends message when game is over (one player wins)
     // Send
     EndOfGameMessage,
+
+    // clients send ConnectMessage once after connecting to register their identity
+    // Recv
+    ConnectMessage,
+
+    // clients send DisconnectMessage before leaving so the server stops routing to them
+    // Recv
+    DisconnectMessage,
+
+    // clients send HelloMessage after connecting with their protocol version and capabilities
+    // Recv
+    HelloMessage { protocol_version: u32, game_variants: Vec<String>, encodings: Vec<Encoding> },
+
+    // pokersim replies to a HelloMessage with the negotiated protocol version, game and encoding
+    // Send
+    WelcomeMessage { protocol_version: u32, game_variant: String, encoding: Encoding },
+
+    // pokersim sends an ErrorMessage describing why a client was refused
+    // Send
+    ErrorMessage { reason: String },
+
+    // Events published to observers, along with ActionMessage, EndOfRoundMessage and EndOfGameMessage
+    // Publish
+    HandStartMessage { players: Vec<String>, stacks: Vec<u32>, blinds: [u32; 2], ante: u32 },
+
+    // cards are only published to observers in god view
+    // Publish
+    DealMessage { player: usize, cards: Option<Vec<u8>> },
+
+    // Publish
+    BoardMessage { turn: Turn, cards: Vec<u8> },
+
+    // face up cards of every player in stud games, empty for players who folded
+    // Publish
+    UpCardsMessage { turn: Turn, up_cards: Vec<Vec<u8>> },
+
+    // asks a player in a draw game which of their cards to throw away, turn is the betting round after the draw
+    // Expects DiscardMessage
+    DrawMessage { cards: Vec<u8>, turn: Turn },
+
+    // cards to throw away and replace, empty to stand pat
+    DiscardMessage { cards: Vec<u8> },
+
+    // Publish
+    DrawResultMessage { player: usize, n_cards: usize, turn: Turn },
+
+    // finishing_position counts from 1 for the winner
+    // Publish
+    EliminationMessage { player: String, finishing_position: usize },
+
+    // tournaments send a LevelMessage before the first hand of each blind level, level counts from 1.
+    // hands is the number of hands played at the level, 0 for the last level
+    // Message for every player
+    // Send
+    LevelMessage { level: usize, blinds: [u32; 2], ante: u32, hands: usize },
+
+    // a player moved to another table of a multi-table tournament, tables count from 1
+    // Message for the moved player
+    // Send
+    TableMoveMessage { player: String, from_table: usize, to_table: usize, seat: usize },
+
+    // final standings of a tournament sorted by finishing position, sent to the table and every player
+    // Send
+    StandingsMessage { standings: Vec<Standing> },
+
+    // asks a player between cash game hands whether to add chips or sit out, buy_in is the smallest and largest stack allowed
+    // Expects CashRequestMessage
+    CashOptionsMessage { stack: u32, buy_in: [u32; 2], sitting_out: bool },
+
+    // chips to rebuy or top up with, sit_out false returns a player who is sitting out
+    CashRequestMessage { add_chips: u32, sit_out: bool },
+
+    // net results of every player at the end of a cash game, sent to the table and every player
+    // Send
+    CashResultsMessage { results: Vec<CashResult> },
 }
 
+impl Message {
+    pub fn message_type(&self) -> &'static str {
+        // the message_type tag the message is serialized with
+        match self {
+            Message::ReadyForGameMessage => "ReadyForGameMessage",
+            Message::StartGameMessage { .. } => "StartGameMessage",
+            Message::RoundInitMessage { .. } => "RoundInitMessage",
+            Message::TurnMessage { .. } => "TurnMessage",
+            Message::PlayMessage { .. } => "PlayMessage",
+            Message::ActionMessage { .. } => "ActionMessage",
+            Message::EndOfRoundMessage { .. } => "EndOfRoundMessage",
+            Message::StatusMessage { .. } => "StatusMessage",
+            Message::EndOfGameMessage => "EndOfGameMessage",
+            Message::ConnectMessage => "ConnectMessage",
+            Message::DisconnectMessage => "DisconnectMessage",
+            Message::HelloMessage { .. } => "HelloMessage",
+            Message::WelcomeMessage { .. } => "WelcomeMessage",
+            Message::ErrorMessage { .. } => "ErrorMessage",
+            Message::HandStartMessage { .. } => "HandStartMessage",
+            Message::DealMessage { .. } => "DealMessage",
+            Message::BoardMessage { .. } => "BoardMessage",
+            Message::UpCardsMessage { .. } => "UpCardsMessage",
+            Message::DrawMessage { .. } => "DrawMessage",
+            Message::DiscardMessage { .. } => "DiscardMessage",
+            Message::DrawResultMessage { .. } => "DrawResultMessage",
+            Message::EliminationMessage { .. } => "EliminationMessage",
+            Message::LevelMessage { .. } => "LevelMessage",
+            Message::TableMoveMessage { .. } => "TableMoveMessage",
+            Message::StandingsMessage { .. } => "StandingsMessage",
+            Message::CashOptionsMessage { .. } => "CashOptionsMessage",
+            Message::CashRequestMessage { .. } => "CashRequestMessage",
+            Message::CashResultsMessage { .. } => "CashResultsMessage",
+        }
+    }
+
+    pub fn min_protocol_version(&self) -> u32 {
+        // oldest protocol version players are sent this message as a notification or asked with it
+        match self {
+            Message::EndOfRoundMessage { .. } => return 2,
+            Message::ActionMessage { .. } => return 3,
+            Message::UpCardsMessage { .. } => return 5,
+            Message::DrawMessage { .. } | Message::DiscardMessage { .. } | Message::DrawResultMessage { .. } => return 6,
+            Message::LevelMessage { .. } | Message::StandingsMessage { .. } => return 7,
+            Message::TableMoveMessage { .. } => return 8,
+            Message::CashOptionsMessage { .. } | Message::CashRequestMessage { .. } | Message::CashResultsMessage { .. } => return 9,
+            _ => return MIN_PROTOCOL_VERSION,
+        }
+    }
+}
+
+// messages in the shape older protocol versions expect, variants are named like the messages they stand in for
+#[derive(Serialize)]
+#[serde(tag = "message_type", content = "message")]
+#[allow(clippy::enum_variant_names)]
+enum LegacyMessage<'a> {
+    EndOfRoundMessage { winnings: &'a Vec<i32> },
+    // 3: TurnMessage has no min_raise
+    TurnMessage {
+        current_players: &'a Vec<usize>,
+        current_position: &'a usize,
+        current_pot: &'a u32,
+        min_bet: &'a u32,
+        max_bet: &'a u32,
+        shared_cards: &'a Vec<u8>,
+        turn: &'a Turn,
+    },
+    // 9: ActionMessage has no blind
+    ActionMessage {
+        player: &'a usize,
+        action: &'a Action,
+        amount: &'a u32,
+        all_in: &'a bool,
+        current_pot: &'a u32,
+        turn: &'a Turn,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub protocol_version: u32,
+    pub game_variant: String,
+    pub encoding: Encoding,
+    // every variant the client offered in its HelloMessage
+    pub game_variants: Vec<String>,
+}
+
+impl Session {
+    pub fn negotiate(hello: &Message, game_variant: &str) -> Result<Session, String> {
+        // newer clients are downgraded to our protocol version, clients older than we support are refused
+        let (protocol_version, game_variants, encodings) = match hello {
+            Message::HelloMessage { protocol_version, game_variants, encodings } => (*protocol_version, game_variants, encodings),
+            msg => return Err(format!("expected a HelloMessage, recieved {:?}", msg)),
+        };
+
+        if protocol_version < MIN_PROTOCOL_VERSION {
+            return Err(format!(
+                "protocol version {} is not supported, pokersim supports versions {} to {}",
+                protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+            ));
+        }
+        if !game_variants.iter().any(|variant| variant == game_variant) {
+            return Err(format!("game variant {} is not supported by the client, which supports {:?}", game_variant, game_variants));
+        }
+        let encoding: Encoding = match encodings.iter().find(|encoding| SUPPORTED_ENCODINGS.contains(encoding)) {
+            Some(encoding) => *encoding,
+            None => return Err(format!("no common encoding, client supports {:?} and pokersim supports {:?}", encodings, SUPPORTED_ENCODINGS)),
+        };
+
+        return Ok(Session { protocol_version: protocol_version.min(PROTOCOL_VERSION), game_variant: game_variant.to_string(), encoding, game_variants: game_variants.clone() })
+    }
+}
+
+// identity of the table controller client, messages sent with an empty header are routed here
+pub const TABLE_IDENTITY: &str = "table";
+
+#[derive(Debug)]
+pub enum ComsError {
+    Disconnected(String),
+    Zmq(zmq::Error),
+    Decode(String),
+    InvalidEndpoint(String),
+    Timeout(String),
+    Handshake(String),
+}
+
+impl fmt::Display for ComsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComsError::Disconnected(name) => write!(f, "client {} is disconnected", name),
+            ComsError::Zmq(err) => write!(f, "zmq error: {}", err),
+            ComsError::Decode(err) => write!(f, "could not decode message: {}", err),
+            ComsError::InvalidEndpoint(endpoint) => write!(f, "invalid endpoint: {}", endpoint),
+            ComsError::Timeout(name) => write!(f, "client {} did not reply in time", name),
+            ComsError::Handshake(reason) => write!(f, "handshake failed: {}", reason),
+        }
+    }
+}
+
+impl From<zmq::Error> for ComsError {
+    fn from(err: zmq::Error) -> ComsError {
+        ComsError::Zmq(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Endpoint {
+    // port None binds an ephemeral port chosen by the OS
+    Tcp { host: String, port: Option<u16> },
+    Ipc(String),
+    Inproc(String),
+}
+
+impl Default for Endpoint {
+    fn default() -> Endpoint {
+        return Endpoint::Tcp { host: "127.0.0.1".to_string(), port: Some(1212) }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Tcp { host, port: Some(port) } => write!(f, "tcp://{}:{}", host, port),
+            Endpoint::Tcp { host, port: None } => write!(f, "tcp://{}:*", host),
+            Endpoint::Ipc(path) => write!(f, "ipc://{}", path),
+            Endpoint::Inproc(name) => write!(f, "inproc://{}", name),
+        }
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = ComsError;
+
+    fn from_str(endpoint: &str) -> Result<Endpoint, ComsError> {
+        let invalid = || ComsError::InvalidEndpoint(endpoint.to_string());
+        let (transport, address) = endpoint.split_once("://").ok_or_else(invalid)?;
+        if address.is_empty() {return Err(invalid())}
+
+        match transport {
+            "tcp" => {
+                let (host, port) = address.rsplit_once(':').ok_or_else(invalid)?;
+                if host.is_empty() {return Err(invalid())}
+                let port: Option<u16> = match port {
+                    "*" | "0" => None,
+                    port => Some(port.parse().map_err(|_| invalid())?),
+                };
+                return Ok(Endpoint::Tcp { host: host.to_string(), port })
+            },
+            "ipc" => return Ok(Endpoint::Ipc(address.to_string())),
+            "inproc" => return Ok(Endpoint::Inproc(address.to_string())),
+            _ => return Err(invalid()),
+        }
+    }
+}
+
+// a message recieved from a client, Err when its payload couldn't be decoded
+type ClientMessage = Result<Message, ComsError>;
+
 pub struct ZmqSocketReply {
     socket: zmq::Socket,
+    // inproc clients have to connect through the context the server is bound in
+    context: zmq::Context,
+    // address the socket is bound to, with ephemeral ports resolved
+    endpoint: String,
+    // identities that have sent a ConnectMessage and not disconnected
+    clients: RefCell<HashSet<String>>,
+    // messages recieved from a client while waiting on a different client
+    pending: RefCell<HashMap<String, VecDeque<ClientMessage>>>,
+    // number of replies still owed by a client for requests that timed out, these are discarded
+    stale: RefCell<HashMap<String, usize>>,
+    // HelloMessages waiting to be negotiated, and the sessions negotiated with each client
+    hellos: RefCell<HashMap<String, Message>>,
+    sessions: RefCell<HashMap<String, Session>>,
+    // game events are published to observers when a publisher is bound
+    publisher: Option<ZmqPublisher>,
+    recorder: HandRecorder,
 }
 
 impl ZmqSocketReply {
 
-    pub fn new(socket: zmq::Socket) -> ZmqSocketReply {
-        return ZmqSocketReply { socket: socket }
+    pub fn new(context: zmq::Context, socket: zmq::Socket) -> ZmqSocketReply {
+        let endpoint: String = socket.get_last_endpoint().unwrap().unwrap_or_default();
+        return ZmqSocketReply {
+            socket,
+            context,
+            endpoint,
+            clients: RefCell::new(HashSet::new()),
+            pending: RefCell::new(HashMap::new()),
+            stale: RefCell::new(HashMap::new()),
+            hellos: RefCell::new(HashMap::new()),
+            sessions: RefCell::new(HashMap::new()),
+            publisher: None,
+            recorder: HandRecorder::new(),
+        }
+    }
+
+    pub fn bind_publisher(&mut self, config: &ObserverConfig) -> Result<(), ComsError> {
+        self.publisher = Some(ZmqPublisher::bind(&self.context, config)?);
+        return Ok(());
+    }
+
+    pub fn publisher_endpoint(&self) -> Option<&str> {
+        return self.publisher.as_ref().map(|publisher| publisher.endpoint());
+    }
+
+    pub fn write_hand_history(&mut self, writer: HandHistoryWriter) {
+        self.recorder.write_hand_history(writer);
+    }
+
+    pub fn write_hand_log(&mut self, writer: HandLogWriter) {
+        self.recorder.write_hand_log(writer);
+    }
+
+    pub fn publish(&self, msg: &Message) {
+        if let Some(publisher) = &self.publisher {
+            publisher.publish(msg);
+        }
+        self.recorder.publish(msg);
+    }
+
+    pub fn endpoint(&self) -> &str {
+        return &self.endpoint;
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        // the bound tcp port, useful when binding an ephemeral port
+        match self.endpoint.parse::<Endpoint>() {
+            Ok(Endpoint::Tcp { port, .. }) => return port,
+            _ => return None,
+        }
+    }
+
+    pub fn context(&self) -> zmq::Context {
+        return self.context.clone();
+    }
+
+    fn route(header_msg: &str) -> &str {
+        match header_msg {
+            "" => TABLE_IDENTITY,
+            name => name,
+        }
+    }
+
+    pub fn is_connected(&self, header_msg: &str) -> bool {
+        return self.clients.borrow().contains(ZmqSocketReply::route(header_msg));
+    }
+
+    pub fn connected_clients(&self) -> Vec<String> {
+        let mut clients: Vec<String> = self.clients.borrow().iter().cloned().collect();
+        clients.sort();
+        return clients;
+    }
+
+    fn recv_any(&self) -> Result<Option<(String, ClientMessage)>, ComsError> {
+        // recieve [identity, .., payload] from the router socket and track connection state,
+        // returns None when the message was handled here: a HelloMessage waiting to be
+        // negotiated or a late reply to a request that timed out.
+        // A payload that can't be decoded is the client's message as an Err, it only fails that client's request
+        let mut recv_multi = self.socket.recv_multipart(0)?;
+        if recv_multi.len() < 2 {
+            return Err(ComsError::Decode(format!("expected identity and payload frames, got {} frames", recv_multi.len())));
+        }
+        let payload: Vec<u8> = recv_multi.pop().unwrap();
+        let identity: String = String::from_utf8_lossy(&recv_multi.swap_remove(0)).into_owned();
+        let msg: Message = match self.encoding(&identity).decode_or_json(&payload) {
+            Ok(msg) => msg,
+            Err(err) => {
+                self.clients.borrow_mut().insert(identity.clone());
+                if self.take_stale(&identity) {return Ok(None)}
+                return Ok(Some((identity, Err(err))));
+            },
+        };
+
+        match msg {
+            Message::DisconnectMessage => {
+                self.clients.borrow_mut().remove(&identity);
+                self.pending.borrow_mut().remove(&identity);
+                self.stale.borrow_mut().remove(&identity);
+                self.hellos.borrow_mut().remove(&identity);
+                self.sessions.borrow_mut().remove(&identity);
+            },
+            Message::ConnectMessage => {self.clients.borrow_mut().insert(identity.clone());},
+            Message::HelloMessage { .. } => {
+                self.clients.borrow_mut().insert(identity.clone());
+                self.hellos.borrow_mut().insert(identity, msg);
+                return Ok(None);
+            },
+            _ => {
+                self.clients.borrow_mut().insert(identity.clone());
+                if self.take_stale(&identity) {return Ok(None)}
+            },
+        }
+        return Ok(Some((identity, Ok(msg))));
+    }
+
+    fn take_stale(&self, identity: &str) -> bool {
+        // true when the message is a late reply to a request that timed out, it's discarded
+        if let Some(owed) = self.stale.borrow_mut().get_mut(identity) && *owed > 0 {
+            *owed -= 1;
+            return true;
+        }
+        return false;
+    }
+
+    fn poll_recv(&self, deadline: Option<Instant>) -> Result<bool, ComsError> {
+        // wait until a message can be recieved, returns false once the deadline has passed
+        loop {
+            let timeout_ms: i64 = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()).as_millis() as i64,
+                None => -1,
+            };
+            if self.socket.poll(zmq::POLLIN, timeout_ms)? > 0 {
+                return Ok(true);
+            }
+            // poll can return slightly before the timeout
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(false);
+            }
+        }
+    }
+
+    pub fn wait_for_clients(&self, identities: &[String]) -> Result<(), ComsError> {
+        // block until every identity has connected
+        while !identities.iter().all(|name| self.is_connected(name)) {
+            let Some((identity, msg)) = self.recv_any()? else {continue};
+            match msg {
+                Ok(Message::ConnectMessage | Message::DisconnectMessage) => (),
+                _ => self.pending.borrow_mut().entry(identity).or_default().push_back(msg),
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn negotiate(&self, header_msg: &str, game_variant: &str, timeout: Option<Duration>) -> Result<Session, ComsError> {
+        // wait for the client's HelloMessage, then reply with a WelcomeMessage or an ErrorMessage
+        let identity = ZmqSocketReply::route(header_msg);
+        let deadline: Option<Instant> = timeout.map(|t| Instant::now() + t);
+        while !self.hellos.borrow().contains_key(identity) {
+            if !self.poll_recv(deadline)? {
+                let reason = format!("no HelloMessage recieved from {} before the handshake timed out", identity);
+                let _ = self.try_send_message(&Message::ErrorMessage { reason: reason.clone() }, identity);
+                return Err(ComsError::Handshake(reason));
+            }
+            let Some((sender, msg)) = self.recv_any()? else {continue};
+            match msg {
+                Ok(Message::ConnectMessage) => continue,
+                Ok(Message::DisconnectMessage) if sender == identity => return Err(ComsError::Disconnected(sender)),
+                Ok(Message::DisconnectMessage) => continue,
+                _ => self.pending.borrow_mut().entry(sender).or_default().push_back(msg),
+            }
+        }
+
+        let hello: Message = self.hellos.borrow_mut().remove(identity).unwrap();
+        match Session::negotiate(&hello, game_variant) {
+            Ok(session) => {
+                let msg = Message::WelcomeMessage {
+                    protocol_version: session.protocol_version,
+                    game_variant: session.game_variant.clone(),
+                    encoding: session.encoding,
+                };
+                self.try_send_message(&msg, identity)?;
+                self.sessions.borrow_mut().insert(identity.to_string(), session.clone());
+                return Ok(session);
+            },
+            Err(reason) => {
+                self.try_send_message(&Message::ErrorMessage { reason: reason.clone() }, identity)?;
+                return Err(ComsError::Handshake(reason));
+            },
+        }
+    }
+
+    pub fn switch_variant(&self, header_msg: &str, game_variant: &str) -> Result<Session, String> {
+        // a negotiated client can play any other variant it offered
+        let identity = ZmqSocketReply::route(header_msg);
+        let mut sessions = self.sessions.borrow_mut();
+        let session: &mut Session = match sessions.get_mut(identity) {
+            Some(session) => session,
+            None => return Err(format!("{} has not negotiated a session", identity)),
+        };
+        if !session.game_variants.iter().any(|variant| variant == game_variant) {
+            return Err(format!("game variant {} is not supported by {}, which supports {:?}", game_variant, identity, session.game_variants));
+        }
+        session.game_variant = game_variant.to_string();
+        return Ok(session.clone());
+    }
+
+    pub fn session(&self, header_msg: &str) -> Option<Session> {
+        return self.sessions.borrow().get(ZmqSocketReply::route(header_msg)).cloned();
+    }
+
+    pub fn protocol_version(&self, header_msg: &str) -> u32 {
+        // clients that haven't negotiated are assumed to speak the current protocol
+        match self.sessions.borrow().get(ZmqSocketReply::route(header_msg)) {
+            Some(session) => return session.protocol_version,
+            None => return PROTOCOL_VERSION,
+        }
+    }
+
+    fn encoding(&self, identity: &str) -> Encoding {
+        // clients use JSON until they have negotiated an encoding
+        match self.sessions.borrow().get(identity) {
+            Some(session) => return session.encoding,
+            None => return Encoding::JSON,
+        }
+    }
+
+    pub fn try_send_message(&self, msg: &Message, header_msg: &str) -> Result<(), ComsError> {
+        let identity = ZmqSocketReply::route(header_msg);
+        if !self.is_connected(identity) {
+            return Err(ComsError::Disconnected(identity.to_string()));
+        }
+        let payload: Vec<u8> = self.encoding(identity).encode_for_version(msg, self.protocol_version(identity));
+        match self.socket.send_multipart([identity.as_bytes(), &payload], 0) {
+            Ok(()) => return Ok(()),
+            // router_mandatory reports peers that are not (or no longer) connected
+            Err(zmq::Error::EHOSTUNREACH) => {
+                self.clients.borrow_mut().remove(identity);
+                return Err(ComsError::Disconnected(identity.to_string()));
+            },
+            Err(err) => return Err(ComsError::Zmq(err)),
+        }
+    }
+
+    pub fn try_recv_message(&self, header_msg: &str) -> Result<Message, ComsError> {
+        return self.try_recv_message_timeout(header_msg, None);
+    }
+
+    pub fn try_recv_message_timeout(&self, header_msg: &str, timeout: Option<Duration>) -> Result<Message, ComsError> {
+        // recieve the next message from a given client, queueing messages from other clients.
+        // If no message arrives within the timeout the client's eventual reply is discarded
+        let identity = ZmqSocketReply::route(header_msg);
+        let queued = self.pending.borrow_mut().get_mut(identity).and_then(|q| q.pop_front());
+        if let Some(msg) = queued {
+            return msg;
+        }
+
+        let deadline: Option<Instant> = timeout.map(|t| Instant::now() + t);
+        loop {
+            if !self.poll_recv(deadline)? {
+                *self.stale.borrow_mut().entry(identity.to_string()).or_default() += 1;
+                return Err(ComsError::Timeout(identity.to_string()));
+            }
+            let Some((sender, msg)) = self.recv_any()? else {continue};
+            match msg {
+                Ok(Message::ConnectMessage) => continue,
+                Ok(Message::DisconnectMessage) if sender == identity => return Err(ComsError::Disconnected(sender)),
+                Ok(Message::DisconnectMessage) => continue,
+                _ if sender == identity => return msg,
+                _ => self.pending.borrow_mut().entry(sender).or_default().push_back(msg),
+            }
+        }
+    }
+
+    pub fn try_send_recv_message(&self, msg: Message, header_msg: &str) -> Result<Message, ComsError> {
+        return self.try_send_recv_message_timeout(msg, header_msg, None);
+    }
+
+    pub fn try_send_recv_message_timeout(&self, msg: Message, header_msg: &str, timeout: Option<Duration>) -> Result<Message, ComsError> {
+        self.try_send_message(&msg, header_msg)?;
+        return self.try_recv_message_timeout(header_msg, timeout);
+    }
+
+    pub fn send_message(&self, msg: Message, header_msg: &str) {
+        // send a message that doesn't expect a reply
+        match self.try_send_message(&msg, header_msg) {
+            Ok(()) => (),
+            Err(err) => panic!("Failed to send {:?}: {}", msg, err),
+        }
     }
 
     pub fn send_recv_message(&self, msg: Message, header_msg: &str) -> Message {
         // send message and recieve response
-        let msg_str = serde_json::to_string(&msg).unwrap();
-        self.socket.send_multipart([header_msg, &msg_str], 0).unwrap();
-        let mut recv_multi = self.socket.recv_multipart(0).unwrap();
-        let response: Vec<u8> = recv_multi.pop().unwrap();
-        let rep_msg: Message = serde_json::from_str(&String::from_utf8(response).unwrap()).unwrap();
-        return rep_msg;
+        match self.try_send_recv_message(msg, header_msg) {
+            Ok(rep_msg) => return rep_msg,
+            Err(err) => panic!("Failed to exchange message with {:?}: {}", ZmqSocketReply::route(header_msg), err),
+        }
+    }
+}
+
+impl TableEvents for ZmqSocketReply {
+    fn publish(&self, msg: &Message) {
+        ZmqSocketReply::publish(self, msg);
+    }
+
+    fn log_deck(&self, seed: u64, deck: &[u8]) {
+        self.recorder.log_deck(seed, deck);
+    }
+
+    fn log_rake(&self, rake: &Rake) {
+        self.recorder.log_rake(rake);
+    }
+
+    fn log_all_in(&self, all_in: AllIn) {
+        self.recorder.log_all_in(all_in);
+    }
+
+    fn end_hand(&self, msg: &Message) {
+        // the table controller replies with a StatusMessage, which is ignored for now
+        let _reply = self.send_recv_message(msg.clone(), "");
     }
 }
 
 pub fn zmq_init () -> ZmqSocketReply {
-    // This app is running as request/response, so start with a request to initialize a game
+    return zmq_bind(&Endpoint::default()).unwrap();
+}
+
+pub fn zmq_bind(endpoint: &Endpoint) -> Result<ZmqSocketReply, ComsError> {
+    // Each client connects a DEALER socket with its player name as identity,
+    // the ROUTER socket routes every message to the matching client
     let ctx = zmq::Context::new();
-    let socket = ctx.socket(zmq::REQ).unwrap();
-    socket.bind("tcp://127.0.0.1:1212").unwrap();
+    let socket = ctx.socket(zmq::ROUTER)?;
+    socket.set_router_mandatory(true)?;
+    socket.set_heartbeat_ivl(1_000)?;
+    socket.set_heartbeat_timeout(5_000)?;
+    socket.set_linger(1_000)?;
+    socket.bind(&endpoint.to_string())?;
     
-    return ZmqSocketReply::new(socket);
+    return Ok(ZmqSocketReply::new(ctx, socket));
+}
+
+pub struct ZmqClient {
+    socket: zmq::Socket,
+    // switched to the negotiated encoding once a WelcomeMessage is recieved
+    encoding: Cell<Encoding>,
+}
+
+impl ZmqClient {
+
+    pub fn connect(endpoint: &str, identity: &str) -> ZmqClient {
+        // client side of the router/dealer pair, used by rust bots and table controllers
+        return ZmqClient::connect_with_context(&zmq::Context::new(), endpoint, identity);
+    }
+
+    pub fn connect_with_context(ctx: &zmq::Context, endpoint: &str, identity: &str) -> ZmqClient {
+        // inproc endpoints are only reachable through the server's context
+        let socket = ctx.socket(zmq::DEALER).unwrap();
+        socket.set_identity(identity.as_bytes()).unwrap();
+        socket.set_linger(100).unwrap();
+        socket.connect(endpoint).unwrap();
+        let client = ZmqClient { socket, encoding: Cell::new(Encoding::JSON) };
+        client.send_message(&Message::ConnectMessage);
+        return client;
+    }
+
+    pub fn hello(&self, game_variants: &[&str], encodings: &[Encoding]) {
+        // start the handshake, the server replies with a WelcomeMessage or an ErrorMessage.
+        // encodings are in order of preference
+        let msg = Message::HelloMessage {
+            protocol_version: PROTOCOL_VERSION,
+            game_variants: game_variants.iter().map(|variant| variant.to_string()).collect(),
+            encodings: encodings.to_vec(),
+        };
+        self.send_message(&msg);
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        return self.encoding.get();
+    }
+
+    pub fn send_message(&self, msg: &Message) {
+        self.socket.send(self.encoding.get().encode(msg), 0).unwrap();
+    }
+
+    pub fn recv_message(&self) -> Message {
+        let response: Vec<u8> = self.socket.recv_bytes(0).unwrap();
+        let msg: Message = self.encoding.get().decode_or_json(&response).unwrap();
+        if let Message::WelcomeMessage { encoding, .. } = msg {
+            self.encoding.set(encoding);
+        }
+        return msg;
+    }
+}
+
+impl Drop for ZmqClient {
+    fn drop(&mut self) {
+        let _ = self.socket.send(self.encoding.get().encode(&Message::DisconnectMessage), zmq::DONTWAIT);
+    }
 }
 
 
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use super::*;
-    use serial_test::serial;
+    use crate::pokersim::rules::variant_names;
+    use std::thread;
+
+    pub fn test_server(name: &str) -> ZmqSocketReply {
+        // each test binds its own inproc endpoint so tests can run in parallel
+        return zmq_bind(&Endpoint::Inproc(name.to_string())).unwrap();
+    }
+
+    pub fn spawn_table(server: &ZmqSocketReply, players: Vec<String>, chips: Vec<u32>) -> thread::JoinHandle<()> {
+        return spawn_table_with_rules(server, players, chips, None);
+    }
+
+    pub fn spawn_table_with_rules(server: &ZmqSocketReply, players: Vec<String>, chips: Vec<u32>, rules: Option<GameRules>) -> thread::JoinHandle<()> {
+        // table controller that starts a game with the given players and acknowledges everything else
+        let (ctx, endpoint) = (server.context(), server.endpoint().to_string());
+        thread::spawn(move || {
+            let client = ZmqClient::connect_with_context(&ctx, &endpoint, TABLE_IDENTITY);
+            let variant: &str = rules.as_ref().map_or("holdem_nl", |rules| rules.variant.as_str());
+            client.hello(&["holdem_nl", variant], &[Encoding::JSON]);
+            loop {
+                match client.recv_message() {
+                    Message::WelcomeMessage { .. } => (),
+                    Message::ErrorMessage { reason } => panic!("table was refused: {}", reason),
+                    Message::ReadyForGameMessage => {
+                        client.send_message(&Message::StartGameMessage { players: players.clone(), chips: chips.clone(), rules: rules.clone() });
+                    },
+                    Message::EndOfGameMessage => {
+                        client.send_message(&Message::StatusMessage { status: Status::OK });
+                        return;
+                    },
+                    _ => client.send_message(&Message::StatusMessage { status: Status::OK }),
+                }
+            }
+        })
+    }
+
+    pub fn spawn_calling_bot(server: &ZmqSocketReply, name: &str) -> thread::JoinHandle<()> {
+        // bot that always calls the minimum bet, in any variant
+        let (ctx, endpoint, name) = (server.context(), server.endpoint().to_string(), name.to_string());
+        thread::spawn(move || {
+            let client = ZmqClient::connect_with_context(&ctx, &endpoint, &name);
+            client.hello(&variant_names(), &[Encoding::MSGPACK, Encoding::JSON]);
+            loop {
+                match client.recv_message() {
+                    Message::ErrorMessage { .. } => return,
+                    Message::TurnMessage { min_bet, .. } => {
+                        client.send_message(&Message::PlayMessage { play_type: Plays::BET, bet_amount: min_bet });
+                    },
+                    // rebuys for the largest buy in when short
+                    Message::CashOptionsMessage { stack, buy_in, .. } => {
+                        let add_chips: u32 = if stack < buy_in[0] {buy_in[1] - stack} else {0};
+                        client.send_message(&Message::CashRequestMessage { add_chips, sit_out: false });
+                    },
+                    Message::EndOfGameMessage => return,
+                    _ => (),
+                }
+            }
+        })
+    }
+
+    pub fn spawn_old_calling_bot(server: &ZmqSocketReply, name: &str, protocol_version: u32) -> thread::JoinHandle<Vec<String>> {
+        // calling bot on an older protocol, reads raw JSON and returns the message_type of every message it recieved
+        let (ctx, endpoint, name) = (server.context(), server.endpoint().to_string(), name.to_string());
+        thread::spawn(move || {
+            let client = ZmqClient::connect_with_context(&ctx, &endpoint, &name);
+            client.send_message(&Message::HelloMessage { protocol_version, game_variants: variant_names().iter().map(|variant| variant.to_string()).collect(), encodings: vec![Encoding::JSON] });
+            let mut message_types: Vec<String> = vec![];
+            loop {
+                let payload: serde_json::Value = serde_json::from_slice(&client.socket.recv_bytes(0).unwrap()).unwrap();
+                let message_type: String = payload["message_type"].as_str().unwrap().to_string();
+                if message_type == "TurnMessage" {
+                    let min_bet: u32 = payload["message"]["min_bet"].as_u64().unwrap() as u32;
+                    client.send_message(&Message::PlayMessage { play_type: Plays::BET, bet_amount: min_bet });
+                }
+                let done: bool = message_type == "EndOfGameMessage" || message_type == "ErrorMessage";
+                message_types.push(message_type);
+                if done {return message_types}
+            }
+        })
+    }
 
     #[test]
     pub fn print_all_messages() {
@@ -138,26 +1073,450 @@ mod tests {
         let max_bet: u32 = 200;
         let shared_cards: Vec<u8> = vec![1, 7, 3, 50];
         let current_pot: u32 = 10000;
-        let msg = Message::TurnMessage{ current_players, current_position, current_pot, min_bet, max_bet, shared_cards, turn: Turn::HOLE };
+        let msg = Message::TurnMessage{ current_players, current_position, current_pot, min_bet, min_raise: max_bet, max_bet, shared_cards, turn: Turn::HOLE };
 
         let ser_msg = serde_json::to_string(&msg).unwrap();
         println!("{}", ser_msg);
 
         let players: Vec<String> = vec!["player_1".to_string(), "player_2".to_string()];
         let chips: Vec<u32> = vec![1000, 1000];
-        let msg = Message::StartGameMessage{ players, chips };
+        let msg = Message::StartGameMessage{ players, chips, rules: None };
         let ser_msg = serde_json::to_string(&msg).unwrap();
         println!("{}", ser_msg);
     }
 
     #[test]
-    #[serial]
     pub fn test_zmq_init() {
         let msg = Message::ReadyForGameMessage;
-        let header: &str = "test_header";
-        let zmq_socket = zmq_init();
+        let header: &str = "";
+        let zmq_socket = test_server("test_zmq_init");
+        let table = spawn_table(&zmq_socket, vec!["player_1".to_string()], vec![1000]);
+        zmq_socket.wait_for_clients(&[TABLE_IDENTITY.to_string()]).unwrap();
+
         let reply: Message = zmq_socket.send_recv_message(msg, header);
-        println!("{:?}", reply);
+        match reply {
+            Message::StartGameMessage { players, chips, .. } => {
+                assert_eq!(players, vec!["player_1".to_string()]);
+                assert_eq!(chips, vec![1000]);
+            },
+            _ => panic!("Expected a StartGameMessage, recieved {:?}", reply),
+        }
+
+        zmq_socket.send_recv_message(Message::EndOfGameMessage, header);
+        table.join().unwrap();
+    }
+
+    #[test]
+    pub fn test_zmq_routing() {
+        let zmq_socket = test_server("test_zmq_routing");
+        let names: Vec<String> = vec!["player_1".to_string(), "player_2".to_string()];
+        let bots: Vec<thread::JoinHandle<()>> = names.iter().map(|name| spawn_calling_bot(&zmq_socket, name)).collect();
+        zmq_socket.wait_for_clients(&names).unwrap();
+        assert_eq!(zmq_socket.connected_clients(), names);
+
+        for (idx, name) in names.iter().enumerate() {
+            let min_bet: u32 = 100 * (idx as u32 + 1);
+            let msg = Message::TurnMessage {
+                current_players: vec![0, 1],
+                current_position: idx,
+                current_pot: 0,
+                min_bet,
+                min_raise: 2 * min_bet,
+                max_bet: 1000,
+                shared_cards: vec![],
+                turn: Turn::HOLE,
+            };
+            match zmq_socket.send_recv_message(msg, name) {
+                Message::PlayMessage { bet_amount, .. } => assert_eq!(bet_amount, min_bet),
+                reply => panic!("Expected a PlayMessage, recieved {:?}", reply),
+            }
+        }
+
+        for name in names.iter() {
+            zmq_socket.send_message(Message::EndOfGameMessage, name);
+        }
+        for bot in bots {
+            bot.join().unwrap();
+        }
+    }
+
+    #[test]
+    pub fn test_zmq_disconnect() {
+        let zmq_socket = test_server("test_zmq_disconnect");
+        let name: String = "player_1".to_string();
+        let client = ZmqClient::connect_with_context(&zmq_socket.context(), zmq_socket.endpoint(), &name);
+        zmq_socket.wait_for_clients(&[name.clone()]).unwrap();
+        drop(client);
+
+        match zmq_socket.try_recv_message(&name) {
+            Err(ComsError::Disconnected(client_name)) => assert_eq!(client_name, name),
+            reply => panic!("Expected a disconnect, recieved {:?}", reply),
+        }
+        assert!(!zmq_socket.is_connected(&name));
+        assert!(zmq_socket.try_send_message(&Message::EndOfGameMessage, &name).is_err());
+    }
+
+    #[test]
+    pub fn test_parse_endpoint() {
+        assert_eq!("tcp://127.0.0.1:1212".parse::<Endpoint>().unwrap(), Endpoint::default());
+        assert_eq!("tcp://localhost:*".parse::<Endpoint>().unwrap(), Endpoint::Tcp { host: "localhost".to_string(), port: None });
+        assert_eq!("ipc:///tmp/pokersim.ipc".parse::<Endpoint>().unwrap(), Endpoint::Ipc("/tmp/pokersim.ipc".to_string()));
+        assert_eq!("inproc://table_1".parse::<Endpoint>().unwrap(), Endpoint::Inproc("table_1".to_string()));
+
+        for endpoint in ["127.0.0.1:1212", "tcp://127.0.0.1", "tcp://:1212", "tcp://127.0.0.1:port", "udp://127.0.0.1:1212", "inproc://"] {
+            assert!(endpoint.parse::<Endpoint>().is_err(), "{} should not parse", endpoint);
+        }
+
+        for endpoint in [Endpoint::default(), Endpoint::Ipc("/tmp/pokersim.ipc".to_string()), Endpoint::Inproc("table_1".to_string())] {
+            assert_eq!(endpoint.to_string().parse::<Endpoint>().unwrap(), endpoint);
+        }
+    }
+
+    #[test]
+    pub fn test_ephemeral_port() {
+        let zmq_socket = zmq_bind(&Endpoint::Tcp { host: "127.0.0.1".to_string(), port: None }).unwrap();
+        let port: u16 = zmq_socket.port().unwrap();
+        assert_ne!(port, 0);
+        assert_eq!(zmq_socket.endpoint(), format!("tcp://127.0.0.1:{}", port));
+
+        // tcp clients don't need the server's context
+        let name: String = "player_1".to_string();
+        let _client = ZmqClient::connect(zmq_socket.endpoint(), &name);
+        zmq_socket.wait_for_clients(&[name]).unwrap();
+    }
+
+    #[test]
+    pub fn test_ipc_endpoint() {
+        let path = std::env::temp_dir().join(format!("pokersim_test_{}.ipc", std::process::id()));
+        let zmq_socket = zmq_bind(&Endpoint::Ipc(path.to_str().unwrap().to_string())).unwrap();
+        assert_eq!(zmq_socket.port(), None);
+
+        let name: String = "player_1".to_string();
+        let _client = ZmqClient::connect(zmq_socket.endpoint(), &name);
+        zmq_socket.wait_for_clients(&[name]).unwrap();
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    pub fn test_zmq_timeout() {
+        let zmq_socket = test_server("test_zmq_timeout");
+        let name: String = "player_1".to_string();
+        let client = ZmqClient::connect_with_context(&zmq_socket.context(), zmq_socket.endpoint(), &name);
+        zmq_socket.wait_for_clients(&[name.clone()]).unwrap();
+
+        let timeout = Some(Duration::from_millis(20));
+        match zmq_socket.try_send_recv_message_timeout(Message::ReadyForGameMessage, &name, timeout) {
+            Err(ComsError::Timeout(client_name)) => assert_eq!(client_name, name),
+            reply => panic!("Expected a timeout, recieved {:?}", reply),
+        }
+
+        // the late reply to the first request is discarded, the second reply is returned
+        client.recv_message();
+        client.send_message(&Message::StatusMessage { status: Status::ERROR });
+        zmq_socket.try_send_message(&Message::ReadyForGameMessage, &name).unwrap();
+        client.recv_message();
+        client.send_message(&Message::StatusMessage { status: Status::OK });
+        match zmq_socket.try_recv_message_timeout(&name, Some(Duration::from_secs(5))) {
+            Ok(Message::StatusMessage { status: Status::OK }) => (),
+            reply => panic!("Expected an OK StatusMessage, recieved {:?}", reply),
+        }
+    }
+
+    fn hello(protocol_version: u32, game_variants: &[&str], encodings: Vec<Encoding>) -> Message {
+        let game_variants: Vec<String> = game_variants.iter().map(|variant| variant.to_string()).collect();
+        return Message::HelloMessage { protocol_version, game_variants, encodings };
+    }
+
+    #[test]
+    pub fn test_session_negotiate() {
+        let session = Session::negotiate(&hello(PROTOCOL_VERSION, &["holdem_nl"], vec![Encoding::JSON]), "holdem_nl").unwrap();
+        assert_eq!(session, Session { protocol_version: PROTOCOL_VERSION, game_variant: "holdem_nl".to_string(), encoding: Encoding::JSON, game_variants: vec!["holdem_nl".to_string()] });
+
+        // newer clients are downgraded
+        let session = Session::negotiate(&hello(PROTOCOL_VERSION + 1, &["holdem_nl"], vec![Encoding::JSON]), "holdem_nl").unwrap();
+        assert_eq!(session.protocol_version, PROTOCOL_VERSION);
+
+        let refused = [
+            hello(MIN_PROTOCOL_VERSION - 1, &["holdem_nl"], vec![Encoding::JSON]),
+            hello(PROTOCOL_VERSION, &["omaha"], vec![Encoding::JSON]),
+            hello(PROTOCOL_VERSION, &["holdem_nl"], vec![]),
+            Message::ReadyForGameMessage,
+        ];
+        for msg in refused.iter() {
+            assert!(Session::negotiate(msg, "holdem_nl").is_err(), "{:?} should be refused", msg);
+        }
+    }
+
+    #[test]
+    pub fn test_end_of_round_protocol_version() {
+        // a table on protocol version 1 only gets the winnings at the end of a hand
+        let zmq_socket = test_server("test_end_of_round_protocol_version");
+        let client = ZmqClient::connect_with_context(&zmq_socket.context(), zmq_socket.endpoint(), TABLE_IDENTITY);
+        client.send_message(&hello(1, &["holdem_nl"], vec![Encoding::JSON]));
+        zmq_socket.wait_for_clients(&[TABLE_IDENTITY.to_string()]).unwrap();
+        zmq_socket.negotiate("", "holdem_nl", Some(Duration::from_secs(5))).unwrap();
+        assert!(matches!(client.recv_message(), Message::WelcomeMessage { protocol_version: 1, .. }));
+
+        let msg = Message::EndOfRoundMessage {
+            winnings: vec![-100, 100],
+            board: vec![1, 14, 52, 20, 33],
+            showdown: vec![],
+            mucked: vec![0],
+            pots: vec![PotResult { amount: 200, winners: vec![1], winnings: vec![200], rake: 0 }],
+            stacks: vec![900, 1100],
+            time_charge: 0,
+            runs: vec![],
+            all_in_ev: vec![],
+        };
+        zmq_socket.try_send_message(&msg, "").unwrap();
+        let payload: serde_json::Value = serde_json::from_slice(&client.socket.recv_bytes(0).unwrap()).unwrap();
+        assert_eq!(payload, serde_json::json!({"message_type": "EndOfRoundMessage", "message": {"winnings": [-100, 100]}}));
+
+        // newer protocol versions get the whole message
+        assert_eq!(Encoding::JSON.decode(&Encoding::JSON.encode_for_version(&msg, 2)).unwrap(), msg);
+    }
+
+    #[test]
+    pub fn test_turn_protocol_version() {
+        // clients before protocol version 4 aren't sent the smallest raise
+        let msg = Message::TurnMessage {
+            current_players: vec![0, 1],
+            current_position: 1,
+            current_pot: 30,
+            min_bet: 10,
+            min_raise: 30,
+            max_bet: 990,
+            shared_cards: vec![],
+            turn: Turn::HOLE,
+        };
+        let payload: serde_json::Value = serde_json::from_slice(&Encoding::JSON.encode_for_version(&msg, 3)).unwrap();
+        assert_eq!(payload["message"].get("min_raise"), None);
+        assert_eq!(payload["message"]["max_bet"], 990);
+        assert_eq!(Encoding::MSGPACK.decode(&Encoding::MSGPACK.encode_for_version(&msg, 4)).unwrap(), msg);
+    }
+
+    #[test]
+    pub fn test_action_protocol_version() {
+        // clients before protocol version 10 aren't told which forced bet a post is for
+        let msg = Message::ActionMessage { player: 2, action: Action::POST, amount: 40, all_in: false, current_pot: 70, turn: Turn::HOLE, blind: Some(BlindType::Straddle) };
+        let payload: serde_json::Value = serde_json::from_slice(&Encoding::JSON.encode_for_version(&msg, 9)).unwrap();
+        assert_eq!(payload["message"].get("blind"), None);
+        assert_eq!(payload["message"]["action"], "POST");
+        assert_eq!(payload["message"]["amount"], 40);
+        assert_eq!(Encoding::MSGPACK.decode(&Encoding::MSGPACK.encode_for_version(&msg, 10)).unwrap(), msg);
+    }
+
+    #[test]
+    pub fn test_zmq_handshake() {
+        let zmq_socket = test_server("test_zmq_handshake");
+        let names: Vec<String> = vec!["player_1".to_string(), "player_2".to_string()];
+        let ctx = zmq_socket.context();
+        let good = ZmqClient::connect_with_context(&ctx, zmq_socket.endpoint(), &names[0]);
+        let old = ZmqClient::connect_with_context(&ctx, zmq_socket.endpoint(), &names[1]);
+        good.hello(&["holdem_nl"], &[Encoding::JSON]);
+        old.send_message(&hello(MIN_PROTOCOL_VERSION - 1, &["holdem_nl"], vec![Encoding::JSON]));
+        zmq_socket.wait_for_clients(&names).unwrap();
+
+        let session = zmq_socket.negotiate(&names[0], "holdem_nl", Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(zmq_socket.session(&names[0]), Some(session));
+        match good.recv_message() {
+            Message::WelcomeMessage { protocol_version, game_variant, encoding } => {
+                assert_eq!(protocol_version, PROTOCOL_VERSION);
+                assert_eq!(game_variant, "holdem_nl");
+                assert_eq!(encoding, Encoding::JSON);
+            },
+            msg => panic!("Expected a WelcomeMessage, recieved {:?}", msg),
+        }
+
+        assert!(matches!(zmq_socket.negotiate(&names[1], "holdem_nl", Some(Duration::from_secs(5))), Err(ComsError::Handshake(_))));
+        assert_eq!(zmq_socket.session(&names[1]), None);
+        match old.recv_message() {
+            Message::ErrorMessage { reason } => assert!(reason.contains("protocol version")),
+            msg => panic!("Expected an ErrorMessage, recieved {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_undecodable_payload() {
+        // a payload that can't be decoded only fails the request of the client that sent it
+        let zmq_socket = test_server("test_undecodable_payload");
+        let names: Vec<String> = vec!["bad_client".to_string(), "good_client".to_string()];
+        let bad = ZmqClient::connect_with_context(&zmq_socket.context(), zmq_socket.endpoint(), &names[0]);
+        let good = ZmqClient::connect_with_context(&zmq_socket.context(), zmq_socket.endpoint(), &names[1]);
+        zmq_socket.wait_for_clients(&names).unwrap();
+
+        bad.socket.send(&b"not a message"[..], 0).unwrap();
+        good.send_message(&Message::PlayMessage { play_type: Plays::BET, bet_amount: 10 });
+        assert_eq!(zmq_socket.try_recv_message(&names[1]).unwrap(), Message::PlayMessage { play_type: Plays::BET, bet_amount: 10 });
+        assert!(matches!(zmq_socket.try_recv_message(&names[0]), Err(ComsError::Decode(_))));
+
+        // the client's next message is recieved as usual
+        bad.send_message(&Message::PlayMessage { play_type: Plays::FOLD, bet_amount: 0 });
+        assert_eq!(zmq_socket.try_recv_message(&names[0]).unwrap(), Message::PlayMessage { play_type: Plays::FOLD, bet_amount: 0 });
+    }
+
+    #[test]
+    pub fn test_zmq_handshake_timeout() {
+        let zmq_socket = test_server("test_zmq_handshake_timeout");
+        let name: String = "player_1".to_string();
+        let client = ZmqClient::connect_with_context(&zmq_socket.context(), zmq_socket.endpoint(), &name);
+        zmq_socket.wait_for_clients(&[name.clone()]).unwrap();
+
+        assert!(zmq_socket.negotiate(&name, "holdem_nl", Some(Duration::from_millis(20))).is_err());
+        assert!(matches!(client.recv_message(), Message::ErrorMessage { .. }));
+    }
+
+    pub fn all_messages() -> Vec<Message> {
+        // one of each message, used to check encodings and the published schema
+        return vec![
+            Message::ReadyForGameMessage,
+            Message::StartGameMessage { players: vec!["player_1".to_string(), "player_2".to_string()], chips: vec![1000, 2000], rules: Some(GameRules::default()) },
+            Message::RoundInitMessage { ante: 10, blinds: [50, 100], n_players: 2, position: 1 },
+            Message::TurnMessage {
+                current_players: vec![0, 1],
+                current_position: 1,
+                current_pot: 300,
+                min_bet: 100,
+                min_raise: 200,
+                max_bet: 900,
+                shared_cards: vec![1, 14, 52],
+                turn: Turn::FLOP,
+            },
+            Message::PlayMessage { play_type: Plays::BET, bet_amount: 100 },
+            Message::ActionMessage { player: 1, action: Action::RAISE, amount: 300, all_in: false, current_pot: 450, turn: Turn::FLOP, blind: None },
+            Message::ActionMessage { player: 2, action: Action::POST, amount: 4, all_in: false, current_pot: 7, turn: Turn::HOLE, blind: Some(BlindType::Straddle) },
+            Message::EndOfRoundMessage {
+                winnings: vec![-100, 95],
+                board: vec![1, 14, 52, 20, 33],
+                showdown: vec![ShowdownHand { player: 1, cards: vec![13, 26], hand_category: HandCategory::TWOPAIR, hand_score: 11201.12, hand_rank: 1 }],
+                mucked: vec![0],
+                pots: vec![PotResult { amount: 200, winners: vec![1], winnings: vec![195], rake: 5 }],
+                stacks: vec![900, 1095],
+                time_charge: 1,
+                runs: vec![vec![1, 14, 52, 20, 33], vec![1, 14, 52, 7, 45]],
+                all_in_ev: vec![-48.5, 43.5],
+            },
+            Message::StatusMessage { status: Status::ERROR },
+            Message::EndOfGameMessage,
+            Message::ConnectMessage,
+            Message::DisconnectMessage,
+            Message::HelloMessage { protocol_version: PROTOCOL_VERSION, game_variants: vec!["holdem_nl".to_string()], encodings: vec![Encoding::MSGPACK, Encoding::JSON] },
+            Message::WelcomeMessage { protocol_version: PROTOCOL_VERSION, game_variant: "holdem_nl".to_string(), encoding: Encoding::MSGPACK },
+            Message::ErrorMessage { reason: "protocol version 0 is not supported".to_string() },
+            Message::HandStartMessage { players: vec!["player_1".to_string(), "player_2".to_string()], stacks: vec![1000, 2000], blinds: [50, 100], ante: 10 },
+            Message::DealMessage { player: 0, cards: Some(vec![13, 26]) },
+            Message::BoardMessage { turn: Turn::TURN, cards: vec![1, 14, 52, 20] },
+            Message::UpCardsMessage { turn: Turn::FOURTH, up_cards: vec![vec![5, 40], vec![], vec![13, 12]] },
+            Message::DrawMessage { cards: vec![1, 2, 3, 4, 5], turn: Turn::DRAW1 },
+            Message::DiscardMessage { cards: vec![2, 5] },
+            Message::DrawResultMessage { player: 1, n_cards: 2, turn: Turn::DRAW1 },
+            Message::EliminationMessage { player: "player_2".to_string(), finishing_position: 2 },
+            Message::LevelMessage { level: 2, blinds: [50, 100], ante: 10, hands: 20 },
+            Message::TableMoveMessage { player: "player_2".to_string(), from_table: 3, to_table: 1, seat: 4 },
+            Message::StandingsMessage { standings: vec![
+                Standing { player: "player_1".to_string(), finishing_position: 1, payout: 1300 },
+                Standing { player: "player_2".to_string(), finishing_position: 2, payout: 700 },
+            ] },
+            Message::CashOptionsMessage { stack: 0, buy_in: [400, 1000], sitting_out: false },
+            Message::CashRequestMessage { add_chips: 1000, sit_out: false },
+            Message::CashResultsMessage { results: vec![
+                CashResult { player: "player_1".to_string(), hands: 200, bought_in: 2000, chips: 2500, net: 500, bb_per_100: 2.5, all_in_net: 420.5, all_in_bb_per_100: 2.1 },
+                CashResult { player: "player_2".to_string(), hands: 150, bought_in: 1000, chips: 500, net: -500, bb_per_100: -3.33, all_in_net: -420.5, all_in_bb_per_100: -2.8 },
+            ] },
+        ];
+    }
+
+    #[test]
+    pub fn test_encoding_round_trip() {
+        for msg in all_messages() {
+            for encoding in SUPPORTED_ENCODINGS {
+                let payload: Vec<u8> = encoding.encode(&msg);
+                assert_eq!(encoding.decode(&payload).unwrap(), msg, "{:?} round trip", encoding);
+            }
+        }
+
+        // MessagePack is more compact than JSON
+        for msg in all_messages() {
+            assert!(Encoding::MSGPACK.encode(&msg).len() < Encoding::JSON.encode(&msg).len(), "{:?}", msg);
+        }
+        assert!(Encoding::MSGPACK.decode(b"not msgpack").is_err());
+    }
+
+    #[test]
+    pub fn test_message_schema() {
+        let schema: serde_json::Value = serde_json::from_str(include_str!("../../schema/message.schema.json")).unwrap();
+        let variants: Vec<&str> = schema["oneOf"].as_array().unwrap().iter()
+            .map(|variant| variant["$ref"].as_str().unwrap().trim_start_matches("#/$defs/"))
+            .collect();
+        // every message in the schema has an example
+        let messages = all_messages();
+        for variant in variants.iter() {
+            assert!(messages.iter().any(|msg| msg.message_type() == *variant), "{} has no example", variant);
+        }
+
+        // every field in the schema is sent by one of the examples
+        let mut sent: Vec<(&str, String)> = vec![];
+        for msg in messages {
+            let message_type: &str = msg.message_type();
+            assert!(variants.contains(&message_type), "{} is missing from the schema", message_type);
+
+            // the fields of each message match the schema
+            let definition = &schema["$defs"][message_type]["properties"];
+            assert_eq!(definition["message_type"]["const"], message_type);
+            let value: serde_json::Value = serde_json::to_value(&msg).unwrap();
+            assert_eq!(value["message_type"], message_type);
+            // optional fields can be left out, required ones can't
+            let fields: Vec<&String> = match value.get("message") {
+                Some(message) => message.as_object().unwrap().keys().collect(),
+                None => vec![],
+            };
+            let (schema_fields, required): (Vec<&String>, Vec<&str>) = match definition.get("message") {
+                Some(message) => (
+                    message["properties"].as_object().unwrap().keys().collect(),
+                    message["required"].as_array().map_or(vec![], |required| required.iter().map(|field| field.as_str().unwrap()).collect()),
+                ),
+                None => (vec![], vec![]),
+            };
+            assert!(fields.iter().all(|field| schema_fields.contains(field)), "{} fields {:?} aren't all in the schema", message_type, fields);
+            assert!(required.iter().all(|field| fields.iter().any(|sent| sent.as_str() == *field)), "{} is missing required fields", message_type);
+            sent.extend(fields.into_iter().map(|field| (message_type, field.clone())));
+        }
+        for variant in variants {
+            if let Some(properties) = schema["$defs"][variant]["properties"]["message"]["properties"].as_object() {
+                for field in properties.keys() {
+                    assert!(sent.contains(&(variant, field.clone())), "no {} example sends {}", variant, field);
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_zmq_msgpack() {
+        let zmq_socket = test_server("test_zmq_msgpack");
+        let name: String = "player_1".to_string();
+        let client = ZmqClient::connect_with_context(&zmq_socket.context(), zmq_socket.endpoint(), &name);
+        client.hello(&["holdem_nl"], &[Encoding::MSGPACK, Encoding::JSON]);
+        zmq_socket.wait_for_clients(&[name.clone()]).unwrap();
+
+        let session = zmq_socket.negotiate(&name, "holdem_nl", Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(session.encoding, Encoding::MSGPACK);
+        assert!(matches!(client.recv_message(), Message::WelcomeMessage { encoding: Encoding::MSGPACK, .. }));
+        assert_eq!(client.encoding(), Encoding::MSGPACK);
+
+        let msg = Message::TurnMessage {
+            current_players: vec![0, 1],
+            current_position: 0,
+            current_pot: 0,
+            min_bet: 50,
+            min_raise: 100,
+            max_bet: 1000,
+            shared_cards: vec![],
+            turn: Turn::HOLE,
+        };
+        zmq_socket.try_send_message(&msg, &name).unwrap();
+        assert_eq!(client.recv_message(), msg);
+        client.send_message(&Message::PlayMessage { play_type: Plays::FOLD, bet_amount: 0 });
+        assert_eq!(zmq_socket.try_recv_message(&name).unwrap(), Message::PlayMessage { play_type: Plays::FOLD, bet_amount: 0 });
     }
 
 }
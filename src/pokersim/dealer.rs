@@ -1,33 +1,136 @@
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::rng;
+use rand::{rng, Rng, SeedableRng};
 
 
 #[derive(Debug)]
 pub struct Dealer{
     card_idx: usize,
     pub deck: Vec<u8>,
+    // cards in the deck before shuffling, the full 52 unless a game removes some
+    cards: Vec<u8>,
+    // every shuffle draws a new seed, the deck order of a hand can be dealt again from it
+    pub seed: u64,
+    rng: StdRng,
+    // deck order used instead of the next shuffle
+    stacked: Option<Vec<u8>>,
+    // cards thrown away in draw games, shuffled into a new stub when the deck runs out
+    discards: Vec<u8>,
 }
 
 impl Dealer {
 
     pub fn new() -> Dealer {
-        let deck: Vec<u8> = (1..=52).collect();
-        Self{ card_idx: 0, deck }
+        return Dealer::with_rng(StdRng::from_rng(&mut rng()));
+    }
+
+    pub fn with_seed(seed: u64) -> Dealer {
+        // the same seed deals the same hands
+        return Dealer::with_rng(StdRng::seed_from_u64(seed));
+    }
+
+    fn with_rng(rng: StdRng) -> Dealer {
+        let deck: Vec<u8> = standard_deck();
+        Self{ card_idx: 0, cards: deck.clone(), deck, seed: 0, rng, stacked: None, discards: vec![] }
+    }
+
+    pub fn fork(&mut self) -> Dealer {
+        // a dealer for another table, seeded dealers fork the same dealers
+        return Dealer::with_seed(self.rng.random());
+    }
+
+    pub fn use_cards(&mut self, cards: Vec<u8>) {
+        // deals only these cards from the next shuffle on
+        if self.cards == cards {return}
+        self.cards = cards;
+        self.deck = self.cards.clone();
+    }
+
+    pub fn use_short_deck(&mut self) {
+        self.use_cards(short_deck());
+    }
+
+    pub fn stack_deck(&mut self, seed: u64, deck: Vec<u8>) {
+        // the next shuffle deals this deck order, used to replay hands
+        if deck.len() != self.cards.len() {panic!("a stacked deck needs {} cards, got {}", self.cards.len(), deck.len())}
+        self.stacked = Some(deck);
+        self.seed = seed;
     }
 
     pub fn shuffle(&mut self) -> () {
         self.card_idx = 0;
-        let mut rng = rng();
-        self.deck.shuffle(&mut rng);
+        self.discards.clear();
+        if let Some(deck) = self.stacked.take() {
+            self.deck = deck;
+            return;
+        }
+        self.seed = self.rng.random();
+        self.deck = self.cards.clone();
+        self.deck.shuffle(&mut StdRng::seed_from_u64(self.seed));
+    }
+
+    pub fn discard(&mut self, cards: &[u8]) {
+        self.discards.extend_from_slice(cards);
+    }
+
+    pub fn draw(&mut self, discards: &[u8]) -> Vec<u8> {
+        // replaces thrown away cards, they are mucked after the replacements are dealt so a stub that runs out
+        // doesn't give them back. Only when the stub and the muck are too small are they shuffled in first
+        let short: bool = self.cards_left() + self.discards.len() < discards.len();
+        if short {self.discard(discards)}
+        let cards: Vec<u8> = (0..discards.len()).map(|_| self.next_card()).collect();
+        if !short {self.discard(discards)}
+        return cards;
+    }
+
+    fn reshuffle_discards(&mut self) {
+        // the stub is seeded from the hand's seed, so a hand dealt again draws the same cards
+        if self.discards.is_empty() {panic!("dealer is out of cards")}
+        let mut stub: Vec<u8> = std::mem::take(&mut self.discards);
+        stub.shuffle(&mut StdRng::seed_from_u64(self.seed.wrapping_add(self.deck.len() as u64)));
+        self.deck.extend(stub);
     }
 
     pub fn next_card(&mut self) -> u8 {
-        if self.card_idx >= 51 {panic!("dealer is out of cards")}
+        if self.card_idx >= self.deck.len() {self.reshuffle_discards()}
         let card: u8 = self.deck[self.card_idx];
         self.card_idx += 1;
         return card
     }
 
+    pub fn cards_left(&self) -> usize {
+        // cards left in the deck, not counting discards
+        return self.deck.len() - self.card_idx;
+    }
+
+    pub fn deal_runs(&mut self, board: &[u8], n_runs: usize, n_cards: usize) -> Vec<Vec<u8>> {
+        // deals the rest of a board n_runs times from the same deck, each run adds n_cards to the board
+        return (0..n_runs).map(|_| {
+            let mut run: Vec<u8> = board.to_vec();
+            run.extend((0..n_cards).map(|_| self.next_card()));
+            run
+        }).collect();
+    }
+
+}
+
+pub fn standard_deck() -> Vec<u8> {
+    return (1..=52).collect();
+}
+
+pub fn short_deck() -> Vec<u8> {
+    // twos through fives are removed
+    return (1..=52).filter(|card| (card - 1) % 13 >= 4).collect();
+}
+
+pub fn kuhn_deck() -> Vec<u8> {
+    // jack, queen and king of spades
+    return vec![10, 11, 12];
+}
+
+pub fn leduc_deck() -> Vec<u8> {
+    // jack, queen and king of spades and hearts
+    return vec![10, 11, 12, 23, 24, 25];
 }
 
 #[cfg(test)]
@@ -47,4 +150,72 @@ mod tests {
         
     }
 
+    #[test]
+    pub fn test_seeded_shuffle() {
+        let mut dealer = Dealer::with_seed(7);
+        let mut other = Dealer::with_seed(7);
+        dealer.shuffle();
+        other.shuffle();
+        assert_eq!(dealer.deck, other.deck);
+        assert_eq!(dealer.seed, other.seed);
+
+        // a hand can be dealt again from its deck order
+        let deck: Vec<u8> = dealer.deck.clone();
+        dealer.shuffle();
+        assert_ne!(dealer.deck, deck);
+        let mut stacked = Dealer::new();
+        stacked.stack_deck(other.seed, deck.clone());
+        stacked.shuffle();
+        assert_eq!(stacked.deck, deck);
+        assert_eq!(stacked.next_card(), deck[0]);
+    }
+
+    #[test]
+    pub fn test_short_deck() {
+        let mut dealer = Dealer::with_seed(5);
+        dealer.use_short_deck();
+        dealer.shuffle();
+        assert_eq!(dealer.deck.len(), 36);
+        let mut deck: Vec<u8> = dealer.deck.clone();
+        deck.sort();
+        assert_eq!(deck, short_deck());
+        for _ in 0..36 {
+            assert!((dealer.next_card() - 1) % 13 >= 4);
+        }
+    }
+
+    #[test]
+    pub fn test_deal_runs() {
+        let mut dealer = Dealer::with_seed(3);
+        dealer.shuffle();
+        let board: Vec<u8> = (0..3).map(|_| dealer.next_card()).collect();
+        let runs: Vec<Vec<u8>> = dealer.deal_runs(&board, 2, 2);
+        assert_eq!(runs, vec![
+            [board.clone(), dealer.deck[3..5].to_vec()].concat(),
+            [board.clone(), dealer.deck[5..7].to_vec()].concat(),
+        ]);
+        assert_eq!(dealer.cards_left(), 45);
+    }
+
+    #[test]
+    pub fn test_reshuffle_discards() {
+        let mut dealer = Dealer::with_seed(9);
+        dealer.shuffle();
+        let dealt: Vec<u8> = (0..52).map(|_| dealer.next_card()).collect();
+        dealer.discard(&dealt[..10]);
+
+        let mut stub: Vec<u8> = (0..10).map(|_| dealer.next_card()).collect();
+        assert_ne!(stub, dealt[..10].to_vec());
+        stub.sort();
+        let mut discarded: Vec<u8> = dealt[..10].to_vec();
+        discarded.sort();
+        assert_eq!(stub, discarded);
+
+        // a new hand starts without the old discards
+        dealer.discard(&dealt[10..20]);
+        dealer.shuffle();
+        for _ in 0..52 {dealer.next_card();}
+        assert!(std::panic::catch_unwind(move || dealer.next_card()).is_err());
+    }
+
 }
@@ -1,50 +1,538 @@
 use crate::pokersim::coms::*;
-use crate::pokersim::games::holdem_nl;
+use crate::pokersim::games::HOLDEM_NL;
+use crate::pokersim::rules::{AnteType, GameRules, Straddle};
+use crate::pokersim::tournament::{Tournament, TournamentConfig, TournamentSummary, write_summary};
+use crate::pokersim::mtt::{MultiTableTournament, RoundResult, Table};
+use crate::pokersim::cash::{CashConfig, CashGame};
 use crate::pokersim::dealer::Dealer;
 use crate::pokersim::player::*;
+use crate::pokersim::observer::ObserverConfig;
+use crate::pokersim::history::HandHistoryWriter;
+use crate::pokersim::hand_log::HandLogWriter;
+use crate::pokersim::acpc::{accept_acpc_players, AcpcConfig, AcpcConnection};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
 
 
+#[derive(Clone, Debug, Default)]
+pub struct DriverConfig {
+    pub endpoint: Endpoint,
+    pub clock: DecisionClock,
+    // game events are published for observers when set
+    pub observer: Option<ObserverConfig>,
+    // PokerStars style hand histories are appended to this file when set
+    pub hand_history: Option<String>,
+    // every hand is appended to this file as a JSON line that can be replayed
+    pub hand_log: Option<String>,
+    // seeds the dealer so games can be dealt again
+    pub seed: Option<u64>,
+    // seats played by ACPC agents over TCP
+    pub acpc: Option<AcpcConfig>,
+    // the game dealt unless the table's StartGameMessage sets other rules
+    pub rules: GameRules,
+    // blind levels and payouts, games are played as a tournament until one player is left
+    pub tournament: TournamentConfig,
+    // plays a cash game with rebuys instead of a tournament when set
+    pub cash: Option<CashConfig>,
+}
+
 pub fn run_pokersim() {
-    let zmq_conn = zmq_init();
-    
-    let msg = Message::ReadyForGameMessage;
-    let header: &str = "";
-    let rply = zmq_conn.send_recv_message(msg, header);
-    println!("{:?}", rply);
+    run_pokersim_with_config(&DriverConfig::default());
+}
+
+pub fn run_pokersim_with_config(config: &DriverConfig) {
+    let mut zmq_conn = match zmq_bind(&config.endpoint) {
+        Ok(conn) => conn,
+        Err(err) => panic!("Could not bind {}: {}", config.endpoint, err),
+    };
+    // report the resolved address so clients can find ephemeral ports
+    println!("pokersim listening on {}", zmq_conn.endpoint());
+    if let Some(observer) = &config.observer {
+        if let Err(err) = zmq_conn.bind_publisher(observer) {
+            panic!("Could not bind the observer stream {}: {}", observer.endpoint, err);
+        }
+        println!("pokersim publishing events on {}", zmq_conn.publisher_endpoint().unwrap());
+    }
+    if let Some(path) = &config.hand_history {
+        let file = match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => file,
+            Err(err) => panic!("Could not open the hand history {}: {}", path, err),
+        };
+        let table: &str = config.observer.as_ref().map_or("pokersim", |observer| observer.table.as_str());
+        zmq_conn.write_hand_history(HandHistoryWriter::new(Box::new(file), table));
+    }
+    if let Some(path) = &config.hand_log {
+        let file = match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => file,
+            Err(err) => panic!("Could not open the hand log {}: {}", path, err),
+        };
+        zmq_conn.write_hand_log(HandLogWriter::new(Box::new(file)));
+    }
+    let dealer: Dealer = match config.seed {
+        Some(seed) => Dealer::with_seed(seed),
+        None => Dealer::new(),
+    };
+    let acpc_players: HashMap<String, AcpcConnection> = match &config.acpc {
+        Some(acpc) => match accept_acpc_players(acpc, Some(HANDSHAKE_TIMEOUT)) {
+            Ok(acpc_players) => acpc_players,
+            Err(err) => panic!("Could not seat the ACPC agents: {}", err),
+        },
+        None => HashMap::new(),
+    };
+    run_game_with_dealer(&zmq_conn, &config.clock, &config.rules, &config.tournament, config.cash.as_ref(), dealer, acpc_players);
+}
 
-    let player_names: Vec<String>;
-    let player_chips: Vec<u32>;
+pub fn run_game(zmq_conn: &ZmqSocketReply, clock: &DecisionClock) {
+    run_game_with_dealer(zmq_conn, clock, &GameRules::default(), &TournamentConfig::default(), None, Dealer::new(), HashMap::new());
+}
 
-    match rply {
-        Message::StartGameMessage { players, chips } => {
-        player_names = players;
-        player_chips = chips;
-    },
-        _ => panic!("Expected a StartGameMessage, recieved {:?}", rply)
+pub fn run_game_with_dealer(zmq_conn: &ZmqSocketReply, clock: &DecisionClock, rules: &GameRules, tournament_config: &TournamentConfig, cash_config: Option<&CashConfig>, mut dealer: Dealer, mut acpc_players: HashMap<String, AcpcConnection>) {
+    zmq_conn.wait_for_clients(&[TABLE_IDENTITY.to_string()]).unwrap();
+    // the table negotiates the configured variant, the game it starts can still use other rules it offered
+    if let Err(err) = zmq_conn.negotiate(TABLE_IDENTITY, &rules.variant, Some(HANDSHAKE_TIMEOUT)) {
+        panic!("Could not start a game with the table controller: {}", err);
     }
-    let mut dealer = Dealer::new();
+
+    // tables that start a game pokersim can't deal are told why and asked again
+    let (player_names, player_chips, rules): (Vec<String>, Vec<u32>, GameRules) = loop {
+        let rply = zmq_conn.send_recv_message(Message::ReadyForGameMessage, "");
+        println!("{:?}", rply);
+        match start_game(zmq_conn, rply, rules, tournament_config, cash_config, &acpc_players) {
+            Ok(start) => break start,
+            Err(reason) => {
+                println!("Could not start the game: {}", reason);
+                zmq_conn.send_message(Message::ErrorMessage { reason }, "");
+            },
+        }
+    };
+    let zmq_names: Vec<String> = player_names.iter().filter(|name| !acpc_players.contains_key(*name)).cloned().collect();
+    zmq_conn.wait_for_clients(&zmq_names).unwrap();
 
     let mut players: Vec<Player> = vec![];
     for idx in 0..player_names.len() {
-        let player = Player::new(player_names[idx].clone(), player_chips[idx], Some(&zmq_conn));
+        if let Some(acpc) = acpc_players.remove(&player_names[idx]) {
+            let mut player = Player::new_acpc(player_names[idx].clone(), player_chips[idx], acpc);
+            player.set_clock(clock.clone());
+            players.push(player);
+            continue;
+        }
+        // players that fail the handshake aren't seated
+        if let Err(err) = zmq_conn.negotiate(&player_names[idx], &rules.variant, Some(HANDSHAKE_TIMEOUT)) {
+            println!("Player {} was refused: {}", player_names[idx], err);
+            continue;
+        }
+        let mut player = Player::new(player_names[idx].clone(), player_chips[idx], Some(zmq_conn));
+        player.set_clock(clock.clone());
         players.push(player);
     }
-    let blinds: [u32; 2] = [0, 0];
-    let ante: u32 = 10;
+    let results: Message = match cash_config {
+        Some(cash) => Message::CashResultsMessage { results: play_cash_game(zmq_conn, players, &rules, cash, dealer) },
+        None => {
+            let summary: TournamentSummary = match tournament_config.table_size {
+                Some(table_size) => play_multi_table(zmq_conn, players, &rules, tournament_config, table_size, &mut dealer),
+                None => play_single_table(zmq_conn, players, &rules, tournament_config, dealer),
+            };
+            if let Some(path) = &tournament_config.summary && let Err(err) = write_summary(path, &summary) {
+                println!("Could not write the tournament summary {}: {}", path, err);
+            }
+            Message::StandingsMessage { standings: summary.standings }
+        },
+    };
+    zmq_conn.publish(&results);
+    // players and tables on a protocol without the results aren't sent them
+    for name in player_names.iter() {
+        if zmq_conn.is_connected(name) && zmq_conn.protocol_version(name) >= results.min_protocol_version() {
+            zmq_conn.send_message(results.clone(), name);
+        }
+    }
+    if zmq_conn.protocol_version("") >= results.min_protocol_version() {
+        zmq_conn.send_recv_message(results, "");
+    }
+    zmq_conn.publish(&Message::EndOfGameMessage);
+
+    for name in player_names.iter() {
+        if zmq_conn.is_connected(name) {
+            zmq_conn.send_message(Message::EndOfGameMessage, name);
+        }
+    }
+    zmq_conn.send_recv_message(Message::EndOfGameMessage, "");
+}
+
+fn start_game(zmq_conn: &ZmqSocketReply, rply: Message, rules: &GameRules, tournament_config: &TournamentConfig, cash_config: Option<&CashConfig>, acpc_players: &HashMap<String, AcpcConnection>) -> Result<(Vec<String>, Vec<u32>, GameRules), String> {
+    // the players, chips and rules of the table's StartGameMessage, tables that leave out the rules get the configured game
+    let (player_names, player_chips, rules): (Vec<String>, Vec<u32>, GameRules) = match rply {
+        Message::StartGameMessage { players, chips, rules: table_rules } => (players, chips, table_rules.unwrap_or_else(|| rules.clone())),
+        _ => return Err(format!("expected a StartGameMessage, recieved {:?}", rply)),
+    };
+    if player_names.len() != player_chips.len() {
+        return Err(format!("{} players with {} stacks", player_names.len(), player_chips.len()));
+    }
+    rules.validate().map_err(|err| err.to_string())?;
+    match cash_config {
+        Some(cash) => {
+            cash.validate(&rules).map_err(|err| format!("the cash game can't be played: {}", err))?;
+            if let Some(idx) = player_chips.iter().position(|chips| *chips < cash.buy_in[0] || *chips > cash.buy_in[1]) {
+                return Err(format!("{} buys in for {}, buy ins are {} to {}", player_names[idx], player_chips[idx], cash.buy_in[0], cash.buy_in[1]));
+            }
+        },
+        None => tournament_config.validate(&rules).map_err(|err| format!("the tournament can't be played: {}", err))?,
+    }
+    let multi_table: bool = cash_config.is_none() && tournament_config.table_size.is_some();
+    if !multi_table && player_names.len() > rules.max_seats {
+        return Err(format!("{} players for {} seats", player_names.len(), rules.max_seats));
+    }
+    // ACPC agents only play hold'em at a single table, the small blind of every hand is ACPC position 0
+    if !acpc_players.is_empty() && rules.variant != HOLDEM_NL {
+        return Err(format!("ACPC agents can't play {}", rules.variant));
+    }
+    if !acpc_players.is_empty() && (multi_table || cash_config.is_some()) {
+        return Err("ACPC agents can't play multi-table tournaments or cash games".to_string());
+    }
+    if !acpc_players.is_empty() && (rules.straddle != Straddle::Off || rules.ante_type != AnteType::PerPlayer) {
+        return Err("ACPC agents can't play with straddles or big blind antes".to_string());
+    }
+    zmq_conn.switch_variant(TABLE_IDENTITY, &rules.variant)?;
+    return Ok((player_names, player_chips, rules));
+}
+
+fn play_single_table(zmq_conn: &ZmqSocketReply, players: Vec<Player>, rules: &GameRules, tournament_config: &TournamentConfig, dealer: Dealer) -> TournamentSummary {
+    // players sit in order and the button moves around the table, the first seat posts the small blind of the first hand
+    let mut tournament = Tournament::new(tournament_config, rules, players.len());
+    let n_seats: usize = players.len();
+    let mut table: Table<Player> = Table::new(1, n_seats, dealer);
+    for (seat, player) in players.into_iter().enumerate() {
+        table.seats[seat] = Some(player);
+    }
+    table.button = n_seats.saturating_sub(1);
+    broadcast_level(&tournament, &mut table, zmq_conn);
 
-    while players.len() > 1 {
-        holdem_nl(&mut dealer, &mut players, blinds, ante, Some(&zmq_conn));
+    while table.n_players() > 1 {
+        let stacks: Vec<u32> = table.seats.iter().map(|seat| seat.as_ref().map_or(0, |player| player.chips())).collect();
+        table.play_hand(&tournament.rules(rules), Some(zmq_conn));
+        let new_level: bool = tournament.end_hand();
+        // players are out of the game once they have lost every chip, a short stack posts its forced bets all in
+        let mut busted: Vec<(String, u32)> = vec![];
+        for (seat, stack) in table.seats.iter_mut().zip(stacks) {
+            if seat.as_ref().is_some_and(|player| player.chips() == 0) {
+                busted.push((seat.take().unwrap().name(), stack));
+            }
+        }
+        for standing in tournament.eliminate(&busted, table.n_players()) {
+            zmq_conn.publish(&Message::EliminationMessage { player: standing.player, finishing_position: standing.finishing_position });
+        }
+        if new_level && table.n_players() > 1 {
+            broadcast_level(&tournament, &mut table, zmq_conn);
+        }
+    }
+    let winner: Option<String> = table.players().next().map(|player| player.name());
+    if let Some(winner) = &winner {
+        zmq_conn.publish(&Message::EliminationMessage { player: winner.clone(), finishing_position: 1 });
     }
+    return tournament.finish(winner, &rules.variant);
+}
+
+fn play_multi_table(zmq_conn: &ZmqSocketReply, players: Vec<Player>, rules: &GameRules, tournament_config: &TournamentConfig, table_size: usize, dealer: &mut Dealer) -> TournamentSummary {
+    let mut mtt: MultiTableTournament<Player> = MultiTableTournament::new(players, table_size, rules, tournament_config, dealer);
+    let level: Message = mtt.level_message();
+    mtt.broadcast(&level);
+    zmq_conn.publish(&level);
+
+    while !mtt.is_finished() {
+        let result: RoundResult = mtt.play_round(Some(zmq_conn));
+        for standing in result.eliminated {
+            zmq_conn.publish(&Message::EliminationMessage { player: standing.player, finishing_position: standing.finishing_position });
+        }
+        for msg in result.moves.iter().chain(result.level.iter()) {
+            zmq_conn.publish(msg);
+        }
+    }
+    if let Some(winner) = mtt.winner() {
+        zmq_conn.publish(&Message::EliminationMessage { player: winner, finishing_position: 1 });
+    }
+    return mtt.finish();
+}
+
+fn play_cash_game(zmq_conn: &ZmqSocketReply, players: Vec<Player>, rules: &GameRules, cash_config: &CashConfig, dealer: Dealer) -> Vec<CashResult> {
+    let mut game: CashGame<Player> = CashGame::new(players, rules, cash_config, dealer);
+    while !game.is_finished() {
+        game.play_hand(Some(zmq_conn));
+    }
+    return game.results();
+}
+
+fn broadcast_level(tournament: &Tournament, table: &mut Table<Player>, zmq_conn: &ZmqSocketReply) {
+    let msg: Message = tournament.level_message();
+    for player in table.seats.iter_mut().flatten() {
+        player.notify(&msg);
+    }
+    zmq_conn.publish(&msg);
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use serial_test::serial;
+    use crate::pokersim::coms::tests as coms_tests;
+    use crate::pokersim::acpc::tests as acpc_tests;
+    use crate::pokersim::games::{KUHN, OMAHA};
+    use crate::pokersim::observer::ZmqSubscriber;
+    use crate::pokersim::tournament::BlindLevel;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
 
     #[test]
-    #[serial]
     pub fn test_run_pokersim() {
-        run_pokersim();
+        let zmq_conn = coms_tests::test_server("test_run_pokersim");
+        let names: Vec<String> = vec!["player1".to_string(), "player2".to_string()];
+        let table = coms_tests::spawn_table(&zmq_conn, names.clone(), vec![100, 100]);
+        let bots: Vec<_> = names.iter().map(|name| coms_tests::spawn_calling_bot(&zmq_conn, name)).collect();
+
+        run_game(&zmq_conn, &DecisionClock::default());
+
+        for bot in bots {
+            bot.join().unwrap();
+        }
+        table.join().unwrap();
+    }
+
+    #[test]
+    pub fn test_start_game_rules() {
+        // the table starts Kuhn poker instead of the configured game
+        let mut zmq_conn = coms_tests::test_server("test_start_game_rules");
+        let observer = ObserverConfig { endpoint: Endpoint::Inproc("test_start_game_rules_events".to_string()), table: "table_1".to_string(), god_view: true };
+        zmq_conn.bind_publisher(&observer).unwrap();
+        let mut subscriber = ZmqSubscriber::connect(&zmq_conn.context(), zmq_conn.publisher_endpoint().unwrap());
+        subscriber.subscribe(None, None);
+        thread::sleep(Duration::from_millis(50));
+
+        let names: Vec<String> = vec!["player1".to_string(), "player2".to_string()];
+        let table = coms_tests::spawn_table_with_rules(&zmq_conn, names.clone(), vec![3, 3], Some(GameRules::new(KUHN).unwrap()));
+        let bots: Vec<_> = names.iter().map(|name| coms_tests::spawn_calling_bot(&zmq_conn, name)).collect();
+        run_game_with_dealer(&zmq_conn, &DecisionClock::default(), &GameRules::default(), &TournamentConfig::default(), None, Dealer::with_seed(5), HashMap::new());
+
+        // every hand antes 1 and deals a single card
+        let mut n_hands: usize = 0;
+        loop {
+            match subscriber.recv_event().1 {
+                Message::EndOfGameMessage => break,
+                Message::HandStartMessage { ante, blinds, .. } => {
+                    assert_eq!((ante, blinds), (1, [0, 0]));
+                    n_hands += 1;
+                },
+                Message::DealMessage { cards, .. } => assert_eq!(cards.unwrap().len(), 1),
+                _ => (),
+            }
+        }
+        assert!(n_hands > 0);
+
+        for bot in bots {
+            bot.join().unwrap();
+        }
+        table.join().unwrap();
+    }
+
+    #[test]
+    pub fn test_invalid_start_game() {
+        // a table that starts a game pokersim can't deal gets an ErrorMessage and is asked again
+        let zmq_conn = coms_tests::test_server("test_invalid_start_game");
+        let names: Vec<String> = vec!["player1".to_string(), "player2".to_string()];
+        let (ctx, endpoint, players) = (zmq_conn.context(), zmq_conn.endpoint().to_string(), names.clone());
+        let table = thread::spawn(move || {
+            let client = ZmqClient::connect_with_context(&ctx, &endpoint, TABLE_IDENTITY);
+            client.hello(&["holdem_nl"], &[Encoding::JSON]);
+            // inverted blinds, then a variant the table didn't offer, then the configured game
+            let mut starts = vec![
+                Some(GameRules { blinds: [10, 5], ..GameRules::default() }),
+                Some(GameRules::new(OMAHA).unwrap()),
+                None,
+            ].into_iter();
+            let mut errors: Vec<String> = vec![];
+            loop {
+                match client.recv_message() {
+                    Message::WelcomeMessage { .. } => (),
+                    Message::ReadyForGameMessage => {
+                        client.send_message(&Message::StartGameMessage { players: players.clone(), chips: vec![100, 100], rules: starts.next().unwrap() });
+                    },
+                    Message::ErrorMessage { reason } => errors.push(reason),
+                    Message::EndOfGameMessage => {
+                        client.send_message(&Message::StatusMessage { status: Status::OK });
+                        return errors;
+                    },
+                    _ => client.send_message(&Message::StatusMessage { status: Status::OK }),
+                }
+            }
+        });
+        let bots: Vec<_> = names.iter().map(|name| coms_tests::spawn_calling_bot(&zmq_conn, name)).collect();
+        run_game(&zmq_conn, &DecisionClock::default());
+
+        let errors: Vec<String> = table.join().unwrap();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("blinds"), "{}", errors[0]);
+        assert!(errors[1].contains(OMAHA), "{}", errors[1]);
+        assert_eq!(zmq_conn.session(TABLE_IDENTITY).unwrap().game_variant, HOLDEM_NL);
+        for bot in bots {
+            bot.join().unwrap();
+        }
+    }
+
+    #[test]
+    pub fn test_tournament() {
+        // blinds go up every two hands until one of the players is knocked out
+        let zmq_conn = coms_tests::test_server("test_tournament");
+        let names: Vec<String> = vec!["player1".to_string(), "player2".to_string()];
+        let table = coms_tests::spawn_table(&zmq_conn, names.clone(), vec![100, 100]);
+        let bots: Vec<_> = names.iter().map(|name| coms_tests::spawn_calling_bot(&zmq_conn, name)).collect();
+        let path = std::env::temp_dir().join("pokersim_test_tournament.json");
+        let tournament = TournamentConfig {
+            levels: vec![
+                BlindLevel { blinds: [1, 2], ante: 0, hands: 2 },
+                BlindLevel { blinds: [5, 10], ante: 1, hands: 2 },
+                BlindLevel { blinds: [25, 50], ante: 5, hands: 2 },
+            ],
+            buy_in: 10,
+            payouts: vec![70, 30],
+            summary: Some(path.to_str().unwrap().to_string()),
+            table_size: None,
+        };
+        run_game_with_dealer(&zmq_conn, &DecisionClock::default(), &GameRules::new(HOLDEM_NL).unwrap(), &tournament, None, Dealer::with_seed(8), HashMap::new());
+
+        for bot in bots {
+            bot.join().unwrap();
+        }
+        table.join().unwrap();
+        let summary: TournamentSummary = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((summary.entrants, summary.prize_pool), (2, 20));
+        assert_eq!(summary.standings.iter().map(|standing| (standing.finishing_position, standing.payout)).collect::<Vec<_>>(), vec![(1, 14), (2, 6)]);
+        // the stacks of 100 last into the later levels
+        assert!(summary.hands > 2);
+        assert!(summary.level > 1);
+    }
+
+    #[test]
+    pub fn test_tournament_old_protocol() {
+        // a protocol version 3 player plays the tournament without the level and standings messages
+        let zmq_conn = coms_tests::test_server("test_tournament_old_protocol");
+        let names: Vec<String> = vec!["player1".to_string(), "player2".to_string()];
+        let table = coms_tests::spawn_table(&zmq_conn, names.clone(), vec![50, 50]);
+        let bot = coms_tests::spawn_calling_bot(&zmq_conn, &names[0]);
+        let old_bot = coms_tests::spawn_old_calling_bot(&zmq_conn, &names[1], 3);
+        let tournament = TournamentConfig { levels: vec![BlindLevel { blinds: [5, 10], ante: 0, hands: 1 }, BlindLevel { blinds: [10, 20], ante: 0, hands: 0 }], ..Default::default() };
+        run_game_with_dealer(&zmq_conn, &DecisionClock::default(), &GameRules::new(HOLDEM_NL).unwrap(), &tournament, None, Dealer::with_seed(8), HashMap::new());
+
+        bot.join().unwrap();
+        table.join().unwrap();
+        let message_types: Vec<String> = old_bot.join().unwrap();
+        assert!(message_types.contains(&"ActionMessage".to_string()));
+        assert!(!message_types.contains(&"LevelMessage".to_string()));
+        assert!(!message_types.contains(&"StandingsMessage".to_string()));
+        assert_eq!(message_types.last().unwrap(), "EndOfGameMessage");
+    }
+
+    #[test]
+    pub fn test_multi_table_tournament() {
+        // five players at tables of three are balanced and brought together at a final table
+        let zmq_conn = coms_tests::test_server("test_multi_table_tournament");
+        let names: Vec<String> = (1..=5).map(|idx| format!("player{}", idx)).collect();
+        let table = coms_tests::spawn_table(&zmq_conn, names.clone(), vec![100; 5]);
+        let bots: Vec<_> = names.iter().map(|name| coms_tests::spawn_calling_bot(&zmq_conn, name)).collect();
+        let path = std::env::temp_dir().join("pokersim_test_multi_table_tournament.json");
+        let tournament = TournamentConfig {
+            levels: vec![
+                BlindLevel { blinds: [1, 2], ante: 0, hands: 2 },
+                BlindLevel { blinds: [10, 20], ante: 5, hands: 0 },
+            ],
+            buy_in: 10,
+            payouts: vec![60, 40],
+            summary: Some(path.to_str().unwrap().to_string()),
+            table_size: Some(3),
+        };
+        run_game_with_dealer(&zmq_conn, &DecisionClock::default(), &GameRules::new(HOLDEM_NL).unwrap(), &tournament, None, Dealer::with_seed(5), HashMap::new());
+
+        for bot in bots {
+            bot.join().unwrap();
+        }
+        table.join().unwrap();
+        let summary: TournamentSummary = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((summary.entrants, summary.prize_pool), (5, 50));
+        assert_eq!(summary.standings.len(), 5);
+        assert_eq!(summary.standings[0].finishing_position, 1);
+        assert_eq!(summary.standings.iter().map(|standing| standing.payout).sum::<u32>(), 50);
+    }
+
+    #[test]
+    pub fn test_cash_game() {
+        // three bots play a set number of hands and rebuy when they bust
+        let mut zmq_conn = coms_tests::test_server("test_cash_game");
+        let observer = ObserverConfig { endpoint: Endpoint::Inproc("test_cash_game_events".to_string()), table: "table_1".to_string(), god_view: false };
+        zmq_conn.bind_publisher(&observer).unwrap();
+        let mut subscriber = ZmqSubscriber::connect(&zmq_conn.context(), zmq_conn.publisher_endpoint().unwrap());
+        subscriber.subscribe(Some("table_1"), Some("CashResultsMessage"));
+        thread::sleep(Duration::from_millis(50));
+
+        let names: Vec<String> = vec!["player1".to_string(), "player2".to_string(), "player3".to_string()];
+        let table = coms_tests::spawn_table(&zmq_conn, names.clone(), vec![100, 60, 100]);
+        let bots: Vec<_> = names.iter().map(|name| coms_tests::spawn_calling_bot(&zmq_conn, name)).collect();
+        let cash = CashConfig { buy_in: [50, 100], hands: 30, requests: true, ..Default::default() };
+        let rules = GameRules { blinds: [5, 10], ..GameRules::new(HOLDEM_NL).unwrap() };
+        run_game_with_dealer(&zmq_conn, &DecisionClock::default(), &rules, &TournamentConfig::default(), Some(&cash), Dealer::with_seed(4), HashMap::new());
+
+        for bot in bots {
+            bot.join().unwrap();
+        }
+        table.join().unwrap();
+        match subscriber.recv_event().1 {
+            Message::CashResultsMessage { results } => {
+                assert_eq!(results.iter().map(|result| result.player.clone()).collect::<Vec<String>>(), names);
+                assert!(results.iter().all(|result| result.hands == 30));
+                assert_eq!(results.iter().map(|result| result.net).sum::<i32>(), 0);
+                // the calling bots bust and rebuy over 30 hands
+                assert!(results.iter().map(|result| result.bought_in).sum::<u32>() > 260);
+            },
+            msg => panic!("Expected a CashResultsMessage, recieved {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_cash_game_old_protocol() {
+        // a protocol version 3 player keeps their stack between hands and isn't sent the results
+        let zmq_conn = coms_tests::test_server("test_cash_game_old_protocol");
+        let names: Vec<String> = vec!["player1".to_string(), "player2".to_string()];
+        let table = coms_tests::spawn_table(&zmq_conn, names.clone(), vec![100, 100]);
+        let bot = coms_tests::spawn_calling_bot(&zmq_conn, &names[0]);
+        let old_bot = coms_tests::spawn_old_calling_bot(&zmq_conn, &names[1], 3);
+        let cash = CashConfig { buy_in: [50, 100], hands: 5, requests: true, ..Default::default() };
+        run_game_with_dealer(&zmq_conn, &DecisionClock::default(), &GameRules::new(HOLDEM_NL).unwrap(), &TournamentConfig::default(), Some(&cash), Dealer::with_seed(4), HashMap::new());
+
+        bot.join().unwrap();
+        table.join().unwrap();
+        let message_types: Vec<String> = old_bot.join().unwrap();
+        assert!(message_types.contains(&"TurnMessage".to_string()));
+        assert!(!message_types.iter().any(|message_type| message_type.starts_with("Cash")));
+        assert_eq!(message_types.last().unwrap(), "EndOfGameMessage");
+    }
+
+    #[test]
+    pub fn test_acpc_agent() {
+        // an ACPC agent plays against a zmq bot
+        let zmq_conn = coms_tests::test_server("test_acpc_agent");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let agent = acpc_tests::spawn_calling_agent(listener.local_addr().unwrap().port());
+        let acpc = AcpcConnection::accept(&listener, Some(HANDSHAKE_TIMEOUT)).unwrap();
+        let names: Vec<String> = vec!["acpc_agent".to_string(), "player2".to_string()];
+        let table = coms_tests::spawn_table(&zmq_conn, names.clone(), vec![30, 30]);
+        let bot = coms_tests::spawn_calling_bot(&zmq_conn, "player2");
+
+        let acpc_players: HashMap<String, AcpcConnection> = HashMap::from([("acpc_agent".to_string(), acpc)]);
+        run_game_with_dealer(&zmq_conn, &DecisionClock::default(), &GameRules::default(), &TournamentConfig::default(), None, Dealer::with_seed(3), acpc_players);
+
+        bot.join().unwrap();
+        table.join().unwrap();
+        let states: Vec<String> = agent.join().unwrap();
+        assert!(!states.is_empty());
+        assert!(states[0].starts_with("MATCHSTATE:0:0::"));
+        // the button moves, the agent posts the small blind from position 0 every other hand
+        assert!(states.iter().any(|state| state.starts_with("MATCHSTATE:1:1:")));
     }
 }
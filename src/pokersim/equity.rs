@@ -0,0 +1,169 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use super::games::HandEvaluation;
+use super::pots::{Pot, pot_winners};
+
+
+// All in equity of board games: the chips each player is expected to win when the rest of the board is
+// dealt from the cards nobody in the hand holds. From the flop on every rest of the board is dealt once and the
+// equity is exact. Before the flop that would be well over a million boards, seconds heads up and far longer for
+// several Omaha hands, so a sample of boards is dealt instead. The sample is seeded by the cards, the same spot
+// always gets the same equity
+
+// boards dealt for an all in before the flop, the equity is within about a percent of the exact one
+const SAMPLED_BOARDS: usize = 10_000;
+
+pub fn unseen_cards(deck: &[u8], known: &[u8]) -> Vec<u8> {
+    // the cards of the deck the rest of the board can be dealt from
+    return deck.iter().copied().filter(|card| !known.contains(card)).collect();
+}
+
+fn sample_seed(hands: &[Option<Vec<u8>>], board: &[u8]) -> u64 {
+    // a seed that only depends on the cards of the spot
+    return hands.iter().flatten().flatten().chain(board.iter()).fold(0, |seed: u64, card| seed.wrapping_mul(53).wrapping_add(*card as u64));
+}
+
+fn for_each_board(cards: &[u8], n_cards: usize, chosen: &mut Vec<u8>, f: &mut dyn FnMut(&[u8])) {
+    // calls f with every combination of n_cards cards
+    if chosen.len() == n_cards {
+        f(chosen);
+        return;
+    }
+    let needed: usize = n_cards - chosen.len();
+    if cards.len() < needed {return}
+    for idx in 0..=cards.len() - needed {
+        chosen.push(cards[idx]);
+        for_each_board(&cards[idx + 1..], n_cards, chosen, f);
+        chosen.pop();
+    }
+}
+
+pub fn pot_equity(hands: &[Option<Vec<u8>>], board: &[u8], unseen: &[u8], pots: &[Pot], evaluation: &HandEvaluation) -> Vec<Vec<f64>> {
+    // expected chips of every player from each pot, [pot][player]. hands are None for players who folded.
+    // Ties split a pot evenly and a pot with a low is split in halves like at showdown
+    let n_players: usize = hands.len();
+    let mut won: Vec<Vec<f64>> = vec![vec![0.0; n_players]; pots.len()];
+    let mut n_boards: usize = 0;
+    let mut full_board: Vec<u8> = board.to_vec();
+    let mut score_board = |cards: &[u8]| {
+        full_board.truncate(board.len());
+        full_board.extend_from_slice(cards);
+        let scores: Vec<Option<f64>> = hands.iter().map(|hand| hand.as_ref().map(|cards| evaluation.score(cards, &full_board))).collect();
+        let lows: Vec<Option<[u8; 5]>> = hands.iter().map(|hand| hand.as_ref().and_then(|cards| evaluation.low(cards, &full_board))).collect();
+        for (pot, pot_won) in pots.iter().zip(won.iter_mut()) {
+            let (high, low) = pot_winners(pot, &scores, &lows);
+            let high_half: u32 = if low.is_empty() {pot.amount} else {pot.amount - pot.amount / 2};
+            for idx in high.iter() {
+                pot_won[*idx] += high_half as f64 / high.len() as f64;
+            }
+            for idx in low.iter() {
+                pot_won[*idx] += (pot.amount / 2) as f64 / low.len() as f64;
+            }
+        }
+        n_boards += 1;
+    };
+    let n_cards: usize = 5 - board.len();
+    if board.len() >= 3 {
+        for_each_board(unseen, n_cards, &mut vec![], &mut score_board);
+    } else if unseen.len() >= n_cards {
+        let mut rng = StdRng::seed_from_u64(sample_seed(hands, board));
+        let mut cards: Vec<u8> = unseen.to_vec();
+        for _ in 0..SAMPLED_BOARDS {
+            let (sample, _) = cards.partial_shuffle(&mut rng, n_cards);
+            score_board(sample);
+        }
+    }
+    for pot_won in won.iter_mut() {
+        for amount in pot_won.iter_mut() {
+            *amount /= n_boards.max(1) as f64;
+        }
+    }
+    return won;
+}
+
+pub fn equity(hands: &[Vec<u8>], board: &[u8], unseen: &[u8], evaluation: &HandEvaluation) -> Vec<f64> {
+    // each hand's share of a pot they all play for, between 0 and 1
+    // an even pot splits into exact halves between the high and the low
+    let pot = Pot { amount: 2, eligible: (0..hands.len()).collect() };
+    let hands: Vec<Option<Vec<u8>>> = hands.iter().cloned().map(Some).collect();
+    return pot_equity(&hands, board, unseen, &[pot], evaluation)[0].iter().map(|amount| amount / 2.0).collect();
+}
+
+pub fn pay_by_equity(amount: u32, equity: &[f64]) -> Vec<u32> {
+    // pays a pot in whole chips by each player's expected share, rounded down.
+    // The chips left over go one each to the largest remainders, ties to the first seats
+    let mut paid: Vec<u32> = equity.iter().map(|share| share.floor() as u32).collect();
+    let left: u32 = amount.saturating_sub(paid.iter().sum());
+    let mut order: Vec<usize> = (0..equity.len()).collect();
+    order.sort_by(|a, b| equity[*b].fract().partial_cmp(&equity[*a].fract()).unwrap());
+    for idx in order.into_iter().cycle().take(left as usize) {
+        paid[idx] += 1;
+    }
+    return paid;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pokersim::dealer::standard_deck;
+
+    #[test]
+    pub fn test_equity() {
+        // aces against kings on the turn, only the two kings left win for the kings
+        let hands: Vec<Vec<u8>> = vec![vec![13, 26], vec![12, 25]];
+        let board: Vec<u8> = vec![27, 45, 34, 42];
+        let unseen: Vec<u8> = unseen_cards(&standard_deck(), &[hands.concat(), board.clone()].concat());
+        assert_eq!(unseen.len(), 44);
+        let shares: Vec<f64> = equity(&hands, &board, &unseen, &HandEvaluation::Holdem);
+        assert!((shares[0] - 42.0 / 44.0).abs() < 1e-9);
+        assert!((shares[1] - 2.0 / 44.0).abs() < 1e-9);
+
+        // the same hands split every board
+        let shares: Vec<f64> = equity(&[vec![13, 12], vec![26, 25]], &board, &unseen, &HandEvaluation::Holdem);
+        assert!(shares.iter().all(|share| (share - 0.5).abs() < 1e-9));
+
+        // a complete board has one outcome
+        assert_eq!(equity(&hands, &[27, 45, 34, 42, 38], &[], &HandEvaluation::Holdem), vec![0.0, 1.0]);
+    }
+
+    #[test]
+    pub fn test_pot_equity() {
+        // player 2 folded, player 1 is all in for the main pot and player 0 alone is in the side pot
+        let hands: Vec<Option<Vec<u8>>> = vec![Some(vec![13, 26]), Some(vec![12, 25]), None];
+        let board: Vec<u8> = vec![27, 45, 34, 42];
+        let unseen: Vec<u8> = unseen_cards(&standard_deck(), &[13, 26, 12, 25, 27, 45, 34, 42]);
+        let pots = vec![Pot { amount: 440, eligible: vec![0, 1] }, Pot { amount: 50, eligible: vec![0] }];
+        let won: Vec<Vec<f64>> = pot_equity(&hands, &board, &unseen, &pots, &HandEvaluation::Holdem);
+        assert!((won[0][0] - 420.0).abs() < 1e-9);
+        assert!((won[0][1] - 20.0).abs() < 1e-9);
+        assert_eq!(won[0][2], 0.0);
+        assert_eq!(won[1], vec![50.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    pub fn test_sampled_equity() {
+        // aces against kings before the flop win about 82 percent, the boards are sampled the same way every time
+        let hands: Vec<Vec<u8>> = vec![vec![13, 26], vec![12, 25]];
+        let unseen: Vec<u8> = unseen_cards(&standard_deck(), &hands.concat());
+        let shares: Vec<f64> = equity(&hands, &[], &unseen, &HandEvaluation::Holdem);
+        assert!((shares[0] - 0.82).abs() < 0.015);
+        assert!((shares[0] + shares[1] - 1.0).abs() < 1e-9);
+        assert_eq!(equity(&hands, &[], &unseen, &HandEvaluation::Holdem), shares);
+
+        // three omaha hands before the flop are sampled too
+        let hands: Vec<Vec<u8>> = vec![vec![13, 26, 12, 25], vec![1, 14, 2, 15], vec![40, 41, 42, 43]];
+        let unseen: Vec<u8> = unseen_cards(&standard_deck(), &hands.concat());
+        let shares: Vec<f64> = equity(&hands, &[], &unseen, &HandEvaluation::Omaha);
+        assert!((shares.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(shares.iter().all(|share| *share > 0.0));
+    }
+
+    #[test]
+    pub fn test_pay_by_equity() {
+        assert_eq!(pay_by_equity(100, &[100.0 * 42.0 / 44.0, 100.0 * 2.0 / 44.0]), vec![95, 5]);
+        assert_eq!(pay_by_equity(100, &[100.0 / 3.0; 3]), vec![34, 33, 33]);
+        assert_eq!(pay_by_equity(90, &[90.0, 0.0]), vec![90, 0]);
+    }
+}
@@ -1,8 +1,234 @@
+use serde::{Serialize, Deserialize};
 use crate::pokersim::coms::*;
 
-use super::dealer::Dealer;
+use super::dealer::{Dealer, kuhn_deck, leduc_deck};
 use super::player::*;
-use super::hand_eval::score_hand;
+use super::hand_eval::{HandCategory, score_cards, cards_category, score_omaha, omaha_category, omaha_low, score_short_deck, short_deck_category, score_razz, razz_category, low_rank, score_deuce_to_seven, score_leduc};
+use super::pots::{Pot, side_pots, split_pot, pot_winners};
+use super::equity::{pot_equity, pay_by_equity, unseen_cards};
+use super::rules::{AnteType, DeckType, Straddle};
+
+
+// what a table reports about its hands besides telling the players: events for observers and records,
+// what only the hand log keeps, and the result of every hand for the table controller
+pub trait TableEvents {
+    fn publish(&self, msg: &Message);
+    // the deck order, rake and all in settlement of the hand being played
+    fn log_deck(&self, seed: u64, deck: &[u8]);
+    fn log_rake(&self, rake: &Rake);
+    fn log_all_in(&self, all_in: AllIn);
+    // called with the EndOfRoundMessage after it was published, tables without a controller have nothing to do
+    fn end_hand(&self, _msg: &Message) {}
+}
+
+pub const HOLDEM_NL: &str = "holdem_nl";
+pub const HOLDEM_LIMIT: &str = "holdem_limit";
+pub const HOLDEM_PL: &str = "holdem_pl";
+pub const OMAHA: &str = "omaha";
+pub const PLO5: &str = "plo5";
+pub const OMAHA_HILO: &str = "omaha_hilo";
+pub const SHORT_DECK: &str = "short_deck";
+pub const SEVEN_CARD_STUD: &str = "seven_card_stud";
+pub const RAZZ: &str = "razz";
+pub const FIVE_CARD_DRAW: &str = "five_card_draw";
+pub const TRIPLE_DRAW: &str = "triple_draw";
+pub const KUHN: &str = "kuhn";
+pub const LEDUC: &str = "leduc";
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Betting {
+    NoLimit,
+    // bets and raises up to the pot after calling
+    PotLimit,
+    // bets are [small bet, big bet], the small bet is used before the turn.
+    // raise_cap counts the bet and raises allowed in a betting round, the big blind counts as the first bet
+    FixedLimit { bets: [u32; 2], raise_cap: Option<usize>, heads_up_uncapped: bool },
+}
+
+// chips the house takes, a percentage of each hand's pot and a time charge from every player dealt in
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Rake {
+    // percent of the pot, rounded down to whole chips
+    pub percent: f64,
+    // most taken from a hand, None takes the percentage of any pot
+    pub cap: Option<u32>,
+    // [(players, cap)], the first cap for hands dealt to at most that many players is used instead of cap
+    pub player_caps: Vec<(usize, u32)>,
+    // no rake is taken from hands that end in the first betting round
+    pub no_flop_no_drop: bool,
+    // taken from every player before each hand, it doesn't go in the pot
+    pub time_charge: u32,
+}
+
+impl Rake {
+    pub fn is_off(&self) -> bool {
+        return *self == Rake::default();
+    }
+
+    pub fn amount(&self, pot: u32, n_players: usize, saw_flop: bool) -> u32 {
+        // rake of a hand's pot, n_players is the number of players dealt in
+        if self.no_flop_no_drop && !saw_flop {return 0}
+        let rake: u32 = (pot as f64 * self.percent / 100.0).floor() as u32;
+        let cap: Option<u32> = self.player_caps.iter().find(|(players, _)| n_players <= *players).map(|(_, cap)| *cap).or(self.cap);
+        return cap.map_or(rake, |cap| rake.min(cap));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HandEvaluation {
+    // best five cards out of the hole cards and the board, stud hands have no board
+    Holdem,
+    // exactly two hole cards and three board cards
+    Omaha,
+    // Omaha, half of every pot goes to the best 8 or better low when there is one
+    OmahaHiLo,
+    // Holdem where a flush beats a full house and A-6-7-8-9 is a straight
+    ShortDeck,
+    // best A-5 low out of the hole cards, the best low has the highest score
+    Razz,
+    // 2-7 low of exactly five hole cards
+    DeuceToSeven,
+    // one hole card, pairing the board beats the higher card
+    Leduc,
+}
+
+impl HandEvaluation {
+    pub fn score(&self, hole_cards: &[u8], board: &[u8]) -> f64 {
+        let all_cards: Vec<u8> = hole_cards.iter().chain(board.iter()).copied().collect();
+        match self {
+            HandEvaluation::Holdem => return score_cards(&all_cards),
+            HandEvaluation::Omaha | HandEvaluation::OmahaHiLo => return score_omaha(hole_cards, board.try_into().unwrap()),
+            HandEvaluation::ShortDeck => return score_short_deck(&all_cards),
+            HandEvaluation::Razz => return score_razz(&all_cards),
+            HandEvaluation::DeuceToSeven => return score_deuce_to_seven(all_cards.as_slice().try_into().unwrap()),
+            HandEvaluation::Leduc => return score_leduc(hole_cards[0], board),
+        }
+    }
+
+    pub fn category(&self, hole_cards: &[u8], board: &[u8]) -> HandCategory {
+        let all_cards: Vec<u8> = hole_cards.iter().chain(board.iter()).copied().collect();
+        match self {
+            HandEvaluation::Holdem | HandEvaluation::DeuceToSeven => return cards_category(&all_cards),
+            HandEvaluation::Omaha | HandEvaluation::OmahaHiLo => return omaha_category(hole_cards, board.try_into().unwrap()),
+            HandEvaluation::ShortDeck => return short_deck_category(&all_cards),
+            HandEvaluation::Razz => return razz_category(&all_cards),
+            HandEvaluation::Leduc => match score_leduc(hole_cards[0], board) >= 100. {
+                true => return HandCategory::PAIR,
+                false => return HandCategory::HIGHCARD,
+            },
+        }
+    }
+
+    pub fn low(&self, hole_cards: &[u8], board: &[u8]) -> Option<[u8; 5]> {
+        match self {
+            HandEvaluation::OmahaHiLo => return omaha_low(hole_cards, board.try_into().unwrap()),
+            _ => return None,
+        }
+    }
+}
+
+// a game played with a flop, turn and river
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoardGame {
+    pub hole_cards: usize,
+    // cards the hand is dealt from
+    pub deck: DeckType,
+    pub betting: Betting,
+    pub evaluation: HandEvaluation,
+    // the last seat posts the only blind and acts last before the flop
+    pub button_blind: bool,
+    pub rake: Rake,
+    pub posting: Posting,
+    pub all_in: AllIn,
+}
+
+// how a hand is settled when the players are all in before the river
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum AllIn {
+    // the rest of the board is dealt once
+    #[default]
+    RunOnce,
+    // the board is dealt once and every player's all in equity is reported with the result
+    ReportEquity,
+    // the rest of the board is dealt this many times from the same deck, every pot is split evenly between the runs.
+    // A deck without enough cards for every run is run as often as it can be
+    RunTimes(usize),
+    // every pot is paid by the players' equity, the board is still dealt once for the actual result
+    PayEquity,
+}
+
+impl AllIn {
+    pub fn is_run_once(&self) -> bool {
+        return *self == AllIn::RunOnce;
+    }
+
+    pub fn has_equity(&self) -> bool {
+        // the all in equity is reported with the result
+        return matches!(self, AllIn::ReportEquity | AllIn::PayEquity);
+    }
+}
+
+// what every player won or lost in a hand
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HandResult {
+    // net chips, the winnings of the EndOfRoundMessage
+    pub winnings: Vec<i32>,
+    // net expected chips of a hand that was all in before the river, when the game doesn't run the board once
+    pub all_in_ev: Option<Vec<f64>>,
+}
+
+// forced bets besides the little and big blind, only posted in games with both blinds
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Posting {
+    pub straddle: Straddle,
+    // with AnteType::BigBlind the big blind posts the ante once for the table
+    pub ante_type: AnteType,
+    // players posting the blinds they missed while sitting out, by their seat in this hand.
+    // They post a big blind and a dead small blind, players in the blinds only post their blind
+    pub missed_blinds: Vec<usize>,
+}
+
+// a game with draws between the betting rounds instead of a board, every player gets five cards
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawGame {
+    pub n_draws: usize,
+    pub deck: DeckType,
+    pub betting: Betting,
+    pub evaluation: HandEvaluation,
+    pub rake: Rake,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LegalBets {
+    // chips to call, 0 is a check. Less than the amount to call when the player is all in
+    pub call: u32,
+    // [smallest, largest] bet or raise, None when the player can't raise
+    pub raise: Option<[u32; 2]>,
+}
+
+impl LegalBets {
+    pub fn min_raise(&self) -> u32 {
+        return self.raise.map_or(self.call, |raise| raise[0]);
+    }
+
+    pub fn max_bet(&self) -> u32 {
+        return self.raise.map_or(self.call, |raise| raise[1]);
+    }
+
+    pub fn bet_size(&self, val: u32, chips: u32) -> Option<u32> {
+        // the bet made for a requested bet, None folds.
+        // Bets under a call fold unless they put the player all in, bets over a call are fitted
+        // to the legal raise sizes, or call when the player can't raise
+        let bet: u32 = val.min(chips);
+        if bet < self.call && bet < chips {return None}
+        if bet <= self.call {return Some(bet)}
+        match self.raise {
+            Some([min, max]) => return Some(bet.clamp(min, max)),
+            None => return Some(self.call),
+        }
+    }
+}
 
 
 #[derive(Debug)]
@@ -13,15 +239,32 @@ struct Round {
     initial_bets_complete: bool,
     n_plays: usize,
     current_players: Vec<usize>,
+    // all in players stay in current_players but don't act
+    all_in: Vec<bool>,
+    // last player to raise in the current betting round
+    last_aggressor: Option<usize>,
+    // bets and raises made in the current betting round
+    n_raises: usize,
+    // stud bring in, the first bet of the round completes it to the small bet
+    bring_in: u32,
+    // betting rounds played to the end, including the round the hand ended in
+    betting_rounds: usize,
+    // chips in the pot that don't count toward a player's bet, big blind antes and dead blinds
+    dead: Vec<u32>,
+    // smallest bet in no limit and pot limit games
+    big_blind: u32,
+    // smallest raise increment of the current betting round, the big blind or the largest bet or raise so far
+    min_raise: u32,
 }
 
 impl Round {
     pub fn new(n_players: usize, initial_bets: Option<Vec<u32>>) -> Round {
         if n_players < 2 {panic!("Can't play a Round with less than 2 players")}
         let current_players: Vec<usize> = (0..n_players).collect();
+        let all_in: Vec<bool> = vec![false; n_players];
         match initial_bets {
-            Some(bets) => Round{ bets, current_player_idx: 0, n_players, initial_bets_complete: false, n_plays: 0, current_players },
-            None => Round{ bets: vec![0; n_players], current_player_idx: 0, n_players, initial_bets_complete: false, n_plays:0, current_players }
+            Some(bets) => Round{ bets, current_player_idx: 0, n_players, initial_bets_complete: false, n_plays: 0, current_players, all_in, last_aggressor: None, n_raises: 0, bring_in: 0, betting_rounds: 0, dead: vec![0; n_players], big_blind: 0, min_raise: 0 },
+            None => Round{ bets: vec![0; n_players], current_player_idx: 0, n_players, initial_bets_complete: false, n_plays:0, current_players, all_in, last_aggressor: None, n_raises: 0, bring_in: 0, betting_rounds: 0, dead: vec![0; n_players], big_blind: 0, min_raise: 0 }
         }
         
     }
@@ -30,12 +273,34 @@ impl Round {
         if self.current_players.is_empty() {panic!("Can't find next player, all have folded")}
 
         self.n_plays += 1;
-        if self.n_plays == self.n_players {self.initial_bets_complete = true;}
+        if self.n_plays >= self.n_players {self.initial_bets_complete = true;}
 
         if move_index {
             self.current_player_idx += 1;
         }
         self.current_player_idx = self.current_player_idx % self.current_players.len();
+        self.skip_all_in();
+    }
+
+    fn skip_all_in(&mut self) {
+        for _ in 0..self.current_players.len() {
+            if !self.all_in[self.current_players[self.current_player_idx]] {return}
+            self.current_player_idx = (self.current_player_idx + 1) % self.current_players.len();
+        }
+    }
+
+    fn first_to_act(&mut self, player_idx: usize) {
+        // the betting round starts with this player instead of the first seat
+        self.current_player_idx = self.current_players.iter().position(|&idx| idx == player_idx).unwrap_or(0);
+        self.skip_all_in();
+    }
+
+    fn acting_players(&self) -> Vec<usize> {
+        return self.current_players.iter().copied().filter(|&idx| !self.all_in[idx]).collect();
+    }
+
+    pub fn set_all_in(&mut self, player_idx: usize) {
+        self.all_in[player_idx] = true;
     }
 
     pub fn next_player_idx(&self) -> usize {
@@ -50,25 +315,38 @@ impl Round {
         self.next_player(false);
     }
 
+    fn set_big_blind(&mut self, big_blind: u32) {
+        self.big_blind = big_blind;
+        self.min_raise = big_blind;
+    }
+
     fn handle_bet(&mut self, val: u32) {
         let player_idx: usize = self.current_players[self.current_player_idx];
+        let max_bet: u32 = *self.bets.iter().max().unwrap();
+        if self.bets[player_idx] + val > max_bet {
+            self.last_aggressor = Some(player_idx);
+            self.n_raises += 1;
+            // an all in for less than a full raise doesn't change the raise size
+            self.min_raise = self.min_raise.max(self.bets[player_idx] + val - max_bet);
+        }
         self.bets[player_idx] += val;
         self.next_player(true);
     }
 
     fn round_ended(&self) -> bool {
         // The round has been completed if:
-        //  - all bets are equal and initial round is completed
+        //  - all bets of players that aren't all in are equal and initial round is completed
         //  - one player is left
+        //  - at most one player isn't all in and has nothing left to call
 
         if self.current_players.len() == 1 {return true};
 
-        let first = &self.bets[self.current_players[0]];
-        if self.initial_bets_complete & self.current_players.iter().all(|x| &self.bets[*x] == first){
-            return true
-        }
-        else {
-            return false
+        let max_bet: u32 = self.current_players.iter().map(|&x| self.bets[x]).max().unwrap();
+        let acting: Vec<usize> = self.acting_players();
+        match acting.len() {
+            0 => return true,
+            1 => return self.bets[acting[0]] >= max_bet,
+            _ => return self.initial_bets_complete & acting.iter().all(|&x| self.bets[x] == max_bet),
         }
     }
 
@@ -85,10 +363,17 @@ impl Round {
     }
 
     pub fn reset_round(&mut self) {
-        // reset n_players to reflect the number of players who didn't fold last betting round
-        self.n_players = self.current_players.len();
+        // reset n_players to reflect the number of players who can still act
+        self.n_players = self.acting_players().len();
+        self.n_plays = 0;
+        self.initial_bets_complete = false;
+        self.last_aggressor = None;
+        self.n_raises = 0;
+        self.bring_in = 0;
+        self.min_raise = self.big_blind;
+        self.betting_rounds += 1;
         self.current_player_idx = 0;
-        
+        self.skip_all_in();
     }
 
     pub fn min_next_bet(&self) -> u32 {
@@ -96,8 +381,44 @@ impl Round {
         return self.bets.iter().max().unwrap() - self.bets[player_idx];
     }
 
+    pub fn legal_bets(&self, chips: u32, betting: &Betting, turn: &Turn) -> LegalBets {
+        let to_call: u32 = self.min_next_bet();
+        let call: u32 = to_call.min(chips);
+        if chips <= to_call {return LegalBets { call, raise: None }}
+        // a raise has to be at least the big blind and the last bet or raise of the round
+        let min_raise: u32 = (to_call + self.min_raise.max(1)).min(chips);
+        match betting {
+            Betting::NoLimit => return LegalBets { call, raise: Some([min_raise, chips]) },
+            Betting::PotLimit => {
+                // the pot counts antes, blinds and every bet still in front of the players
+                let pot_after_call: u32 = self.pot_total() + to_call;
+                let max_raise: u32 = (to_call + pot_after_call).min(chips);
+                return LegalBets { call, raise: Some([min_raise.min(max_raise), max_raise]) };
+            },
+            Betting::FixedLimit { bets, raise_cap, heads_up_uncapped } => {
+                let capped: bool = match raise_cap {
+                    Some(cap) => self.n_raises >= *cap && !(*heads_up_uncapped && self.current_players.len() == 2),
+                    None => false,
+                };
+                if capped {return LegalBets { call, raise: None }}
+                let bet_size: u32 = match turn {
+                    Turn::HOLE | Turn::FLOP | Turn::THIRD | Turn::FOURTH | Turn::DRAW1 => bets[0],
+                    _ => bets[1],
+                };
+                let bet_size: u32 = if self.n_raises == 0 {bet_size.saturating_sub(self.bring_in)} else {bet_size};
+                let raise: u32 = (to_call + bet_size).min(chips);
+                return LegalBets { call, raise: Some([raise, raise]) };
+            },
+        }
+    }
+
     pub fn pot_total(&self) -> u32 { 
-        return self.bets.iter().sum();
+        return self.bets.iter().sum::<u32>() + self.dead.iter().sum::<u32>();
+    }
+
+    fn contributions(&self) -> Vec<u32> {
+        // every player's chips in the pot, bets and dead money
+        return self.bets.iter().zip(self.dead.iter()).map(|(bet, dead)| bet + dead).collect();
     }
 
     // pub fn remaining_players(&self) -> Vec<usize> {
@@ -112,10 +433,46 @@ impl Round {
     }
 }
 
-fn play_holdem_round<T: HoldemPlayer>(players: &mut Vec<T>, round: &mut Round, shared_cards: &Vec<u8>, turn: Turn) {
+fn publish(events: Option<&dyn TableEvents>, msg: &Message) {
+    // events only go to observers and records
+    if let Some(events) = events {
+        events.publish(msg);
+    }
+}
+
+fn broadcast<T: HoldemPlayer>(players: &mut Vec<T>, events: Option<&dyn TableEvents>, msg: &Message) {
+    for player in players.iter_mut() {
+        player.notify(msg);
+    }
+    publish(events, msg);
+}
+
+fn post_blind<T: HoldemPlayer>(players: &mut Vec<T>, player_idx: usize, blind: Blind, current_pot: &mut u32, events: Option<&dyn TableEvents>) -> u32 {
+    // posts a forced bet and tells every player about it, returns the chips posted.
+    // A player who can't cover it posts their whole stack all in
+    let amount: u32 = blind.amount.min(players[player_idx].chips());
+    let btype: BlindType = blind.btype;
+    if amount == 0 {return 0}
+    let all_in: bool = players[player_idx].chips() == amount;
+    players[player_idx].blind(Blind{ amount, btype });
+    *current_pot += amount;
+    let msg = Message::ActionMessage {
+        player: player_idx,
+        action: Action::POST,
+        amount,
+        all_in,
+        current_pot: *current_pot,
+        turn: Turn::HOLE,
+        blind: Some(btype),
+    };
+    broadcast(players, events, &msg);
+    return amount;
+}
+
+fn play_holdem_round<T: HoldemPlayer>(players: &mut Vec<T>, round: &mut Round, shared_cards: &Vec<u8>, turn: Turn, betting: &Betting, events: Option<&dyn TableEvents>) {
     // failsafe, panic if 1000 plays are made in a round.
     let max_plays: usize = 1_000;
-    let mut round_ended: bool = false;
+    let mut round_ended: bool = round.round_ended();
 
     for _ in 0..max_plays {
         if round_ended {
@@ -125,204 +482,1549 @@ fn play_holdem_round<T: HoldemPlayer>(players: &mut Vec<T>, round: &mut Round, s
 
         let min_bet = round.min_next_bet();
         let player_idx = round.next_player_idx();
+        let chips: u32 = players[player_idx].chips();
+        let legal: LegalBets = round.legal_bets(chips, betting, &turn);
         let play = players[player_idx].play(
             &round.current_players,
             round.current_player_idx,
             round.pot_total(),
             min_bet,
+            legal.min_raise(),
+            legal.max_bet(),
             shared_cards,
             &turn,
         );
 
+        let play: Play = match play {
+            Play::Bet(val) => {
+                // players can't bet more than their stack or the rules allow, and can only bet less than a call all in
+                match legal.bet_size(val, chips) {
+                    Some(bet) => {
+                        players[player_idx].bet(shared_cards, bet);
+                        if bet == chips {round.set_all_in(player_idx)}
+                        Play::Bet(bet)
+                    },
+                    None => {
+                        players[player_idx].fold();
+                        Play::Fold
+                    },
+                }
+            },
+            Play::Fold => {
+                players[player_idx].fold();
+                Play::Fold
+            },
+        };
+
+        let (action, amount): (Action, u32) = match play {
+            Play::Fold => (Action::FOLD, 0),
+            Play::Bet(0) => (Action::CHECK, 0),
+            Play::Bet(bet) if bet <= min_bet => (Action::CALL, bet),
+            Play::Bet(bet) if min_bet == 0 => (Action::BET, bet),
+            Play::Bet(bet) => (Action::RAISE, bet),
+        };
         round.next_play(play);
+        let msg = Message::ActionMessage {
+            player: player_idx,
+            action,
+            amount,
+            all_in: amount > 0 && amount == chips,
+            current_pot: round.pot_total(),
+            turn: turn.clone(),
+            blind: None,
+        };
+        broadcast(players, events, &msg);
         round_ended = round.round_ended();
     }
     panic!("max plays of {} reached in a single holdem round", max_plays);
 }
 
-fn one_player_end_hand<T: HoldemPlayer>(round: &Round, players: &mut Vec<T>, winning_players: &Vec<usize>, zmq_socket: Option<&ZmqSocketReply>) {
-    // distributes winnings to players, and calls end_round on all players
-    let n_winners: u32 = winning_players.len().try_into().unwrap();
-    
-    // randomly distribute the remainder
-    if round.pot_total() % n_winners != 0 {
-        panic!("Pot total: {} not divisible by n winners: {}", round.pot_total(), n_winners);
+fn showdown_order(round: &Round) -> Vec<usize> {
+    // the last aggressor shows first, then the rest of the players in order
+    let n: usize = round.current_players.len();
+    let first: usize = match round.last_aggressor {
+        Some(aggressor) => round.current_players.iter().position(|&idx| idx == aggressor).unwrap_or(0),
+        None => 0,
+    };
+    return (0..n).map(|i| round.current_players[(first + i) % n]).collect();
+}
+
+fn uncalled_bet(round: &Round) -> u32 {
+    // chips the biggest bettor put in that nobody matched, they go back to the player
+    let mut bets: Vec<u32> = round.bets.clone();
+    bets.sort();
+    let (biggest, second): (u32, u32) = (bets[bets.len() - 1], bets[bets.len() - 2]);
+    let in_hand: bool = round.current_players.iter().any(|idx| round.bets[*idx] == biggest);
+    return if in_hand {biggest - second} else {0};
+}
+
+// players all in before the river in a game that doesn't just run the board once
+struct RunOut {
+    all_in: AllIn,
+    // the board when the betting ended
+    board: Vec<u8>,
+    // cards nobody in the hand holds, the rest of the board could be any of them
+    unseen: Vec<u8>,
+    // every board dealt after the betting ended, the first run is dealt street by street
+    runs: Vec<Vec<u8>>,
+}
+
+impl RunOut {
+    fn new<T: HoldemPlayer>(all_in: AllIn, round: &Round, players: &[T], board: &[u8], deck: &[u8]) -> RunOut {
+        let known: Vec<u8> = round.current_players.iter().flat_map(|idx| players[*idx].show()).chain(board.iter().copied()).collect();
+        return RunOut { all_in, board: board.to_vec(), unseen: unseen_cards(deck, &known), runs: vec![] };
     }
-    let winnings: u32 = round.pot_total() / n_winners;
-    let mut fitness: Vec<i32> = vec![];
 
-    for (i, player) in players.iter_mut().enumerate() {
-        match winning_players.contains(&i) {
-            true => {
-                player.end_round(Some(winnings));
-                fitness.push(winnings as i32 - round.bets[i] as i32);
-            },
-            false => {
-                player.end_round(None);
-                fitness.push(0 - round.bets[i] as i32);
-            },
-        }
+    fn deal_runs(&mut self, dealer: &mut Dealer, first_run: &[u8]) {
+        // the other runs are dealt from the deck after the first run
+        let n_cards: usize = first_run.len() - self.board.len();
+        let n_runs: usize = match self.all_in {
+            AllIn::RunTimes(n_runs) => n_runs.min(1 + dealer.cards_left() / n_cards),
+            _ => 1,
+        };
+        self.runs = vec![first_run.to_vec()];
+        self.runs.extend(dealer.deal_runs(&self.board, n_runs.saturating_sub(1), n_cards));
     }
+}
+
+fn betting_closed<T: HoldemPlayer>(round: &Round, players: &[T]) -> bool {
+    // no more bets can be made when at most one player still in the hand has chips
+    return round.current_players.len() > 1 && round.current_players.iter().filter(|idx| players[**idx].chips() > 0).count() <= 1;
+}
 
-    match zmq_socket {
-        Some(conn) => {
-            let header_msg: &str = "";
-            let msg = Message::EndOfRoundMessage { winnings: fitness };
-            //
-            // Returns a status message, ignore for now
-            let _reply = conn.send_recv_message(msg, header_msg);
-        },
-        None => (),
+fn award_pot(pot: &Pot, scores: &[Option<f64>], lows: &[Option<[u8; 5]>]) -> (Vec<usize>, Vec<u32>) {
+    // the winners of a pot and their chips
+    let (high, low) = pot_winners(pot, scores, lows);
+    if low.is_empty() {
+        let winnings: Vec<u32> = split_pot(pot.amount, &high);
+        return (high, winnings);
     }
+    // the high hands split the bigger half, a player can win a share of both halves
+    let mut shares: Vec<u32> = vec![0; scores.len()];
+    for (half_winners, half) in [(&high, pot.amount - pot.amount / 2), (&low, pot.amount / 2)] {
+        for (idx, share) in half_winners.iter().zip(split_pot(half, half_winners)) {
+            shares[*idx] += share;
+        }
+    }
+    let winners: Vec<usize> = pot.eligible.iter().copied().filter(|idx| high.contains(idx) || low.contains(idx)).collect();
+    let winnings: Vec<u32> = winners.iter().map(|idx| shares[*idx]).collect();
+    return (winners, winnings);
+}
+
+fn paid_players(pot: &Pot, shares: &[u32]) -> (Vec<usize>, Vec<u32>) {
+    // the players eligible for a pot who were paid from it and their chips, shares are by player
+    let winners: Vec<usize> = pot.eligible.iter().copied().filter(|idx| shares[*idx] > 0).collect();
+    let winnings: Vec<u32> = winners.iter().map(|idx| shares[*idx]).collect();
+    return (winners, winnings);
 }
 
+fn end_hand<T: HoldemPlayer>(round: &Round, players: &mut Vec<T>, shared_cards: &Vec<u8>, evaluation: &HandEvaluation, rake: &Rake, run_out: Option<&RunOut>, events: Option<&dyn TableEvents>) -> HandResult {
+    // awards every pot, reports the hand to all players and the table, and calls end_round on all players
+    let contributions: Vec<u32> = round.contributions();
+    let mut pots: Vec<Pot> = side_pots(&contributions, &round.current_players);
+    let is_showdown: bool = round.current_players.len() > 1;
+
+    // the rake is taken from the called chips, out of the main pot first
+    let called: u32 = round.pot_total() - uncalled_bet(round);
+    let mut rake_left: u32 = rake.amount(called, players.len(), round.betting_rounds > 1);
+    let mut pot_rakes: Vec<u32> = vec![];
+    for pot in pots.iter_mut() {
+        let pot_rake: u32 = rake_left.min(pot.amount);
+        pot.amount -= pot_rake;
+        rake_left -= pot_rake;
+        pot_rakes.push(pot_rake);
+    }
 
-fn compare_and_end_hand<T: HoldemPlayer>(round: &Round, players: &mut Vec<T>, shared_cards: &[u8; 5], zmq_socket: Option<&ZmqSocketReply>) {
-    let mut winning_players: Vec<usize> = vec![];
-    let mut fitness: Vec<i32> = round.bets.iter().map(|&x| 0 - x as i32).collect();
-    let mut max_score: f64 = 0.;
-    for idx in round.current_players.iter() {
-        let player_score: f64 = score_hand(&players[*idx].show(), &shared_cards);
-        if player_score > max_score {
-            winning_players = vec![*idx];
-            max_score = player_score;
+    // a board run more than once splits every pot evenly between the runs, the first run gets the odd chips
+    let boards: Vec<Vec<u8>> = match run_out {
+        Some(run_out) if run_out.runs.len() > 1 => run_out.runs.clone(),
+        _ => vec![shared_cards.clone()],
+    };
+    let hands: Vec<Option<Vec<u8>>> = (0..players.len()).map(|idx| match is_showdown && round.current_players.contains(&idx) {
+        true => Some(players[idx].show()),
+        false => None,
+    }).collect();
+    let scores: Vec<Vec<Option<f64>>> = boards.iter().map(|board| {
+        hands.iter().map(|hand| hand.as_ref().map(|cards| evaluation.score(cards, board))).collect()
+    }).collect();
+    let lows: Vec<Vec<Option<[u8; 5]>>> = boards.iter().map(|board| {
+        hands.iter().map(|hand| hand.as_ref().and_then(|cards| evaluation.low(cards, board))).collect()
+    }).collect();
+
+    // expected chips of every player from each pot after the rake, [pot][player]. Only worked out when it's reported or paid
+    let equity: Option<Vec<Vec<f64>>> = run_out.filter(|run_out| run_out.all_in.has_equity()).map(|run_out| pot_equity(&hands, &run_out.board, &run_out.unseen, &pots, evaluation));
+    let pay_equity: bool = run_out.is_some_and(|run_out| run_out.all_in == AllIn::PayEquity);
+
+    let mut won: Vec<u32> = vec![0; players.len()];
+    let mut pot_results: Vec<PotResult> = vec![];
+    for (pot_idx, pot) in pots.iter().enumerate() {
+        let (winners, winnings): (Vec<usize>, Vec<u32>) = match &equity {
+            Some(equity) if pay_equity => paid_players(pot, &pay_by_equity(pot.amount, &equity[pot_idx])),
+            _ if boards.len() == 1 => award_pot(pot, &scores[0], &lows[0]),
+            _ => {
+                let runs: Vec<usize> = (0..boards.len()).collect();
+                let mut shares: Vec<u32> = vec![0; players.len()];
+                for (run, amount) in split_pot(pot.amount, &runs).into_iter().enumerate() {
+                    let (winners, winnings) = award_pot(&Pot { amount, eligible: pot.eligible.clone() }, &scores[run], &lows[run]);
+                    for (idx, share) in winners.iter().zip(winnings) {
+                        shares[*idx] += share;
+                    }
+                }
+                paid_players(pot, &shares)
+            },
+        };
+        for (idx, amount) in winners.iter().zip(winnings.iter()) {
+            won[*idx] += amount;
+        }
+        pot_results.push(PotResult { amount: pot.amount + pot_rakes[pot_idx], winners, winnings, rake: pot_rakes[pot_idx] });
+    }
+
+    // players show when they win a pot, are all in, or at least tie the best hand shown before them.
+    // Everyone else mucks
+    let mut showdown: Vec<ShowdownHand> = vec![];
+    let mut mucked: Vec<usize> = vec![];
+    if is_showdown {
+        let mut best_shown: f64 = f64::MIN;
+        for idx in showdown_order(round) {
+            let score: f64 = scores[0][idx].unwrap();
+            if won[idx] > 0 || round.all_in[idx] || score >= best_shown {
+                let cards: Vec<u8> = players[idx].show();
+                best_shown = best_shown.max(score);
+                showdown.push(ShowdownHand {
+                    player: idx,
+                    hand_category: evaluation.category(&cards, shared_cards),
+                    cards,
+                    hand_score: score,
+                    hand_rank: 0,
+                });
+            }
+            else {
+                mucked.push(idx);
+            }
         }
-        else if player_score == max_score {
-            winning_players.push(*idx);
+        let shown_scores: Vec<f64> = showdown.iter().map(|hand| hand.hand_score).collect();
+        for hand in showdown.iter_mut() {
+            let mut better: Vec<f64> = shown_scores.iter().copied().filter(|score| *score > hand.hand_score).collect();
+            better.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            better.dedup();
+            hand.hand_rank = better.len() + 1;
         }
     }
 
-    // TODO handling split pot
-    // for now: return the players pot contributions and then split the remaining pot
-    if winning_players.len() == 1 {
-        players[winning_players[0]].end_round(Some(round.pot_total()));
+    let mut fitness: Vec<i32> = vec![];
+    for (i, player) in players.iter_mut().enumerate() {
+        match won[i] {
+            0 => player.end_round(None),
+            amount => player.end_round(Some(amount)),
+        }
+        fitness.push(won[i] as i32 - contributions[i] as i32);
+    }
+    let all_in_ev: Option<Vec<f64>> = equity.map(|equity| (0..players.len()).map(|idx| {
+        equity.iter().map(|pot| pot[idx]).sum::<f64>() - contributions[idx] as f64
+    }).collect());
+
+    let msg = Message::EndOfRoundMessage {
+        winnings: fitness.clone(),
+        board: shared_cards.clone(),
+        showdown,
+        mucked,
+        pots: pot_results,
+        stacks: players.iter().map(|player| player.chips()).collect(),
+        time_charge: rake.time_charge,
+        runs: if boards.len() > 1 {boards} else {vec![]},
+        all_in_ev: all_in_ev.clone().unwrap_or_default(),
+    };
+    broadcast(players, events, &msg);
+
+    if let Some(events) = events {
+        events.end_hand(&msg);
+    }
+    return HandResult { winnings: fitness, all_in_ev };
+}
+
+
+pub fn holdem_nl<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, events: Option<&dyn TableEvents>) -> HandResult {
+    let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(), posting: Posting::default(), all_in: AllIn::RunOnce };
+    return play_board_game(dealer, players, blinds, ante, &game, events);
+}
+
+pub fn holdem_pl<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, events: Option<&dyn TableEvents>) -> HandResult {
+    let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::PotLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(), posting: Posting::default(), all_in: AllIn::RunOnce };
+    return play_board_game(dealer, players, blinds, ante, &game, events);
+}
+
+pub fn holdem_limit<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, betting: &Betting, events: Option<&dyn TableEvents>) -> HandResult {
+    if !matches!(betting, Betting::FixedLimit { .. }) {panic!("holdem_limit needs FixedLimit betting, got {:?}", betting)}
+    let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: betting.clone(), evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(), posting: Posting::default(), all_in: AllIn::RunOnce };
+    return play_board_game(dealer, players, blinds, ante, &game, events);
+}
+
+pub fn omaha<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, betting: &Betting, events: Option<&dyn TableEvents>) -> HandResult {
+    let game = BoardGame { hole_cards: 4, deck: DeckType::Standard, betting: betting.clone(), evaluation: HandEvaluation::Omaha, button_blind: false, rake: Rake::default(), posting: Posting::default(), all_in: AllIn::RunOnce };
+    return play_board_game(dealer, players, blinds, ante, &game, events);
+}
+
+pub fn plo5<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, events: Option<&dyn TableEvents>) -> HandResult {
+    // five card pot limit Omaha, hands still use exactly two hole cards
+    let game = BoardGame { hole_cards: 5, deck: DeckType::Standard, betting: Betting::PotLimit, evaluation: HandEvaluation::Omaha, button_blind: false, rake: Rake::default(), posting: Posting::default(), all_in: AllIn::RunOnce };
+    return play_board_game(dealer, players, blinds, ante, &game, events);
+}
+
+pub fn omaha_hilo<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, betting: &Betting, events: Option<&dyn TableEvents>) -> HandResult {
+    let game = BoardGame { hole_cards: 4, deck: DeckType::Standard, betting: betting.clone(), evaluation: HandEvaluation::OmahaHiLo, button_blind: false, rake: Rake::default(), posting: Posting::default(), all_in: AllIn::RunOnce };
+    return play_board_game(dealer, players, blinds, ante, &game, events);
+}
+
+pub fn short_deck<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, ante: u32, button_blind: u32, events: Option<&dyn TableEvents>) -> HandResult {
+    // no limit short deck, every player antes and the button posts a blind
+    let game = BoardGame { hole_cards: 2, deck: DeckType::ShortDeck, betting: Betting::NoLimit, evaluation: HandEvaluation::ShortDeck, button_blind: true, rake: Rake::default(), posting: Posting::default(), all_in: AllIn::RunOnce };
+    return play_board_game(dealer, players, [0, button_blind], ante, &game, events);
+}
+
+fn charge_time<T: HoldemPlayer>(players: &mut Vec<T>, rake: &Rake) {
+    // the time charge is taken before the hand starts, the hand starts with the stacks after it
+    if rake.time_charge == 0 {return}
+    for player in players.iter_mut() {
+        let amount: u32 = rake.time_charge.min(player.chips());
+        player.blind(Blind{ amount, btype: BlindType::TimeCharge });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_hand<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, hole_cards: usize, button_blind: bool, posting: &Posting, rake: &Rake, events: Option<&dyn TableEvents>) -> Round {
+    // deals the hole cards, posts antes and blinds and returns the round for the first betting round
+    let n_players = players.len();
+
+    charge_time(players, rake);
+    publish(events, &Message::HandStartMessage {
+        players: players.iter().map(|player| player.name()).collect(),
+        stacks: players.iter().map(|player| player.chips()).collect(),
+        blinds,
+        ante,
+    });
+    if let Some(events) = events {
+        events.log_deck(dealer.seed, &dealer.deck);
+        events.log_rake(rake);
+    }
+
+    for (idx, player) in players.iter_mut().enumerate() {
+        let cards: Vec<u8> = (0..hole_cards).map(|_| dealer.next_card()).collect();
+        player.recieve_cards(cards.clone());
+        player.assign_position(idx + 1, n_players);
+        publish(events, &Message::DealMessage { player: idx, cards: Some(cards) });
+    }
+
+    // a button blind is posted by the last seat instead of the usual little and big blinds
+    let big_blind_seat: usize = if button_blind {n_players - 1} else {1};
+    let both_blinds: bool = !button_blind && blinds[0] > 0;
+    let mut pre_bets: Vec<u32> = vec![0; n_players];
+    let mut dead: Vec<u32> = vec![0; n_players];
+    let mut current_pot: u32 = 0;
+    if both_blinds && posting.ante_type == AnteType::BigBlind {
+        dead[big_blind_seat] += post_blind(players, big_blind_seat, Blind{ amount: ante, btype: BlindType::Ante }, &mut current_pot, events);
     }
     else {
-        let pot = round.pot_total();
-        let mut winners_contrib: u32 = 0;
-        for idx in winning_players.iter() {
-            winners_contrib += players[*idx].pot_contribution();
+        for (idx, bet) in pre_bets.iter_mut().enumerate() {
+            *bet += post_blind(players, idx, Blind{ amount: ante, btype: BlindType::Ante }, &mut current_pot, events);
         }
-        // potential to round down?
-        let pot_split: u32 = (pot - winners_contrib) / winning_players.len() as u32;
-        for idx in winning_players.iter() {
-            let player_contrib = players[*idx].pot_contribution();
-            players[*idx].end_round(Some(player_contrib + pot_split));
-            fitness[*idx] += pot_split as i32;
+    }
+    if !button_blind {
+        pre_bets[0] += post_blind(players, 0, Blind{ amount: blinds[0], btype: BlindType::Little }, &mut current_pot, events);
+    }
+    pre_bets[big_blind_seat] += post_blind(players, big_blind_seat, Blind{ amount: blinds[1], btype: BlindType::Big }, &mut current_pot, events);
+
+    // heads up there is nobody to straddle, a player who can't cover the straddle doesn't post it
+    let straddle_seat: Option<usize> = match posting.straddle {
+        Straddle::Utg if both_blinds && n_players > 2 => Some(2),
+        Straddle::Button if both_blinds && n_players > 2 => Some(n_players - 1),
+        _ => None,
+    }.filter(|seat| players[*seat].chips() >= 2 * blinds[1]);
+    if let Some(seat) = straddle_seat {
+        pre_bets[seat] += post_blind(players, seat, Blind{ amount: 2 * blinds[1], btype: BlindType::Straddle }, &mut current_pot, events);
+    }
+
+    // returning players post what they can of the missed blinds, a straddle stands in for the big blind
+    for &idx in posting.missed_blinds.iter() {
+        if !both_blinds || idx < 2 || idx >= n_players {continue}
+        if Some(idx) != straddle_seat {
+            pre_bets[idx] += post_blind(players, idx, Blind{ amount: blinds[1], btype: BlindType::Big }, &mut current_pot, events);
         }
+        dead[idx] += post_blind(players, idx, Blind{ amount: blinds[0], btype: BlindType::Dead }, &mut current_pot, events);
     }
 
-    match zmq_socket {
-        Some(conn) => {
-            let header_msg: &str = "";
-            let msg = Message::EndOfRoundMessage { winnings: fitness };
-            //
-            // Returns a status message, ignore for now
-            let _reply = conn.send_recv_message(msg, header_msg);
-        },
-        None => (),
+    let mut round = Round::new(players.len(), Some(pre_bets));
+    round.dead = dead;
+    round.set_big_blind(blinds[1]);
+    // players the forced bets put all in don't act
+    for idx in (0..n_players).filter(|idx| players[*idx].chips() == 0) {
+        round.set_all_in(idx);
+    }
+    round.skip_all_in();
+    // the big blind is the first bet of the hole cards round, a straddle raises it
+    if blinds[1] > 0 {round.n_raises = 1}
+    if let Some(seat) = straddle_seat {
+        // the straddler acts last before the flop, and raises have to be at least the straddle
+        round.n_raises = 2;
+        round.min_raise = 2 * blinds[1];
+        round.first_to_act((seat + 1) % n_players);
     }
+    return round;
 }
 
-
-pub fn holdem_nl<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, zmq_socket: Option<&ZmqSocketReply>) {
+pub fn play_board_game<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, game: &BoardGame, events: Option<&dyn TableEvents>) -> HandResult {
     if blinds[0] > blinds[1] {panic!("Blinds must be passed in [Little, Big]")}
     if players.len() < 2 {panic!("Can't play holdem with less than 2 players")}
+    let betting: &Betting = &game.betting;
 
+    // the dealer keeps the deck of its last game, every hand sets its own
+    dealer.use_cards(game.deck.cards());
     dealer.shuffle();
+    if players.len() * game.hole_cards + 5 > dealer.deck.len() {panic!("Not enough cards to deal {} hole cards to {} players", game.hole_cards, players.len())}
 
-    let n_players = players.len();
-    let mut player_position: usize = 1;
+    let mut round: Round = start_hand(dealer, players, blinds, ante, game.hole_cards, game.button_blind, &game.posting, &game.rake, events);
+    if let Some(events) = events {
+        events.log_all_in(game.all_in);
+    }
 
-    for player in players.iter_mut() {
-        player.recieve_cards([dealer.next_card(), dealer.next_card()]);
-        player.assign_position(player_position, n_players);
-        player.blind(Blind{ amount: ante, btype: BlindType::Ante });
-        player_position += 1;
+    let mut shared_cards: Vec<u8> = vec![];
+    let mut run_out: Option<RunOut> = None;
+
+    // Play a betting round with hole cards, then one after each of the flop, turn and river
+    play_holdem_round(players, &mut round, &shared_cards, Turn::HOLE, betting, events);
+    for (turn, n_cards) in [(Turn::FLOP, 3), (Turn::TURN, 1), (Turn::RIVER, 1)] {
+        if round.one_remaining_player().is_some() {break}
+        if run_out.is_none() && !game.all_in.is_run_once() && betting_closed(&round, players) {
+            run_out = Some(RunOut::new(game.all_in, &round, players, &shared_cards, &dealer.deck));
+        }
+        for _ in 0..n_cards { shared_cards.push(dealer.next_card()); }
+        publish(events, &Message::BoardMessage { turn: turn.clone(), cards: shared_cards.clone() });
+        play_holdem_round(players, &mut round, &shared_cards, turn, betting, events);
+    }
+    if let Some(run_out) = run_out.as_mut() {
+        run_out.deal_runs(dealer, &shared_cards);
     }
 
-    players[1].blind(Blind{ amount: blinds[0], btype: BlindType::Little });
+    // Remaining players compare cards
+    return end_hand(&round, players, &shared_cards, &game.evaluation, &game.rake, run_out.as_ref(), events);
+}
 
-    let player_2: usize = players.len() % 2;
-    players[player_2].blind(Blind{ amount: blinds[1], btype: BlindType::Big });
 
-    let mut pre_bets: Vec<u32> = vec![ante; n_players];
-    pre_bets[0] += blinds[0];
-    pre_bets[1] += blinds[1];
+pub fn five_card_draw<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, betting: &Betting, events: Option<&dyn TableEvents>) -> HandResult {
+    let game = DrawGame { n_draws: 1, deck: DeckType::Standard, betting: betting.clone(), evaluation: HandEvaluation::Holdem, rake: Rake::default() };
+    return play_draw_game(dealer, players, blinds, ante, &game, events);
+}
 
-    let mut round = Round::new(players.len(), Some(pre_bets));
+pub fn triple_draw<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, betting: &Betting, events: Option<&dyn TableEvents>) -> HandResult {
+    // 2-7 triple draw
+    let game = DrawGame { n_draws: 3, deck: DeckType::Standard, betting: betting.clone(), evaluation: HandEvaluation::DeuceToSeven, rake: Rake::default() };
+    return play_draw_game(dealer, players, blinds, ante, &game, events);
+}
 
-    let mut shared_cards: Vec<u8> = vec![];
-    let mut winning_players: Vec<usize> = vec![];
-
-    // Play a betting round with hole cards
-    play_holdem_round(players, &mut round, &shared_cards, Turn::HOLE);
-    match round.one_remaining_player() {
-        Some(played_idx) => {
-            winning_players.push(played_idx);
-            one_player_end_hand(&round, players, &winning_players, zmq_socket);
-            return;
-        },
-        None => (),
-    };
+fn draw_cards<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, player_idx: usize, turn: &Turn, events: Option<&dyn TableEvents>) {
+    // replaces the cards a player throws away, a player discarding cards they don't hold stands pat
+    let mut cards: Vec<u8> = players[player_idx].show();
+    let mut discards: Vec<u8> = players[player_idx].discard(turn);
+    discards.sort();
+    discards.dedup();
+    if !discards.iter().all(|card| cards.contains(card)) {discards.clear()}
 
-    // Flop
-    for _ in 0..3 { shared_cards.push(dealer.next_card()); }
-    play_holdem_round(players, &mut round, &shared_cards, Turn::FLOP);
-    match round.one_remaining_player() {
-        Some(played_idx) => {
-            winning_players.push(played_idx);
-            one_player_end_hand(&round, players, &winning_players, zmq_socket);
-            return;
-        },
-        None => (),
-    };
+    cards.retain(|card| !discards.contains(card));
+    cards.extend(dealer.draw(&discards));
+    players[player_idx].recieve_cards(cards.clone());
+    publish(events, &Message::DealMessage { player: player_idx, cards: Some(cards) });
+    broadcast(players, events, &Message::DrawResultMessage { player: player_idx, n_cards: discards.len(), turn: turn.clone() });
+}
 
-    // Turn
-    shared_cards.push(dealer.next_card());
-    play_holdem_round(players, &mut round, &shared_cards, Turn::TURN);
-    match round.one_remaining_player() {
-        Some(played_idx) => {
-            winning_players.push(played_idx);
-            one_player_end_hand(&round, players, &winning_players, zmq_socket);
-            return;
-        },
-        None => (),
+pub fn play_draw_game<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, blinds: [u32; 2], ante: u32, game: &DrawGame, events: Option<&dyn TableEvents>) -> HandResult {
+    if blinds[0] > blinds[1] {panic!("Blinds must be passed in [Little, Big]")}
+    if players.len() < 2 {panic!("Can't play a draw game with less than 2 players")}
+    if game.n_draws > 3 {panic!("Draw games have at most 3 draws, got {}", game.n_draws)}
+
+    dealer.use_cards(game.deck.cards());
+    dealer.shuffle();
+    if players.len() * 5 > dealer.deck.len() {panic!("Not enough cards to deal five cards to {} players", players.len())}
+
+    let mut round: Round = start_hand(dealer, players, blinds, ante, 5, false, &Posting::default(), &game.rake, events);
+    let no_board: Vec<u8> = vec![];
+
+    // hands are kept to muck them when a player folds, so the stub can be shuffled with them
+    let mut hands: Vec<Option<Vec<u8>>> = players.iter().map(|player| Some(player.show())).collect();
+
+    play_holdem_round(players, &mut round, &no_board, Turn::HOLE, &game.betting, events);
+    // fixed limit bets the big bet in the later half of the betting rounds
+    let big_bet_round: usize = game.n_draws.div_ceil(2);
+    for (draw, turn) in [Turn::DRAW1, Turn::DRAW2, Turn::DRAW3].into_iter().take(game.n_draws).enumerate() {
+        if round.one_remaining_player().is_some() {break}
+        for (idx, hand) in hands.iter_mut().enumerate() {
+            if round.current_players.contains(&idx) {continue}
+            if let Some(cards) = hand.take() {
+                dealer.discard(&cards);
+            }
+        }
+        // every player still in the hand draws in seat order, all in players too
+        for idx in round.current_players.clone() {
+            draw_cards(dealer, players, idx, &turn, events);
+            hands[idx] = Some(players[idx].show());
+        }
+        let betting: Betting = match &game.betting {
+            Betting::FixedLimit { bets, raise_cap, heads_up_uncapped } if draw + 1 >= big_bet_round => {
+                Betting::FixedLimit { bets: [bets[1], bets[1]], raise_cap: *raise_cap, heads_up_uncapped: *heads_up_uncapped }
+            },
+            betting => betting.clone(),
+        };
+        play_holdem_round(players, &mut round, &no_board, turn, &betting, events);
+    }
+
+    return end_hand(&round, players, &no_board, &game.evaluation, &game.rake, None, events);
+}
+
+// Kuhn and Leduc poker are small research games with known equilibria.
+// Every player antes 1 and gets one card, betting is fixed limit with the bet size of each round
+
+pub fn kuhn<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, events: Option<&dyn TableEvents>) -> HandResult {
+    // three cards, one betting round with a single bet of 1
+    if players.len() != 2 {panic!("Kuhn poker is played by 2 players, got {}", players.len())}
+    dealer.use_cards(kuhn_deck());
+    dealer.shuffle();
+
+    let mut round: Round = start_hand(dealer, players, [0, 0], 1, 1, false, &Posting::default(), &Rake::default(), events);
+    let no_board: Vec<u8> = vec![];
+    let betting = Betting::FixedLimit { bets: [1, 1], raise_cap: Some(1), heads_up_uncapped: false };
+    play_holdem_round(players, &mut round, &no_board, Turn::HOLE, &betting, events);
+    return end_hand(&round, players, &no_board, &HandEvaluation::Leduc, &Rake::default(), None, events);
+}
+
+pub fn leduc<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, events: Option<&dyn TableEvents>) -> HandResult {
+    // six cards, bets of 2 before and 4 after the board card, with a bet and a raise in each round
+    if players.len() != 2 {panic!("Leduc hold'em is played by 2 players, got {}", players.len())}
+    dealer.use_cards(leduc_deck());
+    dealer.shuffle();
+
+    let mut round: Round = start_hand(dealer, players, [0, 0], 1, 1, false, &Posting::default(), &Rake::default(), events);
+    let mut shared_cards: Vec<u8> = vec![];
+    let betting = Betting::FixedLimit { bets: [2, 2], raise_cap: Some(2), heads_up_uncapped: false };
+    play_holdem_round(players, &mut round, &shared_cards, Turn::HOLE, &betting, events);
+    if round.one_remaining_player().is_some() {
+        return end_hand(&round, players, &shared_cards, &HandEvaluation::Leduc, &Rake::default(), None, events);
     };
-    
-    // River
+
     shared_cards.push(dealer.next_card());
-    play_holdem_round(players, &mut round, &shared_cards, Turn::RIVER);
-    match round.one_remaining_player() {
-        Some(played_idx) => {
-            winning_players.push(played_idx);
-            one_player_end_hand(&round, players, &winning_players, zmq_socket);
-            return;
-        },
-        None => (),
-    };
+    publish(events, &Message::BoardMessage { turn: Turn::FLOP, cards: shared_cards.clone() });
+    let betting = Betting::FixedLimit { bets: [4, 4], raise_cap: Some(2), heads_up_uncapped: false };
+    play_holdem_round(players, &mut round, &shared_cards, Turn::FLOP, &betting, events);
+    return end_hand(&round, players, &shared_cards, &HandEvaluation::Leduc, &Rake::default(), None, events);
+}
+
+pub fn seven_card_stud<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, ante: u32, bring_in: u32, betting: &Betting, events: Option<&dyn TableEvents>) -> HandResult {
+    return play_stud(dealer, players, ante, bring_in, betting, &HandEvaluation::Holdem, DeckType::Standard, &Rake::default(), events);
+}
+
+pub fn razz<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, ante: u32, bring_in: u32, betting: &Betting, events: Option<&dyn TableEvents>) -> HandResult {
+    return play_stud(dealer, players, ante, bring_in, betting, &HandEvaluation::Razz, DeckType::Standard, &Rake::default(), events);
+}
+
+fn bring_in_player(up_cards: &[Vec<u8>], evaluation: &HandEvaluation) -> usize {
+    // the lowest up card brings in, or the highest in razz. Suits break ties, clubs are lowest and spades highest
+    let suit_rank = |card: u8| 3 - (card - 1) / 13;
+    let players = 0..up_cards.len();
+    match evaluation {
+        HandEvaluation::Razz => return players.max_by_key(|&idx| (low_rank(up_cards[idx][0]), suit_rank(up_cards[idx][0]))).unwrap(),
+        _ => return players.min_by_key(|&idx| ((up_cards[idx][0] - 1) % 13, suit_rank(up_cards[idx][0]))).unwrap(),
+    }
+}
+
+fn best_showing(up_cards: &[Vec<u8>], current_players: &[usize], evaluation: &HandEvaluation) -> usize {
+    // the best hand showing acts first from fourth street on, ties go to the first seat
+    let mut best: usize = current_players[0];
+    for idx in current_players.iter().copied() {
+        if evaluation.score(&up_cards[idx], &[]) > evaluation.score(&up_cards[best], &[]) {
+            best = idx;
+        }
+    }
+    return best;
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn play_stud<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, ante: u32, bring_in: u32, betting: &Betting, evaluation: &HandEvaluation, deck: DeckType, rake: &Rake, events: Option<&dyn TableEvents>) -> HandResult {
+    if players.len() < 2 {panic!("Can't play stud with less than 2 players")}
+
+    dealer.use_cards(deck.cards());
+    if players.len() * 7 > dealer.deck.len() {panic!("Can't deal seven card stud to {} players", players.len())}
+    dealer.shuffle();
+
+    let n_players = players.len();
+
+    charge_time(players, rake);
+    publish(events, &Message::HandStartMessage {
+        players: players.iter().map(|player| player.name()).collect(),
+        stacks: players.iter().map(|player| player.chips()).collect(),
+        blinds: [0, 0],
+        ante,
+    });
+    if let Some(events) = events {
+        events.log_deck(dealer.seed, &dealer.deck);
+        events.log_rake(rake);
+    }
+
+    let mut current_pot: u32 = 0;
+    let antes: Vec<u32> = (0..n_players).map(|idx| post_blind(players, idx, Blind{ amount: ante, btype: BlindType::Ante }, &mut current_pot, events)).collect();
+    let mut round = Round::new(n_players, Some(antes));
+    // without limits the bring in is the smallest bet
+    round.set_big_blind(bring_in);
+    // players the antes put all in don't act
+    for idx in (0..n_players).filter(|idx| players[*idx].chips() == 0) {
+        round.set_all_in(idx);
+    }
+
+    // players get every card dealt to them so far on each street, up cards are also shown to everyone
+    let mut cards: Vec<Vec<u8>> = vec![vec![]; n_players];
+    let mut up_cards: Vec<Vec<u8>> = vec![vec![]; n_players];
+    let no_board: Vec<u8> = vec![];
+    for turn in [Turn::THIRD, Turn::FOURTH, Turn::FIFTH, Turn::SIXTH, Turn::SEVENTH] {
+        let (n_down, n_up): (usize, usize) = match turn {
+            Turn::THIRD => (2, 1),
+            Turn::SEVENTH => (1, 0),
+            _ => (0, 1),
+        };
+        for idx in round.current_players.clone() {
+            for _ in 0..n_down {
+                cards[idx].push(dealer.next_card());
+            }
+            for _ in 0..n_up {
+                let card: u8 = dealer.next_card();
+                cards[idx].push(card);
+                up_cards[idx].push(card);
+            }
+            players[idx].recieve_cards(cards[idx].clone());
+            if turn == Turn::THIRD {
+                players[idx].assign_position(idx + 1, n_players);
+            }
+            publish(events, &Message::DealMessage { player: idx, cards: Some(cards[idx].clone()) });
+        }
+        let shown: Vec<Vec<u8>> = (0..n_players).map(|idx| match round.current_players.contains(&idx) {
+            true => up_cards[idx].clone(),
+            false => vec![],
+        }).collect();
+        broadcast(players, events, &Message::UpCardsMessage { turn: turn.clone(), up_cards: shown });
+
+        if turn == Turn::THIRD {
+            let first: usize = bring_in_player(&up_cards, evaluation);
+            let amount: u32 = post_blind(players, first, Blind{ amount: bring_in, btype: BlindType::BringIn }, &mut current_pot, events);
+            round.bets[first] += amount;
+            round.bring_in = amount;
+            if players[first].chips() == 0 {round.set_all_in(first)}
+            if amount > 0 {
+                // posting the bring in is the player's first action, they only act again when it's completed
+                round.n_plays = 1;
+                round.first_to_act((first + 1) % n_players);
+            }
+            else {
+                round.first_to_act(first);
+            }
+        }
+        else {
+            round.first_to_act(best_showing(&up_cards, &round.current_players, evaluation));
+        }
+
+        play_holdem_round(players, &mut round, &no_board, turn, betting, events);
+        if round.one_remaining_player().is_some() {
+            return end_hand(&round, players, &no_board, evaluation, rake, None, events);
+        };
+    }
 
     // Remaining players compare cards
-    let final_cards: [u8; 5] = shared_cards.as_slice().try_into().unwrap();
-    compare_and_end_hand(&round, players, &final_cards, zmq_socket);
+    return end_hand(&round, players, &no_board, evaluation, rake, None, events);
 }
 
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use serial_test::serial;
+    use crate::pokersim::coms::tests as coms_tests;
+    use crate::pokersim::card_enums::Card;
+    use crate::pokersim::dealer::standard_deck;
+    use crate::pokersim::equity::equity;
+    use crate::pokersim::rules::{play_hand, GameRules};
+    use std::cell::RefCell;
+
+    fn card(suit: &str, value: &str) -> u8 {
+        return Card::card_from_name(suit, value).to_int();
+    }
+
+    #[test]
+    pub fn test_side_pot_showdown() {
+        // player 1 is all in for 50 with the best hand, player 2 beats player 0 for the side pot
+        let board: Vec<u8> = vec![
+            card("spade", "two"), card("heart", "seven"), card("club", "nine"), card("diamond", "jack"), card("spade", "king"),
+        ];
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(900, vec![]), ScriptedPlayer::new(0, vec![]), ScriptedPlayer::new(900, vec![])];
+        players[0].recieve_cards(vec![card("heart", "three"), card("club", "four")]);
+        players[1].recieve_cards(vec![card("heart", "king"), card("club", "king")]);
+        players[2].recieve_cards(vec![card("heart", "jack"), card("club", "five")]);
+
+        let mut round = Round::new(3, Some(vec![100, 50, 100]));
+        round.set_all_in(1);
+        end_hand(&round, &mut players, &board, &HandEvaluation::Holdem, &Rake::default(), None, None);
+
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), vec![900, 150, 1000]);
+        for player in players.iter() {
+            assert_eq!(player.notifications.len(), 1);
+        }
+        match &players[0].notifications[0] {
+            Message::EndOfRoundMessage { winnings, board: msg_board, showdown, mucked, pots, stacks, .. } => {
+                assert_eq!(winnings, &vec![-100, 100, 0]);
+                assert_eq!(msg_board, &board);
+                assert_eq!(pots, &vec![
+                    PotResult { amount: 150, winners: vec![1], winnings: vec![150], rake: 0 },
+                    PotResult { amount: 100, winners: vec![2], winnings: vec![100], rake: 0 },
+                ]);
+                assert_eq!(stacks, &vec![900, 150, 1000]);
+
+                // player 0 shows first with the worst hand, the others beat it
+                let shown: Vec<(usize, HandCategory, usize)> = showdown.iter().map(|hand| (hand.player, hand.hand_category, hand.hand_rank)).collect();
+                assert_eq!(shown, vec![(0, HandCategory::HIGHCARD, 3), (1, HandCategory::TRIPS, 1), (2, HandCategory::PAIR, 2)]);
+                assert!(mucked.is_empty());
+            },
+            msg => panic!("Expected an EndOfRoundMessage, recieved {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_rake() {
+        let rake = Rake { percent: 10.0, cap: Some(20), player_caps: vec![(2, 5)], no_flop_no_drop: true, time_charge: 0 };
+        assert_eq!(rake.amount(150, 3, true), 15);
+        assert_eq!(rake.amount(250, 3, true), 20);
+        assert_eq!(rake.amount(250, 2, true), 5);
+        assert_eq!(rake.amount(250, 3, false), 0);
+        assert_eq!(Rake { percent: 5.0, ..Rake::default() }.amount(259, 9, false), 12);
+
+        // the side pot showdown of test_side_pot_showdown after the flop, the rake comes out of the main pot
+        let board: Vec<u8> = vec![
+            card("spade", "two"), card("heart", "seven"), card("club", "nine"), card("diamond", "jack"), card("spade", "king"),
+        ];
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(900, vec![]), ScriptedPlayer::new(0, vec![]), ScriptedPlayer::new(900, vec![])];
+        players[0].recieve_cards(vec![card("heart", "three"), card("club", "four")]);
+        players[1].recieve_cards(vec![card("heart", "king"), card("club", "king")]);
+        players[2].recieve_cards(vec![card("heart", "jack"), card("club", "five")]);
+        let mut round = Round::new(3, Some(vec![100, 50, 100]));
+        round.set_all_in(1);
+        round.reset_round();
+        round.reset_round();
+        end_hand(&round, &mut players, &board, &HandEvaluation::Holdem, &rake, None, None);
+
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), vec![900, 130, 1000]);
+        match &players[0].notifications[0] {
+            Message::EndOfRoundMessage { winnings, pots, .. } => {
+                assert_eq!(winnings, &vec![-100, 80, 0]);
+                assert_eq!(pots, &vec![
+                    PotResult { amount: 150, winners: vec![1], winnings: vec![130], rake: 20 },
+                    PotResult { amount: 100, winners: vec![2], winnings: vec![100], rake: 0 },
+                ]);
+            },
+            msg => panic!("Expected an EndOfRoundMessage, recieved {:?}", msg),
+        }
+
+        // nothing is taken from the uncalled part of a bet
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(900, vec![]), ScriptedPlayer::new(900, vec![])];
+        let mut round = Round::new(2, Some(vec![40, 100]));
+        round.reset_round();
+        round.reset_round();
+        round.current_players = vec![1];
+        end_hand(&round, &mut players, &board, &HandEvaluation::Holdem, &Rake { percent: 10.0, ..Rake::default() }, None, None);
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), vec![900, 1032]);
+
+        // every player pays the time charge before the hand, it isn't part of the pot
+        let mut dealer = Dealer::with_seed(3);
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(500, vec![]), ScriptedPlayer::new(500, vec![]), ScriptedPlayer::new(500, vec![])];
+        let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake { time_charge: 3, ..rake }, posting: Posting::default(), all_in: AllIn::RunOnce };
+        play_board_game(&mut dealer, &mut players, [5, 10], 0, &game, None);
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { winnings, pots, stacks, time_charge, .. } => {
+                assert_eq!(*time_charge, 3);
+                // winnings are what the hand won or lost, without the time charge
+                assert_eq!(winnings, &vec![-10, -10, 17]);
+                assert_eq!(pots, &vec![PotResult { amount: 30, winners: vec![2], winnings: vec![27], rake: 3 }]);
+                assert_eq!(stacks, &vec![487, 487, 514]);
+            },
+            msg => panic!("Expected an EndOfRoundMessage, recieved {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_showdown_muck() {
+        // player 1 bet the river and shows first, player 0 can't beat it and mucks
+        let board: Vec<u8> = vec![
+            card("spade", "two"), card("heart", "seven"), card("club", "nine"), card("diamond", "jack"), card("spade", "king"),
+        ];
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(900, vec![]), ScriptedPlayer::new(900, vec![])];
+        players[0].recieve_cards(vec![card("heart", "three"), card("club", "four")]);
+        players[1].recieve_cards(vec![card("heart", "king"), card("club", "ace")]);
+
+        let mut round = Round::new(2, Some(vec![100, 100]));
+        round.reset_round();
+        round.next_play(Play::Bet(0));
+        round.next_play(Play::Bet(50));
+        round.next_play(Play::Bet(50));
+        assert!(round.round_ended());
+        end_hand(&round, &mut players, &board, &HandEvaluation::Holdem, &Rake::default(), None, None);
+
+        match &players[0].notifications[0] {
+            Message::EndOfRoundMessage { showdown, mucked, pots, .. } => {
+                assert_eq!(showdown.len(), 1);
+                assert_eq!(showdown[0].player, 1);
+                assert_eq!(showdown[0].cards, [card("heart", "king"), card("club", "ace")]);
+                assert_eq!(mucked, &vec![0]);
+                assert_eq!(pots, &vec![PotResult { amount: 300, winners: vec![1], winnings: vec![300], rake: 0 }]);
+            },
+            msg => panic!("Expected an EndOfRoundMessage, recieved {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_all_in_hand() {
+        // player 0 shoves, player 1 calls for less, player 2 calls. The board runs out without more plays
+        let mut dealer = Dealer::new();
+        let mut players: Vec<ScriptedPlayer> = vec![
+            ScriptedPlayer::new(1_000, vec![Play::Bet(5_000)]),
+            ScriptedPlayer::new(400, vec![Play::Bet(1_000)]),
+            ScriptedPlayer::new(2_000, vec![]),
+        ];
+        holdem_nl(&mut dealer, &mut players, [10, 20], 5, None);
+
+        assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 3_400);
+        // player 2 still has chips behind but nobody left to bet against
+        assert_eq!(players.iter().map(|player| player.n_turns).collect::<Vec<usize>>(), vec![1, 1, 1]);
+        match players[2].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { board, pots, stacks, winnings, .. } => {
+                assert_eq!(board.len(), 5);
+                assert_eq!(pots.iter().map(|pot| pot.amount).sum::<u32>(), 2_400);
+                assert_eq!(pots[0].amount, 1_200);
+                assert_eq!(stacks.iter().sum::<u32>(), 3_400);
+                assert_eq!(winnings.iter().sum::<i32>(), 0);
+            },
+            msg => panic!("Expected an EndOfRoundMessage, recieved {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_action_broadcast() {
+        let mut dealer = Dealer::new();
+        let mut players: Vec<ScriptedPlayer> = vec![
+            ScriptedPlayer::new(1_000, vec![Play::Bet(90)]),
+            ScriptedPlayer::new(1_000, vec![Play::Bet(2_000)]),
+            ScriptedPlayer::new(1_000, vec![Play::Fold]),
+        ];
+        holdem_nl(&mut dealer, &mut players, [10, 20], 0, None);
+
+        // every player sees every action, then the end of the hand
+        for player in players.iter() {
+            let actions: Vec<(usize, Action, u32, bool, u32)> = player.notifications.iter().filter_map(|msg| match msg {
+                Message::ActionMessage { player, action, amount, all_in, current_pot, .. } => Some((*player, action.clone(), *amount, *all_in, *current_pot)),
+                _ => None,
+            }).collect();
+            assert_eq!(actions, vec![
+                (0, Action::POST, 10, false, 10),
+                (1, Action::POST, 20, false, 30),
+                (0, Action::RAISE, 90, false, 120),
+                (1, Action::RAISE, 980, true, 1_100),
+                (2, Action::FOLD, 0, false, 1_100),
+                (0, Action::CALL, 900, true, 2_000),
+            ]);
+            assert!(matches!(player.notifications.last().unwrap(), Message::EndOfRoundMessage { .. }));
+        }
+    }
+
+    #[test]
+    pub fn test_every_player_acts_each_street() {
+        let mut dealer = Dealer::new();
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(1_000, vec![]), ScriptedPlayer::new(1_000, vec![]), ScriptedPlayer::new(1_000, vec![])];
+        holdem_nl(&mut dealer, &mut players, [10, 20], 0, None);
+
+        // everyone calls preflop and checks the flop, turn and river
+        assert_eq!(players.iter().map(|player| player.n_turns).collect::<Vec<usize>>(), vec![4, 4, 4]);
+        assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 3_000);
+    }
+
+    fn actions(player: &ScriptedPlayer, turn: Turn) -> Vec<(usize, Action, u32)> {
+        return player.notifications.iter().filter_map(|msg| match msg {
+            Message::ActionMessage { player, action, amount, turn: action_turn, .. } if *action_turn == turn && *action != Action::POST => Some((*player, action.clone(), *amount)),
+            _ => None,
+        }).collect();
+    }
+
+    fn posts(player: &ScriptedPlayer) -> Vec<(usize, u32, BlindType)> {
+        return player.notifications.iter().filter_map(|msg| match msg {
+            Message::ActionMessage { player, action: Action::POST, amount, blind: Some(blind), .. } => Some((*player, *amount, *blind)),
+            _ => None,
+        }).collect();
+    }
+
+    #[test]
+    pub fn test_straddles() {
+        // the straddler posts two big blinds and acts last before the flop, everyone calls
+        let straddled = |straddle: Straddle| -> Vec<ScriptedPlayer> {
+            let mut players: Vec<ScriptedPlayer> = (0..4).map(|_| ScriptedPlayer::new(1_000, vec![])).collect();
+            let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(),
+                posting: Posting { straddle, ..Posting::default() }, all_in: AllIn::RunOnce };
+            play_board_game(&mut Dealer::with_seed(5), &mut players, [10, 20], 0, &game, None);
+            return players;
+        };
+
+        let players: Vec<ScriptedPlayer> = straddled(Straddle::Utg);
+        assert_eq!(posts(&players[0]), vec![(0, 10, BlindType::Little), (1, 20, BlindType::Big), (2, 40, BlindType::Straddle)]);
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![(3, Action::CALL, 40), (0, Action::CALL, 30), (1, Action::CALL, 20), (2, Action::CHECK, 0)]);
+        // the blinds act first after the flop again
+        assert_eq!(actions(&players[0], Turn::FLOP)[0].0, 0);
+        assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 4_000);
+
+        let players: Vec<ScriptedPlayer> = straddled(Straddle::Button);
+        assert_eq!(posts(&players[0])[2], (3, 40, BlindType::Straddle));
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![(0, Action::CALL, 30), (1, Action::CALL, 20), (2, Action::CALL, 40), (3, Action::CHECK, 0)]);
+
+        // heads up nobody straddles, a raise over the straddle reopens the betting for it
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(1_000, vec![]), ScriptedPlayer::new(1_000, vec![])];
+        let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(),
+            posting: Posting { straddle: Straddle::Utg, ..Posting::default() }, all_in: AllIn::RunOnce };
+        play_board_game(&mut Dealer::with_seed(5), &mut players, [10, 20], 0, &game, None);
+        assert_eq!(posts(&players[0]).len(), 2);
+
+        let mut players: Vec<ScriptedPlayer> = vec![
+            ScriptedPlayer::new(1_000, vec![Play::Bet(100), Play::Fold]),
+            ScriptedPlayer::new(1_000, vec![Play::Fold]),
+            ScriptedPlayer::new(1_000, vec![Play::Bet(140)]),
+        ];
+        play_board_game(&mut Dealer::with_seed(5), &mut players, [10, 20], 0, &game, None);
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![(0, Action::RAISE, 100), (1, Action::FOLD, 0), (2, Action::RAISE, 140), (0, Action::FOLD, 0)]);
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), vec![890, 980, 1_130]);
+    }
+
+    #[test]
+    pub fn test_big_blind_ante() {
+        // the big blind antes for the table, the ante isn't part of its bet
+        let mut players: Vec<ScriptedPlayer> = (0..3).map(|_| ScriptedPlayer::new(1_000, vec![])).collect();
+        let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::PotLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(),
+            posting: Posting { ante_type: AnteType::BigBlind, ..Posting::default() }, all_in: AllIn::RunOnce };
+        play_board_game(&mut Dealer::with_seed(8), &mut players, [10, 20], 20, &game, None);
+        assert_eq!(posts(&players[0]), vec![(1, 20, BlindType::Ante), (0, 10, BlindType::Little), (1, 20, BlindType::Big)]);
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![(0, Action::CALL, 10), (1, Action::CHECK, 0), (2, Action::CALL, 20)]);
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { winnings, pots, .. } => {
+                assert_eq!(pots.iter().map(|pot| pot.amount).sum::<u32>(), 80);
+                // the big blind lost or won its ante with the hand
+                assert_eq!(winnings.iter().sum::<i32>(), 0);
+            },
+            msg => panic!("Expected an EndOfRoundMessage, recieved {:?}", msg),
+        }
+
+        // a missed small blind is dead money, the returning player only has to match the big blind
+        let mut players: Vec<ScriptedPlayer> = (0..4).map(|_| ScriptedPlayer::new(1_000, vec![])).collect();
+        let game = BoardGame { posting: Posting { missed_blinds: vec![1, 3], ..Posting::default() }, ..game };
+        play_board_game(&mut Dealer::with_seed(8), &mut players, [10, 20], 0, &game, None);
+        assert_eq!(posts(&players[0]), vec![(0, 10, BlindType::Little), (1, 20, BlindType::Big), (3, 20, BlindType::Big), (3, 10, BlindType::Dead)]);
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![(0, Action::CALL, 10), (1, Action::CHECK, 0), (2, Action::CALL, 20), (3, Action::CHECK, 0)]);
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { pots, .. } => assert_eq!(pots.iter().map(|pot| pot.amount).sum::<u32>(), 90),
+            msg => panic!("Expected an EndOfRoundMessage, recieved {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_missed_blinds_short_stack() {
+        // a returning player who can't cover both missed blinds posts the big blind and what's left as dead money
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(1_000, vec![]), ScriptedPlayer::new(1_000, vec![]), ScriptedPlayer::new(1_000, vec![]), ScriptedPlayer::new(25, vec![])];
+        let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(),
+            posting: Posting { missed_blinds: vec![3], ..Posting::default() }, all_in: AllIn::RunOnce };
+        play_board_game(&mut Dealer::with_seed(8), &mut players, [10, 20], 0, &game, None);
+        assert_eq!(posts(&players[0]), vec![(0, 10, BlindType::Little), (1, 20, BlindType::Big), (3, 20, BlindType::Big), (3, 5, BlindType::Dead)]);
+        let all_in: Vec<bool> = players[0].notifications.iter().filter_map(|msg| match msg {
+            Message::ActionMessage { player: 3, all_in, .. } => Some(*all_in),
+            _ => None,
+        }).collect();
+        assert_eq!(all_in, vec![false, true]);
+        assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 3_025);
+
+        // the straddle stands in for the missed big blind
+        let mut players: Vec<ScriptedPlayer> = (0..4).map(|_| ScriptedPlayer::new(1_000, vec![])).collect();
+        let game = BoardGame { posting: Posting { straddle: Straddle::Utg, missed_blinds: vec![2], ..Posting::default() }, ..game };
+        play_board_game(&mut Dealer::with_seed(8), &mut players, [10, 20], 0, &game, None);
+        assert_eq!(posts(&players[0]), vec![(0, 10, BlindType::Little), (1, 20, BlindType::Big), (2, 40, BlindType::Straddle), (2, 10, BlindType::Dead)]);
+        assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 4_000);
+    }
+
+    #[test]
+    pub fn test_short_forced_bets() {
+        // the big blind covers the ante but not the blind, it posts what's left all in and doesn't act
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(1_000, vec![]), ScriptedPlayer::new(7, vec![]), ScriptedPlayer::new(1_000, vec![])];
+        holdem_nl(&mut Dealer::with_seed(3), &mut players, [5, 10], 2, None);
+        assert_eq!(posts(&players[0]), vec![(0, 2, BlindType::Ante), (1, 2, BlindType::Ante), (2, 2, BlindType::Ante), (0, 5, BlindType::Little), (1, 5, BlindType::Big)]);
+        assert_eq!(players[1].n_turns, 0);
+        assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 2_007);
+
+        // a stud player who can't cover the ante is all in for what they have
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(1, vec![]), ScriptedPlayer::new(100, vec![]), ScriptedPlayer::new(100, vec![])];
+        seven_card_stud(&mut Dealer::with_seed(3), &mut players, 2, 3, &Betting::FixedLimit { bets: [10, 20], raise_cap: Some(4), heads_up_uncapped: false }, None);
+        assert_eq!(posts(&players[1])[..3], [(0, 1, BlindType::Ante), (1, 2, BlindType::Ante), (2, 2, BlindType::Ante)]);
+        assert_eq!(players[0].n_turns, 0);
+        assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 201);
+    }
+
+    #[test]
+    pub fn test_legal_bets() {
+        let limit = Betting::FixedLimit { bets: [10, 20], raise_cap: Some(4), heads_up_uncapped: false };
+        let mut round = Round::new(3, Some(vec![5, 10, 0]));
+        round.set_big_blind(10);
+        round.n_raises = 1;
+        round.current_player_idx = 2;
+        assert_eq!(round.legal_bets(1000, &limit, &Turn::HOLE), LegalBets { call: 10, raise: Some([20, 20]) });
+        assert_eq!(round.legal_bets(15, &limit, &Turn::HOLE), LegalBets { call: 10, raise: Some([15, 15]) });
+        assert_eq!(round.legal_bets(8, &limit, &Turn::HOLE), LegalBets { call: 8, raise: None });
+        assert_eq!(round.legal_bets(1000, &limit, &Turn::TURN), LegalBets { call: 10, raise: Some([30, 30]) });
+        assert_eq!(round.legal_bets(1000, &Betting::NoLimit, &Turn::HOLE), LegalBets { call: 10, raise: Some([20, 1000]) });
+
+        round.n_raises = 4;
+        assert_eq!(round.legal_bets(1000, &limit, &Turn::HOLE), LegalBets { call: 10, raise: None });
+
+        let legal = LegalBets { call: 10, raise: Some([20, 20]) };
+        assert_eq!(legal.bet_size(5, 1000), None);
+        assert_eq!(legal.bet_size(5, 5), Some(5));
+        assert_eq!(legal.bet_size(10, 1000), Some(10));
+        assert_eq!(legal.bet_size(12, 1000), Some(20));
+        assert_eq!(legal.bet_size(500, 1000), Some(20));
+        assert_eq!(LegalBets { call: 10, raise: None }.bet_size(500, 1000), Some(10));
+    }
+
+    #[test]
+    pub fn test_min_raise() {
+        // after a 3x open the next raise has to be at least another 20
+        let mut round = Round::new(3, Some(vec![5, 10, 0]));
+        round.set_big_blind(10);
+        round.current_player_idx = 2;
+        round.next_play(Play::Bet(30));
+        assert_eq!(round.legal_bets(1000, &Betting::NoLimit, &Turn::HOLE), LegalBets { call: 25, raise: Some([45, 1000]) });
+        assert_eq!(round.legal_bets(1000, &Betting::NoLimit, &Turn::HOLE).bet_size(40, 1000), Some(45));
+        // a short stack can still go all in for less
+        assert_eq!(round.legal_bets(40, &Betting::NoLimit, &Turn::HOLE), LegalBets { call: 25, raise: Some([40, 40]) });
+
+        // in a hand the under raise is made a full raise
+        let mut players: Vec<ScriptedPlayer> = vec![
+            ScriptedPlayer::new(1_000, vec![Play::Bet(25), Play::Fold]),
+            ScriptedPlayer::new(1_000, vec![Play::Bet(30)]),
+            ScriptedPlayer::new(1_000, vec![Play::Fold]),
+        ];
+        holdem_nl(&mut Dealer::with_seed(3), &mut players, [5, 10], 0, None);
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![(0, Action::RAISE, 25), (1, Action::RAISE, 40), (2, Action::FOLD, 0), (0, Action::FOLD, 0)]);
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), vec![970, 1_030, 1_000]);
+
+        // the raise size starts over at the big blind on the flop
+        round.reset_round();
+        assert_eq!(round.min_raise, 10);
+    }
+
+    #[test]
+    pub fn test_pot_limit_bets() {
+        // the small blind calls 5 into a pot of 15 and can raise the 20 in the pot after calling
+        let mut round = Round::new(3, Some(vec![5, 10, 0]));
+        round.set_big_blind(10);
+        assert_eq!(round.legal_bets(1000, &Betting::PotLimit, &Turn::HOLE), LegalBets { call: 5, raise: Some([15, 25]) });
+        assert_eq!(round.legal_bets(20, &Betting::PotLimit, &Turn::HOLE), LegalBets { call: 5, raise: Some([15, 20]) });
+        assert_eq!(round.legal_bets(12, &Betting::PotLimit, &Turn::HOLE), LegalBets { call: 5, raise: Some([12, 12]) });
+
+        // antes are dead money in the pot
+        let mut round = Round::new(3, Some(vec![15, 20, 5]));
+        round.set_big_blind(10);
+        round.current_player_idx = 2;
+        assert_eq!(round.legal_bets(1000, &Betting::PotLimit, &Turn::HOLE), LegalBets { call: 15, raise: Some([25, 70]) });
+    }
+
+    #[test]
+    pub fn test_pot_limit_min_raise() {
+        // after a pot sized open of 35 the next raise has to be at least another 25
+        let mut round = Round::new(3, Some(vec![5, 10, 0]));
+        round.set_big_blind(10);
+        round.current_player_idx = 2;
+        round.next_play(Play::Bet(35));
+        assert_eq!(round.legal_bets(1000, &Betting::PotLimit, &Turn::HOLE), LegalBets { call: 30, raise: Some([55, 110]) });
+        assert_eq!(round.legal_bets(1000, &Betting::PotLimit, &Turn::HOLE).bet_size(40, 1000), Some(55));
+    }
+
+    #[test]
+    pub fn test_holdem_pl() {
+        let mut dealer = Dealer::new();
+        let mut players: Vec<ScriptedPlayer> = (0..3).map(|_| ScriptedPlayer::new(1_000, vec![Play::Bet(1_000)])).collect();
+        holdem_pl(&mut dealer, &mut players, [5, 10], 0, None);
+
+        // each raise is capped at the pot after calling
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![
+            (0, Action::RAISE, 25), (1, Action::RAISE, 80), (2, Action::RAISE, 300), (0, Action::CALL, 270), (1, Action::CALL, 210),
+        ]);
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { pots, .. } => assert_eq!(pots.iter().map(|pot| pot.amount).sum::<u32>(), 900),
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_holdem_limit() {
+        // everyone tries to bet 1000, bets and raises are fixed and capped at 4 a round
+        let mut dealer = Dealer::new();
+        let mut players: Vec<ScriptedPlayer> = (0..3).map(|_| ScriptedPlayer::new(1_000, (0..8).map(|_| Play::Bet(1_000)).collect())).collect();
+        let limit = Betting::FixedLimit { bets: [10, 20], raise_cap: Some(4), heads_up_uncapped: false };
+        holdem_limit(&mut dealer, &mut players, [5, 10], 0, &limit, None);
+
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![
+            (0, Action::RAISE, 15), (1, Action::RAISE, 20), (2, Action::RAISE, 40), (0, Action::CALL, 20), (1, Action::CALL, 10),
+        ]);
+        assert_eq!(actions(&players[0], Turn::FLOP), vec![
+            (0, Action::BET, 10), (1, Action::RAISE, 20), (2, Action::RAISE, 30), (0, Action::RAISE, 30), (1, Action::CALL, 20), (2, Action::CALL, 10),
+        ]);
+        assert_eq!(actions(&players[0], Turn::RIVER)[..2], [(0, Action::BET, 20), (1, Action::RAISE, 40)]);
+        assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 3_000);
+    }
+
+    #[test]
+    pub fn test_holdem_limit_heads_up_uncapped() {
+        // heads up the players keep raising until one of them is all in
+        let mut dealer = Dealer::new();
+        let mut players: Vec<ScriptedPlayer> = (0..2).map(|_| ScriptedPlayer::new(100, (0..20).map(|_| Play::Bet(1_000)).collect())).collect();
+        let limit = Betting::FixedLimit { bets: [10, 20], raise_cap: Some(4), heads_up_uncapped: true };
+        holdem_limit(&mut dealer, &mut players, [5, 10], 0, &limit, None);
+
+        let preflop: Vec<(usize, Action, u32)> = actions(&players[0], Turn::HOLE);
+        assert_eq!(preflop.iter().filter(|(_, action, _)| *action == Action::RAISE).count(), 9);
+        assert_eq!(preflop.last().unwrap(), &(1, Action::CALL, 10));
+        assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 200);
+    }
 
     #[test]
-    #[serial]
     pub fn test_holdem_nl() {
-        let zmq_conn = zmq_init();
+        let zmq_conn = coms_tests::test_server("test_holdem_nl");
+        let names: Vec<String> = vec!["player1".to_string(), "player2".to_string()];
+        let table = coms_tests::spawn_table(&zmq_conn, names.clone(), vec![100_000, 100_000]);
+        let bots: Vec<_> = names.iter().map(|name| coms_tests::spawn_calling_bot(&zmq_conn, name)).collect();
+        let mut clients = names.clone();
+        clients.push(TABLE_IDENTITY.to_string());
+        zmq_conn.wait_for_clients(&clients).unwrap();
+
         let mut dealer = Dealer::new();
         let player1 = Player::new("player1".to_string(), 100_000, Some(&zmq_conn));
         let player2 = Player::new("player2".to_string(), 100_000, Some(&zmq_conn));
         let mut players: Vec<Player> = vec![player1, player2];
         let blinds: [u32; 2] = [100, 200];
         holdem_nl(&mut dealer, &mut players, blinds, 0, Some(&zmq_conn));
+
+        for name in names.iter() {
+            zmq_conn.send_message(Message::EndOfGameMessage, name);
+        }
+        zmq_conn.send_recv_message(Message::EndOfGameMessage, "");
+        for bot in bots {
+            bot.join().unwrap();
+        }
+        table.join().unwrap();
+    }
+
+    #[test]
+    pub fn test_omaha_hilo_quartering() {
+        // player 0 wins the high and ties player 1 for the low, player 1 gets a quarter of the pot
+        let board: Vec<u8> = vec![
+            card("spade", "two"), card("heart", "seven"), card("club", "eight"), card("spade", "king"), card("heart", "king"),
+        ];
+        let mut players: Vec<ScriptedPlayer> = (0..3).map(|_| ScriptedPlayer::new(900, vec![])).collect();
+        players[0].recieve_cards(vec![card("spade", "ace"), card("diamond", "three"), card("diamond", "king"), card("club", "nine")]);
+        players[1].recieve_cards(vec![card("diamond", "ace"), card("spade", "three"), card("club", "queen"), card("diamond", "queen")]);
+        players[2].recieve_cards(vec![card("club", "jack"), card("diamond", "jack"), card("club", "ten"), card("heart", "ten")]);
+
+        let round = Round::new(3, Some(vec![100, 100, 100]));
+        end_hand(&round, &mut players, &board, &HandEvaluation::OmahaHiLo, &Rake::default(), None, None);
+
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), vec![1125, 975, 900]);
+        match &players[0].notifications[0] {
+            Message::EndOfRoundMessage { pots, showdown, .. } => {
+                assert_eq!(pots, &vec![PotResult { amount: 300, winners: vec![0, 1], winnings: vec![225, 75], rake: 0 }]);
+                assert_eq!(showdown[0].hand_category, HandCategory::TRIPS);
+            },
+            msg => panic!("Expected an EndOfRoundMessage, recieved {:?}", msg),
+        }
+
+        // without an 8 or better low the high hand takes the whole pot
+        let board: Vec<u8> = vec![
+            card("spade", "two"), card("heart", "nine"), card("club", "eight"), card("spade", "king"), card("heart", "king"),
+        ];
+        players[0].recieve_cards(vec![card("spade", "ace"), card("diamond", "three"), card("diamond", "king"), card("club", "nine")]);
+        players[1].recieve_cards(vec![card("diamond", "ace"), card("spade", "three"), card("club", "queen"), card("diamond", "queen")]);
+        players[2].recieve_cards(vec![card("club", "jack"), card("diamond", "jack"), card("club", "ten"), card("heart", "ten")]);
+        end_hand(&round, &mut players, &board, &HandEvaluation::OmahaHiLo, &Rake::default(), None, None);
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), vec![1425, 975, 900]);
+    }
+
+    #[test]
+    pub fn test_plo5() {
+        let mut dealer = Dealer::with_seed(3);
+        let mut players: Vec<ScriptedPlayer> = (0..3).map(|_| ScriptedPlayer::new(1_000, vec![])).collect();
+        plo5(&mut dealer, &mut players, [5, 10], 0, None);
+
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { board, showdown, .. } => {
+                let board: [u8; 5] = board.as_slice().try_into().unwrap();
+                assert!(!showdown.is_empty());
+                for hand in showdown.iter() {
+                    assert_eq!(hand.cards.len(), 5);
+                    assert_eq!(hand.hand_score, score_omaha(&hand.cards, &board));
+                }
+            },
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_short_deck() {
+        let mut dealer = Dealer::with_seed(8);
+        let mut players: Vec<ScriptedPlayer> = (0..3).map(|_| ScriptedPlayer::new(1_000, vec![])).collect();
+        short_deck(&mut dealer, &mut players, 1, 2, None);
+
+        // everyone antes, the button posts the only blind and closes the action before the flop
+        let posts: Vec<(usize, u32)> = players[0].notifications.iter().filter_map(|msg| match msg {
+            Message::ActionMessage { player, action: Action::POST, amount, .. } => Some((*player, *amount)),
+            _ => None,
+        }).collect();
+        assert_eq!(posts, vec![(0, 1), (1, 1), (2, 1), (2, 2)]);
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![(0, Action::CALL, 2), (1, Action::CALL, 2), (2, Action::CHECK, 0)]);
+
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { board, showdown, .. } => {
+                let cards: Vec<u8> = showdown.iter().flat_map(|hand| hand.cards.clone()).chain(board.iter().copied()).collect();
+                assert!(cards.iter().all(|card| (card - 1) % 13 >= 4));
+            },
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+        assert_eq!(dealer.deck.len(), 36);
+
+        // the next game on the same dealer deals from its own deck again
+        for game in 0..3 {
+            short_deck(&mut dealer, &mut players, 1, 2, None);
+            let mut players: Vec<ScriptedPlayer> = (0..3).map(|_| ScriptedPlayer::new(1_000, vec![])).collect();
+            match game {
+                0 => {holdem_nl(&mut dealer, &mut players, [5, 10], 0, None);},
+                1 => {five_card_draw(&mut dealer, &mut players, [5, 10], 0, &Betting::NoLimit, None);},
+                _ => {seven_card_stud(&mut dealer, &mut players, 1, 2, &Betting::FixedLimit { bets: [10, 20], raise_cap: Some(4), heads_up_uncapped: false }, None);},
+            }
+            assert_eq!(dealer.deck.len(), 52);
+        }
+    }
+
+    fn stud_deck(dealt: &[(&str, &str)]) -> Vec<u8> {
+        // deals the given cards in order, followed by the rest of the deck
+        let mut deck: Vec<u8> = dealt.iter().map(|(suit, value)| card(suit, value)).collect();
+        let rest: Vec<u8> = (1..=52).filter(|card| !deck.contains(card)).collect();
+        deck.extend(rest);
+        return deck;
+    }
+
+    fn stud_hand(razz_rules: bool, plays: Vec<Vec<Play>>) -> Vec<ScriptedPlayer> {
+        let deck: Vec<u8> = stud_deck(&[
+            // third street, two down cards and one up card each
+            ("heart", "ace"), ("diamond", "ace"), ("club", "king"),
+            ("club", "two"), ("club", "three"), ("spade", "four"),
+            ("heart", "seven"), ("heart", "eight"), ("diamond", "two"),
+            // fourth street
+            ("heart", "king"), ("spade", "five"), ("diamond", "nine"),
+        ]);
+        let mut dealer = Dealer::new();
+        dealer.stack_deck(0, deck);
+        let mut players: Vec<ScriptedPlayer> = plays.into_iter().map(|plays| ScriptedPlayer::new(1_000, plays)).collect();
+        let betting = Betting::FixedLimit { bets: [10, 20], raise_cap: Some(4), heads_up_uncapped: false };
+        match razz_rules {
+            true => razz(&mut dealer, &mut players, 1, 3, &betting, None),
+            false => seven_card_stud(&mut dealer, &mut players, 1, 3, &betting, None),
+        };
+        return players;
     }
 
+    fn bring_ins(player: &ScriptedPlayer) -> Vec<(usize, u32)> {
+        return player.notifications.iter().filter_map(|msg| match msg {
+            Message::ActionMessage { player, action: Action::POST, amount, .. } if *amount > 1 => Some((*player, *amount)),
+            _ => None,
+        }).collect();
+    }
+
+    #[test]
+    pub fn test_seven_card_stud() {
+        // the two of diamonds brings in, player 0 completes to the small bet
+        let players: Vec<ScriptedPlayer> = stud_hand(false, vec![vec![Play::Bet(100)], vec![], vec![]]);
+        assert_eq!(bring_ins(&players[0]), vec![(2, 3)]);
+        assert_eq!(actions(&players[0], Turn::THIRD), vec![(0, Action::RAISE, 10), (1, Action::CALL, 10), (2, Action::CALL, 7)]);
+        // the pair of kings showing acts first on fourth street
+        assert_eq!(actions(&players[0], Turn::FOURTH)[0], (0, Action::CHECK, 0));
+
+        let up_cards: Vec<Vec<Vec<u8>>> = players[1].notifications.iter().filter_map(|msg| match msg {
+            Message::UpCardsMessage { up_cards, .. } => Some(up_cards.clone()),
+            _ => None,
+        }).collect();
+        assert_eq!(up_cards.len(), 5);
+        assert_eq!(up_cards[0], vec![vec![card("club", "king")], vec![card("spade", "four")], vec![card("diamond", "two")]]);
+        assert_eq!(up_cards[1][0], vec![card("club", "king"), card("heart", "king")]);
+        assert!(up_cards[4].iter().all(|cards| cards.len() == 4));
+
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { board, showdown, pots, .. } => {
+                assert!(board.is_empty());
+                assert_eq!(showdown.iter().map(|hand| hand.cards.len()).collect::<Vec<usize>>(), vec![7; showdown.len()]);
+                assert_eq!(pots.iter().map(|pot| pot.amount).sum::<u32>(), 33);
+            },
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_razz() {
+        // the king brings in, everyone calls and the bring in doesn't act again
+        let players: Vec<ScriptedPlayer> = stud_hand(true, vec![vec![], vec![], vec![]]);
+        assert_eq!(bring_ins(&players[0]), vec![(0, 3)]);
+        assert_eq!(actions(&players[0], Turn::THIRD), vec![(1, Action::CALL, 3), (2, Action::CALL, 3)]);
+        // 5-4 is the lowest hand showing on fourth street
+        assert_eq!(actions(&players[0], Turn::FOURTH)[0], (1, Action::CHECK, 0));
+    }
+
+    // every event a table publishes
+    #[derive(Default)]
+    struct Published(RefCell<Vec<Message>>);
+
+    impl TableEvents for Published {
+        fn publish(&self, msg: &Message) {
+            self.0.borrow_mut().push(msg.clone());
+        }
+        fn log_deck(&self, _seed: u64, _deck: &[u8]) {}
+        fn log_rake(&self, _rake: &Rake) {}
+        fn log_all_in(&self, _all_in: AllIn) {}
+    }
+
+    fn drawing_whole_hands(dealer: &mut Dealer, n_players: usize) -> Vec<ScriptedPlayer> {
+        // players who throw away the five cards a deck in order deals them
+        dealer.stack_deck(0, (1..=52).collect());
+        return (0..n_players).map(|idx| {
+            let mut player = ScriptedPlayer::new(1_000, vec![]);
+            let first: u8 = 5 * idx as u8 + 1;
+            player.discards = vec![(first..first + 5).collect()].into();
+            player
+        }).collect();
+    }
+
+    #[test]
+    pub fn test_full_table_draw() {
+        // ten players can all throw away their hands, the stub is shuffled with the discards and the folded hand
+        let mut dealer = Dealer::new();
+        let mut players: Vec<ScriptedPlayer> = drawing_whole_hands(&mut dealer, 10);
+        players[9].plays = vec![Play::Fold].into();
+        let published = Published::default();
+        five_card_draw(&mut dealer, &mut players, [5, 10], 0, &Betting::NoLimit, Some(&published));
+
+        let mut hands: Vec<Vec<u8>> = vec![vec![]; 10];
+        for msg in published.0.borrow().iter() {
+            if let Message::DealMessage { player, cards: Some(cards) } = msg {
+                hands[*player] = cards.clone();
+            }
+        }
+        let mut held: Vec<u8> = hands[..9].concat();
+        held.sort();
+        held.dedup();
+        assert_eq!(held.len(), 45);
+        assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 10_000);
+
+        // a full table of five card draw can always draw
+        let rules = GameRules::new(FIVE_CARD_DRAW).unwrap();
+        let mut players: Vec<ScriptedPlayer> = drawing_whole_hands(&mut dealer, rules.max_seats);
+        play_hand(&mut dealer, &mut players, &rules, None);
+        let draws: usize = players[0].notifications.iter().filter(|msg| matches!(msg, Message::DrawResultMessage { n_cards: 5, .. })).count();
+        assert_eq!(draws, rules.max_seats);
+    }
+
+    #[test]
+    pub fn test_draw_stub_runs_out() {
+        // ten players throw away their whole hands, the two card stub runs out on the first draw and nobody
+        // is dealt back the cards they threw away
+        let mut dealer = Dealer::new();
+        let mut players: Vec<ScriptedPlayer> = drawing_whole_hands(&mut dealer, 10);
+        players[9].plays = vec![Play::Fold].into();
+        let published = Published::default();
+        five_card_draw(&mut dealer, &mut players, [5, 10], 0, &Betting::NoLimit, Some(&published));
+
+        let mut hands: Vec<Vec<u8>> = vec![vec![]; 10];
+        for msg in published.0.borrow().iter() {
+            if let Message::DealMessage { player, cards: Some(cards) } = msg {
+                hands[*player] = cards.clone();
+            }
+        }
+        for (idx, hand) in hands[..9].iter().enumerate() {
+            let first: u8 = 5 * idx as u8 + 1;
+            assert_eq!(hand.len(), 5);
+            assert!(hand.iter().all(|card| !(first..first + 5).contains(card)));
+        }
+
+        // a stub and muck too small for the draw shuffle in the player's own cards instead of running out
+        let mut dealer = Dealer::new();
+        dealer.stack_deck(0, (1..=52).collect());
+        dealer.shuffle();
+        let held: Vec<u8> = (0..50).map(|_| dealer.next_card()).collect();
+        let cards: Vec<u8> = dealer.draw(&held[..5]);
+        assert_eq!(cards.len(), 5);
+        assert_eq!(dealer.cards_left(), 2);
+    }
+
+    #[test]
+    pub fn test_draw_limit_bets() {
+        // five card draw bets the big bet after the draw
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(1_000, vec![Play::Bet(5), Play::Bet(100)]), ScriptedPlayer::new(1_000, vec![])];
+        let betting = Betting::FixedLimit { bets: [10, 20], raise_cap: Some(4), heads_up_uncapped: false };
+        five_card_draw(&mut Dealer::with_seed(4), &mut players, [5, 10], 0, &betting, None);
+        assert_eq!(actions(&players[0], Turn::DRAW1), vec![(0, Action::BET, 20), (1, Action::CALL, 20)]);
+    }
+
+    #[test]
+    pub fn test_triple_draw() {
+        let deck: Vec<u8> = stud_deck(&[
+            ("spade", "seven"), ("club", "five"), ("diamond", "four"), ("heart", "three"), ("club", "king"),
+            ("spade", "eight"), ("club", "six"), ("spade", "four"), ("spade", "three"), ("diamond", "two"),
+            ("club", "two"),
+        ]);
+        let mut dealer = Dealer::new();
+        dealer.stack_deck(0, deck);
+        let mut players: Vec<ScriptedPlayer> = (0..2).map(|_| ScriptedPlayer::new(1_000, vec![])).collect();
+        players[0].discards = vec![vec![card("club", "king")]].into();
+        // discarding a card the player doesn't hold stands pat
+        players[1].discards = vec![vec![card("heart", "ace")]].into();
+        let betting = Betting::FixedLimit { bets: [10, 20], raise_cap: Some(4), heads_up_uncapped: false };
+        triple_draw(&mut dealer, &mut players, [5, 10], 0, &betting, None);
+
+        let draws: Vec<(usize, usize, Turn)> = players[1].notifications.iter().filter_map(|msg| match msg {
+            Message::DrawResultMessage { player, n_cards, turn } => Some((*player, *n_cards, turn.clone())),
+            _ => None,
+        }).collect();
+        assert_eq!(draws, vec![
+            (0, 1, Turn::DRAW1), (1, 0, Turn::DRAW1), (0, 0, Turn::DRAW2), (1, 0, Turn::DRAW2), (0, 0, Turn::DRAW3), (1, 0, Turn::DRAW3),
+        ]);
+        assert_eq!(actions(&players[0], Turn::DRAW3), vec![(0, Action::CHECK, 0), (1, Action::CHECK, 0)]);
+
+        // 7-5-4-3-2 beats 8-6-4-3-2
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { showdown, pots, .. } => {
+                let winner: &ShowdownHand = showdown.iter().find(|hand| hand.hand_rank == 1).unwrap();
+                assert_eq!(winner.player, 0);
+                assert!(winner.cards.contains(&card("club", "two")));
+                assert_eq!(pots, &vec![PotResult { amount: 20, winners: vec![0], winnings: vec![20], rake: 0 }]);
+            },
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+    }
+
+    #[test]
+    pub fn test_kuhn() {
+        let mut dealer = Dealer::new();
+        dealer.use_cards(kuhn_deck());
+        dealer.stack_deck(0, vec![card("spade", "king"), card("spade", "jack"), card("spade", "queen")]);
+        // there is only one bet, a raise calls
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(10, vec![Play::Bet(5)]), ScriptedPlayer::new(10, vec![Play::Bet(5)])];
+        kuhn(&mut dealer, &mut players, None);
+
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![(0, Action::BET, 1), (1, Action::CALL, 1)]);
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), vec![12, 8]);
+    }
+
+    #[test]
+    pub fn test_leduc() {
+        let mut dealer = Dealer::new();
+        dealer.use_cards(leduc_deck());
+        dealer.stack_deck(0, vec![
+            card("heart", "jack"), card("spade", "queen"), card("spade", "jack"), card("heart", "queen"), card("spade", "king"), card("heart", "king"),
+        ]);
+        let mut players: Vec<ScriptedPlayer> = (0..2).map(|_| ScriptedPlayer::new(100, vec![Play::Bet(100), Play::Bet(100), Play::Bet(100)])).collect();
+        leduc(&mut dealer, &mut players, None);
+
+        // bets are 2 before the board card and 4 after, capped at a bet and a raise
+        assert_eq!(actions(&players[0], Turn::HOLE), vec![(0, Action::BET, 2), (1, Action::RAISE, 4), (0, Action::CALL, 2)]);
+        assert_eq!(actions(&players[0], Turn::FLOP), vec![(0, Action::BET, 4), (1, Action::RAISE, 8), (0, Action::CALL, 4)]);
+        // the jack pairs the board and beats the queen
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), vec![113, 87]);
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { board, showdown, mucked, .. } => {
+                assert_eq!(board, &vec![card("spade", "jack")]);
+                assert_eq!(showdown.iter().map(|hand| (hand.player, hand.hand_category)).collect::<Vec<(usize, HandCategory)>>(), vec![(0, HandCategory::PAIR)]);
+                assert_eq!(mucked, &vec![1]);
+            },
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+    }
+
+    fn all_in_on_flop(all_in: AllIn) -> (Vec<ScriptedPlayer>, HandResult) {
+        // aces and kings are all in on the flop, the kings hit a set on the first river and miss the second run
+        let top: Vec<u8> = vec![13, 26, 12, 25, 27, 45, 34, 42, 38, 41, 30];
+        let deck: Vec<u8> = top.iter().copied().chain((1..=52).filter(|card| !top.contains(card))).collect();
+        let mut dealer = Dealer::new();
+        dealer.stack_deck(0, deck);
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(100, vec![Play::Bet(5), Play::Bet(90)]), ScriptedPlayer::new(100, vec![Play::Bet(0), Play::Bet(90)])];
+        let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(), posting: Posting::default(), all_in };
+        let result: HandResult = play_board_game(&mut dealer, &mut players, [5, 10], 0, &game, None);
+        return (players, result);
+    }
+
+    #[test]
+    pub fn test_all_in_runs() {
+        let unseen: Vec<u8> = unseen_cards(&standard_deck(), &[13, 26, 12, 25, 27, 45, 34]);
+        let shares: Vec<f64> = equity(&[vec![13, 26], vec![12, 25]], &[27, 45, 34], &unseen, &HandEvaluation::Holdem);
+        let expected_ev: Vec<f64> = shares.iter().map(|share| 200.0 * share - 100.0).collect();
+
+        // the board is run once and nothing else is reported
+        let (players, result) = all_in_on_flop(AllIn::RunOnce);
+        assert_eq!(result, HandResult { winnings: vec![-100, 100], all_in_ev: None });
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { runs, all_in_ev, .. } => assert!(runs.is_empty() && all_in_ev.is_empty()),
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+
+        // the kings win the actual hand, the aces were ahead when the chips went in
+        let (players, result) = all_in_on_flop(AllIn::ReportEquity);
+        assert_eq!(result.winnings, vec![-100, 100]);
+        let all_in_ev: Vec<f64> = result.all_in_ev.unwrap();
+        assert!(all_in_ev.iter().zip(expected_ev.iter()).all(|(ev, expected)| (ev - expected).abs() < 1e-9));
+        assert!(all_in_ev[0] > 50.0);
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { all_in_ev: reported, .. } => assert_eq!(*reported, all_in_ev),
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+
+        // run twice each run wins half of the pot, the equity isn't reported
+        let (players, result) = all_in_on_flop(AllIn::RunTimes(2));
+        assert_eq!(result, HandResult { winnings: vec![0, 0], all_in_ev: None });
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), vec![100, 100]);
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { board, runs, pots, all_in_ev, .. } => {
+                assert!(all_in_ev.is_empty());
+                assert_eq!(*runs, vec![vec![27, 45, 34, 42, 38], vec![27, 45, 34, 41, 30]]);
+                assert_eq!(*board, runs[0]);
+                assert_eq!(*pots, vec![PotResult { amount: 200, winners: vec![0, 1], winnings: vec![100, 100], rake: 0 }]);
+            },
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+
+        // the deck runs out of cards before a hundred runs
+        let (players, _) = all_in_on_flop(AllIn::RunTimes(100));
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { runs, pots, .. } => {
+                assert_eq!(runs.len(), 22);
+                assert_eq!(pots[0].winnings.iter().sum::<u32>(), 200);
+            },
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+
+        // paying by equity ignores the river
+        let (players, result) = all_in_on_flop(AllIn::PayEquity);
+        let paid: Vec<u32> = pay_by_equity(200, &[200.0 * shares[0], 200.0 * shares[1]]);
+        assert_eq!(result.winnings, vec![paid[0] as i32 - 100, paid[1] as i32 - 100]);
+        assert_eq!(players.iter().map(|player| player.chips).collect::<Vec<u32>>(), paid);
+        match players[0].notifications.last().unwrap() {
+            Message::EndOfRoundMessage { board, pots, .. } => {
+                assert_eq!(*board, vec![27, 45, 34, 42, 38]);
+                assert_eq!(*pots, vec![PotResult { amount: 200, winners: vec![0, 1], winnings: paid, rake: 0 }]);
+            },
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+    }
 }
@@ -1,4 +1,21 @@
 
+use serde::{Serialize, Deserialize};
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum HandCategory {
+    HIGHCARD,
+    PAIR,
+    TWOPAIR,
+    TRIPS,
+    STRAIGHT,
+    FLUSH,
+    FULLHOUSE,
+    QUADS,
+    STRAIGHTFLUSH,
+    ROYALFLUSH,
+}
+
 // hand ranking
 // royal flush - only 4 combos (4 masks): 80000
 // straight flush - HC tie break (36 masks): 70000
@@ -18,7 +35,11 @@ pub fn score_hand(player_cards: &[u8; 2], shared_cards: &[u8; 5]) -> f64 {
     let (one, two) = all_cards.split_at_mut(2);
     one.copy_from_slice(player_cards);
     two.copy_from_slice(shared_cards);
+    return score_cards(&all_cards);
+}
 
+pub fn score_cards(all_cards: &[u8]) -> f64 {
+    // scores the best five card hand within any number of cards
     let mut suit_count: [u32; 4] = [0; 4];
     let mut card_count: [u32; 13] = [0; 13];
     for card in all_cards.iter() {
@@ -53,7 +74,7 @@ pub fn score_hand(player_cards: &[u8; 2], shared_cards: &[u8; 5]) -> f64 {
         // mask checks for straight and royal flush
         // return if either mask check passes
         let mut one_hot_cards: [u32; 52] = [0; 52];
-        for card in all_cards {one_hot_cards[(card as usize) - 1] = 1;}
+        for &card in all_cards {one_hot_cards[(card as usize) - 1] = 1;}
         for card_num in (5..14).rev() {
             for suit in 0..4 {
                 let end_idx: usize = card_num + suit * 13;
@@ -76,7 +97,7 @@ pub fn score_hand(player_cards: &[u8; 2], shared_cards: &[u8; 5]) -> f64 {
     if is_flush {
         // find hc
         let mut one_hot_cards: [u32; 52] = [0; 52];
-        for card in all_cards {one_hot_cards[(card - 1) as usize] = 1;}
+        for &card in all_cards {one_hot_cards[(card - 1) as usize] = 1;}
         for suit in 0..4 {
             let start_idx: usize = (suit) * 13;
             let masked_cards: [u32; 13] = one_hot_cards[start_idx..start_idx+13].try_into().unwrap();
@@ -170,6 +191,208 @@ pub fn score_hand(player_cards: &[u8; 2], shared_cards: &[u8; 5]) -> f64 {
     
 }
 
+pub fn hand_category(player_cards: &[u8; 2], shared_cards: &[u8; 5]) -> HandCategory {
+    let all_cards: Vec<u8> = player_cards.iter().chain(shared_cards.iter()).copied().collect();
+    return cards_category(&all_cards);
+}
+
+pub fn cards_category(all_cards: &[u8]) -> HandCategory {
+    return category_from_score(score_cards(all_cards), all_cards);
+}
+
+fn category_from_score(score: f64, all_cards: &[u8]) -> HandCategory {
+    // category of a score_cards score, pairs and two pairs share a score range
+    match score {
+        s if s >= 80000. => return HandCategory::ROYALFLUSH,
+        s if s >= 70000. => return HandCategory::STRAIGHTFLUSH,
+        s if s >= 60000. => return HandCategory::QUADS,
+        s if s >= 50000. => return HandCategory::FULLHOUSE,
+        s if s >= 40000. => return HandCategory::FLUSH,
+        s if s >= 30000. => return HandCategory::STRAIGHT,
+        s if s >= 20000. => return HandCategory::TRIPS,
+        s if s >= 10000. => (),
+        _ => return HandCategory::HIGHCARD,
+    }
+
+    let mut card_count: [u32; 13] = [0; 13];
+    for card in all_cards {
+        card_count[((card - 1) % 13) as usize] += 1;
+    }
+    match card_count.iter().filter(|&&count| count == 2).count() {
+        1 => return HandCategory::PAIR,
+        _ => return HandCategory::TWOPAIR,
+    }
+}
+
+// short deck hands are scored like score_cards with two changes.
+// A flush beats a full house, its scores move to 55000 between full houses and quads,
+// and A-6-7-8-9 is the lowest straight, scored like a nine high straight
+
+fn has_short_deck_wheel(all_cards: &[u8], suit: Option<u8>) -> bool {
+    // ace, six, seven, eight and nine, all of one suit when a suit is given
+    return [12, 4, 5, 6, 7].iter().all(|val| all_cards.iter().any(|card| {
+        ((card - 1) % 13 == *val) && suit.is_none_or(|suit| (card - 1) / 13 == suit)
+    }));
+}
+
+pub fn score_short_deck(all_cards: &[u8]) -> f64 {
+    let score: f64 = score_cards(all_cards);
+    if (score < 70000.) && (0..4).any(|suit| has_short_deck_wheel(all_cards, Some(suit))) {
+        return 70800.;
+    }
+    if (40000. ..50000.).contains(&score) {
+        return score + 15000.;
+    }
+    if (score < 30000.) && has_short_deck_wheel(all_cards, None) {
+        return 30800.;
+    }
+    return score;
+}
+
+pub fn short_deck_category(all_cards: &[u8]) -> HandCategory {
+    let score: f64 = score_short_deck(all_cards);
+    match score {
+        s if (55000. ..60000.).contains(&s) => return HandCategory::FLUSH,
+        s if (50000. ..55000.).contains(&s) => return HandCategory::FULLHOUSE,
+        s => return category_from_score(s, all_cards),
+    }
+}
+
+fn omaha_hands(player_cards: &[u8], shared_cards: &[u8; 5]) -> Vec<[u8; 5]> {
+    // every hand made of exactly two hole cards and three board cards
+    let mut hands: Vec<[u8; 5]> = vec![];
+    for i in 0..player_cards.len() {
+        for j in i+1..player_cards.len() {
+            for a in 0..5 {
+                for b in a+1..5 {
+                    for c in b+1..5 {
+                        hands.push([player_cards[i], player_cards[j], shared_cards[a], shared_cards[b], shared_cards[c]]);
+                    }
+                }
+            }
+        }
+    }
+    return hands;
+}
+
+pub fn best_omaha_hand(player_cards: &[u8], shared_cards: &[u8; 5]) -> [u8; 5] {
+    return omaha_hands(player_cards, shared_cards).into_iter()
+        .max_by(|a, b| score_cards(a).partial_cmp(&score_cards(b)).unwrap())
+        .unwrap();
+}
+
+pub fn score_omaha(player_cards: &[u8], shared_cards: &[u8; 5]) -> f64 {
+    return score_cards(&best_omaha_hand(player_cards, shared_cards));
+}
+
+pub fn omaha_category(player_cards: &[u8], shared_cards: &[u8; 5]) -> HandCategory {
+    return cards_category(&best_omaha_hand(player_cards, shared_cards));
+}
+
+// 8 or better lows count aces as one and ignore straights and flushes,
+// the ranks are compared from the highest down so the smaller array is the better low
+
+pub fn low_rank(card: u8) -> u8 {
+    // ace is 1, king is 13
+    match (card - 1) % 13 {
+        12 => return 1,
+        val => return val + 2,
+    }
+}
+
+pub fn low_hand(cards: &[u8; 5]) -> Option<[u8; 5]> {
+    // ranks of the five cards from high to low, None if they aren't five different ranks of eight or less
+    let mut ranks: Vec<u8> = cards.iter().map(|card| low_rank(*card)).collect();
+    ranks.sort_unstable_by(|a, b| b.cmp(a));
+    ranks.dedup();
+    if (ranks.len() != 5) || (ranks[0] > 8) {
+        return None;
+    }
+    return Some(ranks.try_into().unwrap());
+}
+
+pub fn omaha_low(player_cards: &[u8], shared_cards: &[u8; 5]) -> Option<[u8; 5]> {
+    return omaha_hands(player_cards, shared_cards).iter().filter_map(low_hand).min();
+}
+
+// A-5 lowball (razz) ignores straights and flushes, fewer matched cards always make the better low.
+// Keys hold the pattern of matched cards, e.g. [2, 1, 1, 1] for a pair, then the ranks
+// from the most matched and highest down, both padded to five hex digits. Smaller keys are better lows
+
+fn razz_key(cards: &[u8]) -> u64 {
+    let mut counts: [u8; 14] = [0; 14];
+    for card in cards {
+        counts[low_rank(*card) as usize] += 1;
+    }
+    let mut groups: Vec<(u8, u8)> = (1..14).filter(|rank| counts[*rank] > 0).map(|rank| (counts[rank], rank as u8)).collect();
+    groups.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut pattern: Vec<u8> = groups.iter().map(|(count, _)| *count).collect();
+    let mut ranks: Vec<u8> = groups.iter().flat_map(|(count, rank)| vec![*rank; *count as usize]).collect();
+    pattern.resize(5, 0);
+    ranks.resize(5, 0);
+    return pattern.iter().chain(ranks.iter()).fold(0, |key, digit| key * 16 + *digit as u64);
+}
+
+fn best_razz_hand(all_cards: &[u8]) -> Vec<u8> {
+    // the five cards making the best low, or all of them when there are fewer
+    if all_cards.len() <= 5 {return all_cards.to_vec()}
+    let n: usize = all_cards.len();
+    return (0..(1u32 << n))
+        .filter(|mask| mask.count_ones() == 5)
+        .map(|mask| (0..n).filter(|idx| mask & (1 << idx) != 0).map(|idx| all_cards[idx]).collect::<Vec<u8>>())
+        .min_by_key(|hand| razz_key(hand))
+        .unwrap();
+}
+
+pub fn score_razz(all_cards: &[u8]) -> f64 {
+    // negated so the best low has the highest score, like the high hand scores
+    return -(razz_key(&best_razz_hand(all_cards)) as f64);
+}
+
+pub fn razz_category(all_cards: &[u8]) -> HandCategory {
+    let hand: Vec<u8> = best_razz_hand(all_cards);
+    let mut counts: [u8; 14] = [0; 14];
+    for card in hand.iter() {
+        counts[low_rank(*card) as usize] += 1;
+    }
+    let n_pairs: usize = counts.iter().filter(|&&count| count == 2).count();
+    match (*counts.iter().max().unwrap(), n_pairs) {
+        (4, _) => return HandCategory::QUADS,
+        (3, 1) => return HandCategory::FULLHOUSE,
+        (3, _) => return HandCategory::TRIPS,
+        (2, 2) => return HandCategory::TWOPAIR,
+        (2, _) => return HandCategory::PAIR,
+        _ => return HandCategory::HIGHCARD,
+    }
+}
+
+// 2-7 lowball counts aces high, straights and flushes against the hand.
+// The worst high hand is the best low, hands compare by category, then by the ranks
+// from the most matched and highest down
+
+pub fn score_deuce_to_seven(cards: &[u8; 5]) -> f64 {
+    // negated so the best low has the highest score
+    let mut counts: [u8; 13] = [0; 13];
+    for card in cards {
+        counts[((card - 1) % 13) as usize] += 1;
+    }
+    let mut groups: Vec<(u8, u8)> = (0..13).filter(|val| counts[*val] > 0).map(|val| (counts[val], val as u8)).collect();
+    groups.sort_unstable_by(|a, b| b.cmp(a));
+    let ranks: Vec<u8> = groups.iter().flat_map(|(count, val)| vec![*val; *count as usize]).collect();
+    let key: u64 = ranks.iter().fold(cards_category(cards) as u64, |key, val| key * 16 + *val as u64);
+    return -(key as f64);
+}
+
+pub fn score_leduc(hole_card: u8, board: &[u8]) -> f64 {
+    // Kuhn and Leduc poker: pairing the board beats any unpaired card, otherwise the higher hole card wins
+    let val: u8 = (hole_card - 1) % 13;
+    if board.iter().any(|card| (card - 1) % 13 == val) {
+        return 100. + val as f64;
+    }
+    return val as f64;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -391,4 +614,157 @@ mod tests {
         test_cards(&shared_cards, &player_cards_3, &player_cards_4, Result::P2WINS);
         test_cards(&shared_cards, &player_cards_1, &player_cards_5, Result::P1WINS);
     }
+
+    fn category(shared_cards: &[Card; 5], player_cards: &[Card; 2]) -> HandCategory {
+        let shared_cards_int: [u8; 5] = shared_cards.iter().map(|x| x.to_int()).collect::<Vec<u8>>().try_into().unwrap();
+        let player_int: [u8; 2] = player_cards.iter().map(|x| x.to_int()).collect::<Vec<u8>>().try_into().unwrap();
+        return hand_category(&player_int, &shared_cards_int);
+    }
+
+    #[test]
+    pub fn test_hand_category() {
+        let shared_cards: [Card; 5] = [
+            Card::card_from_name("club", "queen"),
+            Card::card_from_name("club", "ten"),
+            Card::card_from_name("club", "jack"),
+            Card::card_from_name("heart", "king"),
+            Card::card_from_name("spade", "two"),
+        ];
+
+        let hands: [([Card; 2], HandCategory); 9] = [
+            ([Card::card_from_name("club", "ace"), Card::card_from_name("club", "king")], HandCategory::ROYALFLUSH),
+            ([Card::card_from_name("club", "nine"), Card::card_from_name("club", "eight")], HandCategory::STRAIGHTFLUSH),
+            ([Card::card_from_name("club", "four"), Card::card_from_name("club", "five")], HandCategory::FLUSH),
+            ([Card::card_from_name("heart", "ace"), Card::card_from_name("spade", "three")], HandCategory::STRAIGHT),
+            ([Card::card_from_name("heart", "queen"), Card::card_from_name("spade", "queen")], HandCategory::TRIPS),
+            ([Card::card_from_name("heart", "queen"), Card::card_from_name("spade", "two")], HandCategory::TWOPAIR),
+            ([Card::card_from_name("heart", "two"), Card::card_from_name("spade", "four")], HandCategory::PAIR),
+            ([Card::card_from_name("heart", "four"), Card::card_from_name("spade", "six")], HandCategory::HIGHCARD),
+            ([Card::card_from_name("diamond", "two"), Card::card_from_name("club", "two")], HandCategory::TRIPS),
+        ];
+
+        for (player_cards, expected) in hands.iter() {
+            assert_eq!(category(&shared_cards, player_cards), *expected);
+        }
+
+        let shared_cards: [Card; 5] = [
+            Card::card_from_name("club", "queen"),
+            Card::card_from_name("heart", "queen"),
+            Card::card_from_name("club", "jack"),
+            Card::card_from_name("heart", "jack"),
+            Card::card_from_name("spade", "two"),
+        ];
+        let full_house: [Card; 2] = [Card::card_from_name("spade", "queen"), Card::card_from_name("spade", "three")];
+        let quads: [Card; 2] = [Card::card_from_name("spade", "jack"), Card::card_from_name("diamond", "jack")];
+        assert_eq!(category(&shared_cards, &full_house), HandCategory::FULLHOUSE);
+        assert_eq!(category(&shared_cards, &quads), HandCategory::QUADS);
+    }
+
+    fn ints(cards: &[(&str, &str)]) -> Vec<u8> {
+        return cards.iter().map(|(suit, name)| Card::card_from_name(suit, name).to_int()).collect();
+    }
+
+    #[test]
+    pub fn test_omaha() {
+        let board: [u8; 5] = ints(&[("spade", "ace"), ("spade", "king"), ("spade", "seven"), ("spade", "two"), ("heart", "ace")]).try_into().unwrap();
+
+        // one spade in the hand can't make a flush with four on the board
+        let one_spade: Vec<u8> = ints(&[("spade", "queen"), ("heart", "nine"), ("club", "four"), ("diamond", "three")]);
+        assert_eq!(omaha_category(&one_spade, &board), HandCategory::PAIR);
+        assert!(score_hand(&one_spade[..2].try_into().unwrap(), &board) >= 40000.);
+        let two_spades: Vec<u8> = ints(&[("spade", "queen"), ("spade", "nine"), ("club", "four"), ("diamond", "three")]);
+        assert_eq!(omaha_category(&two_spades, &board), HandCategory::FLUSH);
+        assert!(score_omaha(&two_spades, &board) > score_omaha(&one_spade, &board));
+
+        // trips with the board aces use exactly two hole cards and three board cards
+        let trips: Vec<u8> = ints(&[("club", "ace"), ("heart", "queen"), ("club", "four"), ("diamond", "three"), ("heart", "jack")]);
+        assert_eq!(omaha_category(&trips, &board), HandCategory::TRIPS);
+        assert_eq!(best_omaha_hand(&trips, &board).iter().filter(|card| trips.contains(card)).count(), 2);
+    }
+
+    #[test]
+    pub fn test_omaha_low() {
+        let board: [u8; 5] = ints(&[("spade", "two"), ("heart", "three"), ("club", "four"), ("spade", "king"), ("heart", "queen")]).try_into().unwrap();
+
+        let wheel: Vec<u8> = ints(&[("club", "ace"), ("diamond", "five"), ("club", "king"), ("diamond", "king")]);
+        assert_eq!(omaha_low(&wheel, &board), Some([5, 4, 3, 2, 1]));
+        let eight_low: Vec<u8> = ints(&[("club", "ace"), ("diamond", "eight"), ("club", "queen"), ("diamond", "jack")]);
+        assert_eq!(omaha_low(&eight_low, &board), Some([8, 4, 3, 2, 1]));
+        assert!(omaha_low(&wheel, &board) < omaha_low(&eight_low, &board));
+
+        // a pair with the board counterfeits the low
+        let counterfeit: Vec<u8> = ints(&[("club", "ace"), ("diamond", "two"), ("club", "king"), ("diamond", "king")]);
+        assert_eq!(omaha_low(&counterfeit, &board), None);
+        let nine: Vec<u8> = ints(&[("club", "ace"), ("diamond", "nine"), ("club", "king"), ("diamond", "king")]);
+        assert_eq!(omaha_low(&nine, &board), None);
+    }
+
+    #[test]
+    pub fn test_short_deck() {
+        let flush: Vec<u8> = ints(&[("heart", "six"), ("heart", "nine"), ("heart", "jack"), ("heart", "king"), ("heart", "ace"), ("club", "king"), ("diamond", "six")]);
+        let full_house: Vec<u8> = ints(&[("spade", "king"), ("club", "king"), ("diamond", "king"), ("heart", "six"), ("club", "six"), ("spade", "ten"), ("club", "jack")]);
+        assert!(score_cards(&flush) < score_cards(&full_house));
+        assert!(score_short_deck(&flush) > score_short_deck(&full_house));
+        assert_eq!(short_deck_category(&flush), HandCategory::FLUSH);
+        assert_eq!(short_deck_category(&full_house), HandCategory::FULLHOUSE);
+        let quads: Vec<u8> = ints(&[("spade", "six"), ("club", "six"), ("diamond", "six"), ("heart", "six"), ("club", "seven"), ("spade", "ten"), ("club", "jack")]);
+        assert!(score_short_deck(&quads) > score_short_deck(&flush));
+
+        // ace, six, seven, eight, nine is the lowest straight
+        let wheel: Vec<u8> = ints(&[("spade", "ace"), ("club", "six"), ("diamond", "seven"), ("heart", "eight"), ("club", "nine"), ("spade", "king"), ("club", "king")]);
+        let ten_high: Vec<u8> = ints(&[("spade", "ten"), ("club", "six"), ("diamond", "seven"), ("heart", "eight"), ("club", "nine"), ("spade", "king"), ("club", "king")]);
+        let trips: Vec<u8> = ints(&[("spade", "ace"), ("club", "ace"), ("diamond", "ace"), ("heart", "eight"), ("club", "nine"), ("spade", "king"), ("club", "queen")]);
+        assert_eq!(short_deck_category(&wheel), HandCategory::STRAIGHT);
+        assert!(score_short_deck(&wheel) > score_short_deck(&trips));
+        assert!(score_short_deck(&wheel) < score_short_deck(&ten_high));
+
+        let wheel_flush: Vec<u8> = ints(&[("spade", "ace"), ("spade", "six"), ("spade", "seven"), ("spade", "eight"), ("spade", "nine"), ("heart", "ace"), ("club", "ace")]);
+        assert_eq!(short_deck_category(&wheel_flush), HandCategory::STRAIGHTFLUSH);
+        assert!(score_short_deck(&wheel_flush) > score_short_deck(&quads));
+    }
+
+    #[test]
+    pub fn test_razz() {
+        let wheel: Vec<u8> = ints(&[("spade", "ace"), ("club", "two"), ("diamond", "three"), ("heart", "four"), ("club", "five"), ("spade", "king"), ("club", "king")]);
+        let eight: Vec<u8> = ints(&[("spade", "eight"), ("club", "two"), ("diamond", "three"), ("heart", "four"), ("club", "five"), ("spade", "eight"), ("club", "queen")]);
+        let nine: Vec<u8> = ints(&[("spade", "nine"), ("club", "ace"), ("diamond", "two"), ("heart", "three"), ("club", "four"), ("spade", "nine"), ("club", "jack")]);
+        let pair: Vec<u8> = ints(&[("spade", "ace"), ("club", "ace"), ("diamond", "two"), ("heart", "two"), ("club", "three"), ("spade", "three"), ("club", "four")]);
+        // straights and flushes don't count against a low
+        assert_eq!(razz_category(&wheel), HandCategory::HIGHCARD);
+        assert!(score_razz(&wheel) > score_razz(&eight));
+        assert!(score_razz(&eight) > score_razz(&nine));
+        // three pairs in seven cards make a pair of aces with 4-3-2, worse than any unpaired low
+        assert_eq!(razz_category(&pair), HandCategory::PAIR);
+        assert!(score_razz(&nine) > score_razz(&pair));
+        assert_eq!(best_razz_hand(&pair).iter().map(|card| low_rank(*card)).max(), Some(4));
+
+        // showing hands of fewer cards compare the same way
+        let king: Vec<u8> = ints(&[("spade", "king")]);
+        let queen: Vec<u8> = ints(&[("spade", "queen")]);
+        assert!(score_razz(&queen) > score_razz(&king));
+        let paired: Vec<u8> = ints(&[("spade", "two"), ("heart", "two")]);
+        let unpaired: Vec<u8> = ints(&[("spade", "king"), ("heart", "queen")]);
+        assert!(score_razz(&unpaired) > score_razz(&paired));
+    }
+
+    #[test]
+    pub fn test_deuce_to_seven() {
+        let hand = |cards: &[(&str, &str)]| -> [u8; 5] {ints(cards).try_into().unwrap()};
+        let number_one: [u8; 5] = hand(&[("spade", "seven"), ("club", "five"), ("diamond", "four"), ("heart", "three"), ("club", "two")]);
+        let seven_six: [u8; 5] = hand(&[("spade", "seven"), ("club", "six"), ("diamond", "four"), ("heart", "three"), ("club", "two")]);
+        let eight: [u8; 5] = hand(&[("spade", "eight"), ("club", "five"), ("diamond", "four"), ("heart", "three"), ("club", "two")]);
+        assert!(score_deuce_to_seven(&number_one) > score_deuce_to_seven(&seven_six));
+        assert!(score_deuce_to_seven(&seven_six) > score_deuce_to_seven(&eight));
+
+        // aces are high and straights and flushes count against the hand
+        let ace: [u8; 5] = hand(&[("spade", "ace"), ("club", "five"), ("diamond", "four"), ("heart", "three"), ("club", "two")]);
+        let king: [u8; 5] = hand(&[("spade", "king"), ("club", "queen"), ("diamond", "jack"), ("heart", "ten"), ("club", "eight")]);
+        let straight: [u8; 5] = hand(&[("spade", "six"), ("club", "five"), ("diamond", "four"), ("heart", "three"), ("club", "two")]);
+        let flush: [u8; 5] = hand(&[("club", "seven"), ("club", "five"), ("club", "four"), ("club", "three"), ("club", "two")]);
+        let pair: [u8; 5] = hand(&[("spade", "two"), ("club", "five"), ("diamond", "four"), ("heart", "three"), ("club", "two")]);
+        assert!(score_deuce_to_seven(&king) > score_deuce_to_seven(&ace));
+        assert!(score_deuce_to_seven(&ace) > score_deuce_to_seven(&pair));
+        assert!(score_deuce_to_seven(&pair) > score_deuce_to_seven(&straight));
+        assert!(score_deuce_to_seven(&straight) > score_deuce_to_seven(&flush));
+    }
 }
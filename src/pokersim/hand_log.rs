@@ -0,0 +1,326 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::io::{BufRead, Write};
+use super::coms::*;
+use super::dealer::Dealer;
+use super::games::{play_board_game, AllIn, BoardGame, Betting, HandEvaluation, Posting, Rake};
+use super::rules::{AnteType, DeckType, Straddle};
+use super::player::{BlindType, Play, ScriptedPlayer};
+
+
+// Machine readable log of every hand, one JSON object per line.
+// Replaying a hand deals the logged deck order to players that repeat the logged actions,
+// the replayed hand has to serialize to exactly the same line.
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HandLog {
+    pub hand_id: u64,
+    pub seed: u64,
+    pub players: Vec<String>,
+    pub stacks: Vec<u32>,
+    pub blinds: [u32; 2],
+    pub ante: u32,
+    pub deck: Vec<u8>,
+    // stacks are after the time charge, replays take it again
+    #[serde(default, skip_serializing_if = "Rake::is_off")]
+    pub rake: Rake,
+    // how all ins before the river were settled
+    #[serde(default, skip_serializing_if = "AllIn::is_run_once")]
+    pub all_in: AllIn,
+    // ActionMessages in the order they were played, including antes and blinds
+    pub actions: Vec<Message>,
+    // the EndOfRoundMessage of the hand
+    pub result: Message,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Decode(String),
+    Io(String),
+    // the replayed hand differs from the log
+    Mismatch { hand_id: u64, logged: String, replayed: String },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Decode(reason) => write!(f, "could not decode hand log: {}", reason),
+            ReplayError::Io(reason) => write!(f, "could not read hand log: {}", reason),
+            ReplayError::Mismatch { hand_id, logged, replayed } => {
+                write!(f, "hand {} replayed differently\n  logged:   {}\n  replayed: {}", hand_id, logged, replayed)
+            },
+        }
+    }
+}
+
+pub struct HandLogWriter {
+    writer: Box<dyn Write>,
+    next_hand_id: u64,
+    current: Option<HandLog>,
+}
+
+impl HandLogWriter {
+
+    pub fn new(writer: Box<dyn Write>) -> HandLogWriter {
+        return HandLogWriter { writer, next_hand_id: 1, current: None }
+    }
+
+    pub fn record_deck(&mut self, seed: u64, deck: &[u8]) {
+        // the deck isn't part of any message, games report it after the hand starts
+        if let Some(hand) = self.current.as_mut() {
+            hand.seed = seed;
+            hand.deck = deck.to_vec();
+        }
+    }
+
+    pub fn record_rake(&mut self, rake: &Rake) {
+        if let Some(hand) = self.current.as_mut() {
+            hand.rake = rake.clone();
+        }
+    }
+
+    pub fn record_all_in(&mut self, all_in: AllIn) {
+        if let Some(hand) = self.current.as_mut() {
+            hand.all_in = all_in;
+        }
+    }
+
+    pub fn record(&mut self, msg: &Message) -> std::io::Result<()> {
+        match msg {
+            Message::HandStartMessage { players, stacks, blinds, ante } => {
+                self.current = Some(HandLog {
+                    hand_id: self.next_hand_id,
+                    seed: 0,
+                    players: players.clone(),
+                    stacks: stacks.clone(),
+                    blinds: *blinds,
+                    ante: *ante,
+                    deck: vec![],
+                    rake: Rake::default(),
+                    all_in: AllIn::RunOnce,
+                    actions: vec![],
+                    result: Message::EndOfRoundMessage { winnings: vec![], board: vec![], showdown: vec![], mucked: vec![], pots: vec![], stacks: vec![], time_charge: 0, runs: vec![], all_in_ev: vec![] },
+                });
+                self.next_hand_id += 1;
+            },
+            Message::ActionMessage { .. } => {
+                if let Some(hand) = self.current.as_mut() {
+                    hand.actions.push(msg.clone());
+                }
+            },
+            Message::EndOfRoundMessage { .. } => {
+                if let Some(mut hand) = self.current.take() {
+                    hand.result = msg.clone();
+                    writeln!(self.writer, "{}", serde_json::to_string(&hand).unwrap())?;
+                    self.writer.flush()?;
+                }
+            },
+            _ => (),
+        }
+        return Ok(());
+    }
+}
+
+pub fn replay_player(log: &HandLog, player: usize) -> ScriptedPlayer {
+    // repeats the player's logged decisions, forced bets are posted by the engine.
+    // A player asked to act more often than logged calls, the result won't match the log
+    let plays: Vec<Play> = log.actions.iter().filter_map(|msg| match msg {
+        Message::ActionMessage { player: idx, action, amount, .. } if *idx == player => match action {
+            Action::POST => None,
+            Action::FOLD => Some(Play::Fold),
+            _ => Some(Play::Bet(*amount)),
+        },
+        _ => None,
+    }).collect();
+    return ScriptedPlayer { name: log.players[player].clone(), ..ScriptedPlayer::new(log.stacks[player] + log.rake.time_charge, plays) };
+}
+
+fn logged_posting(log: &HandLog) -> Posting {
+    // straddles, big blind antes and missed blinds are read back from the logged posts
+    let mut posting = Posting::default();
+    let mut antes: Vec<usize> = vec![];
+    for msg in log.actions.iter() {
+        match msg {
+            Message::ActionMessage { player, blind: Some(BlindType::Straddle), .. } => {
+                posting.straddle = if *player == 2 {Straddle::Utg} else {Straddle::Button};
+            },
+            Message::ActionMessage { player, blind: Some(BlindType::Dead), .. } => posting.missed_blinds.push(*player),
+            Message::ActionMessage { player, blind: Some(BlindType::Ante), .. } => antes.push(*player),
+            _ => (),
+        }
+    }
+    // every player antes unless the big blind posted the only ante
+    if antes == [1] {
+        posting.ante_type = AnteType::BigBlind;
+    }
+    return posting;
+}
+
+pub fn replay_hand(line: &str) -> Result<HandLog, ReplayError> {
+    // plays a logged hand as no limit hold'em with the logged rake and all in settlement and checks it serializes to the same line
+    let log: HandLog = serde_json::from_str(line).map_err(|err| ReplayError::Decode(err.to_string()))?;
+    if log.deck.len() != 52 {
+        return Err(ReplayError::Decode(format!("hand {} has {} cards in the deck", log.hand_id, log.deck.len())));
+    }
+
+    let mut dealer = Dealer::new();
+    dealer.stack_deck(log.seed, log.deck.clone());
+    let mut players: Vec<ScriptedPlayer> = (0..log.players.len()).map(|idx| replay_player(&log, idx)).collect();
+    let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: log.rake.clone(), posting: logged_posting(&log), all_in: log.all_in };
+    play_board_game(&mut dealer, &mut players, log.blinds, log.ante, &game, None);
+
+    let mut notifications: Vec<Message> = players[0].notifications.clone();
+    let result: Option<Message> = notifications.pop();
+    let replayed = HandLog {
+        deck: dealer.deck.clone(),
+        actions: notifications,
+        result: result.unwrap_or(Message::EndOfGameMessage),
+        ..log.clone()
+    };
+    let replayed_line: String = serde_json::to_string(&replayed).unwrap();
+    if replayed_line != line.trim() {
+        return Err(ReplayError::Mismatch { hand_id: log.hand_id, logged: line.trim().to_string(), replayed: replayed_line });
+    }
+    return Ok(log);
+}
+
+pub fn replay_hand_log<R: BufRead>(reader: R) -> Result<usize, ReplayError> {
+    // replays every hand in a log, returns the number of hands that matched
+    let mut n_hands: usize = 0;
+    for line in reader.lines() {
+        let line: String = line.map_err(|err| ReplayError::Io(err.to_string()))?;
+        if line.trim().is_empty() {continue}
+        replay_hand(&line)?;
+        n_hands += 1;
+    }
+    return Ok(n_hands);
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pokersim::coms::tests as coms_tests;
+    use crate::pokersim::recorder::tests::SharedBuffer;
+
+    fn logged_game(name: &str, n_hands: usize, rake: Rake, posting: Posting) -> String {
+        // plays hands with scripted players and returns the log
+        let players: Vec<ScriptedPlayer> = vec![
+            ScriptedPlayer::new(1000, vec![Play::Bet(60), Play::Bet(0), Play::Bet(200)]),
+            ScriptedPlayer::new(300, vec![Play::Bet(300)]),
+            ScriptedPlayer::new(1000, vec![Play::Fold]),
+        ];
+        let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake, posting, all_in: AllIn::RunOnce };
+        return logged_hands(name, n_hands, players, &game);
+    }
+
+    fn logged_hands(name: &str, n_hands: usize, mut players: Vec<ScriptedPlayer>, game: &BoardGame) -> String {
+        let mut zmq_conn = coms_tests::test_server(name);
+        let buffer = SharedBuffer::default();
+        zmq_conn.write_hand_log(HandLogWriter::new(Box::new(buffer.clone())));
+        let table = coms_tests::spawn_table(&zmq_conn, vec![], vec![]);
+        zmq_conn.wait_for_clients(&[TABLE_IDENTITY.to_string()]).unwrap();
+
+        let mut dealer = Dealer::with_seed(11);
+        for (idx, player) in players.iter_mut().enumerate() {
+            player.name = format!("player_{}", idx);
+        }
+        for _ in 0..n_hands {
+            play_board_game(&mut dealer, &mut players, [5, 10], 1, game, Some(&zmq_conn));
+        }
+        zmq_conn.send_recv_message(Message::EndOfGameMessage, "");
+        table.join().unwrap();
+        return buffer.text();
+    }
+
+    #[test]
+    pub fn test_replay_hand_log() {
+        let log: String = logged_game("test_replay_hand_log", 4, Rake::default(), Posting::default());
+        assert_eq!(log.lines().count(), 4);
+        assert_eq!(replay_hand_log(log.as_bytes()).unwrap(), 4);
+
+        let first: HandLog = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        assert_eq!(first.hand_id, 1);
+        assert_eq!(first.players, vec!["player_0", "player_1", "player_2"]);
+        assert_eq!(first.stacks, vec![1000, 300, 1000]);
+        let mut deck: Vec<u8> = first.deck.clone();
+        deck.sort();
+        assert_eq!(deck, (1..=52).collect::<Vec<u8>>());
+        // antes and blinds are logged as actions
+        assert_eq!(first.actions.iter().filter(|msg| matches!(msg, Message::ActionMessage { action: Action::POST, .. })).count(), 5);
+    }
+
+    #[test]
+    pub fn test_replay_detects_changes() {
+        let log: String = logged_game("test_replay_detects_changes", 1, Rake::default(), Posting::default());
+        let mut hand: HandLog = serde_json::from_str(log.trim()).unwrap();
+
+        // the logged result is what an engine that paid out differently would have written
+        if let Message::EndOfRoundMessage { stacks, .. } = &mut hand.result {
+            stacks[0] += 1;
+        }
+        let line: String = serde_json::to_string(&hand).unwrap();
+        match replay_hand(&line) {
+            Err(ReplayError::Mismatch { hand_id: 1, .. }) => (),
+            result => panic!("expected a mismatch, got {:?}", result),
+        }
+
+        // logged actions are replayed as they were made, a different bet changes the pot
+        let mut hand: HandLog = serde_json::from_str(log.trim()).unwrap();
+        if let Message::ActionMessage { amount, .. } = &mut hand.actions[5] {
+            *amount += 10;
+        }
+        assert!(matches!(replay_hand(&serde_json::to_string(&hand).unwrap()), Err(ReplayError::Mismatch { .. })));
+
+        assert!(matches!(replay_hand("{\"hand_id\": 1}"), Err(ReplayError::Decode(_))));
+    }
+
+    #[test]
+    pub fn test_replay_raked_hands() {
+        let rake = Rake { percent: 5.0, cap: Some(15), time_charge: 2, ..Rake::default() };
+        let log: String = logged_game("test_replay_raked_hands", 3, rake.clone(), Posting::default());
+        assert_eq!(replay_hand_log(log.as_bytes()).unwrap(), 3);
+
+        let first: HandLog = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        assert_eq!(first.rake, rake);
+        assert_eq!(first.stacks, vec![998, 298, 998]);
+
+        // a hand replayed without the rake pays out more than was logged
+        let mut hand: HandLog = first.clone();
+        hand.rake = Rake { time_charge: 2, ..Rake::default() };
+        assert!(matches!(replay_hand(&serde_json::to_string(&hand).unwrap()), Err(ReplayError::Mismatch { .. })));
+    }
+
+    #[test]
+    pub fn test_replay_straddles() {
+        // straddles, big blind antes and missed blinds are replayed from the logged posts
+        let posting = Posting { straddle: Straddle::Button, ante_type: AnteType::BigBlind, missed_blinds: vec![] };
+        let log: String = logged_game("test_replay_straddles", 3, Rake::default(), posting.clone());
+        assert_eq!(replay_hand_log(log.as_bytes()).unwrap(), 3);
+        let first: HandLog = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        // with three players the button is under the gun
+        assert_eq!(logged_posting(&first), Posting { straddle: Straddle::Utg, ..posting });
+
+        let posting = Posting { missed_blinds: vec![2], ..Posting::default() };
+        let log: String = logged_game("test_replay_missed_blinds", 2, Rake::default(), posting.clone());
+        assert_eq!(replay_hand_log(log.as_bytes()).unwrap(), 2);
+        let first: HandLog = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        assert_eq!(logged_posting(&first), posting);
+    }
+
+    #[test]
+    pub fn test_replay_runs() {
+        // player 1 is all in on the flop and called, the board is run twice
+        let players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(1000, vec![Play::Bet(5), Play::Bet(0)]), ScriptedPlayer::new(300, vec![Play::Bet(0), Play::Bet(289)])];
+        let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(), posting: Posting::default(), all_in: AllIn::RunTimes(2) };
+        let log: String = logged_hands("test_replay_runs", 1, players, &game);
+        assert_eq!(replay_hand_log(log.as_bytes()).unwrap(), 1);
+        let mut hand: HandLog = serde_json::from_str(log.trim()).unwrap();
+        assert_eq!(hand.all_in, AllIn::RunTimes(2));
+        assert!(matches!(&hand.result, Message::EndOfRoundMessage { runs, all_in_ev, .. } if runs.len() == 2 && all_in_ev.is_empty()));
+
+        // replayed with the board run once, the hand doesn't match the log
+        hand.all_in = AllIn::RunOnce;
+        assert!(matches!(replay_hand(&serde_json::to_string(&hand).unwrap()), Err(ReplayError::Mismatch { .. })));
+    }
+}
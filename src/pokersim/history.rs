@@ -0,0 +1,738 @@
+use std::fmt;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::card_enums::Card;
+use super::coms::*;
+use super::hand_eval::{score_hand, hand_category, HandCategory};
+use super::player::BlindType;
+
+
+// Hand histories in the PokerStars text format, so hands can be loaded into tracking and replay tools.
+// Every player's hole cards are written as "Dealt to" lines, pokersim sees all of them.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HandHistory {
+    pub hand_id: u64,
+    pub table: String,
+    // unix time in seconds when the hand started
+    pub timestamp: u64,
+    // HandStart, Deal, Action, Board and EndOfRound messages in the order they were published
+    pub events: Vec<Message>,
+}
+
+#[derive(Debug)]
+pub enum HistoryError {
+    Parse(String),
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::Parse(reason) => write!(f, "invalid hand history: {}", reason),
+        }
+    }
+}
+
+fn parse_error(reason: &str, line: &str) -> HistoryError {
+    return HistoryError::Parse(format!("{} in line \"{}\"", reason, line));
+}
+
+fn card_name(card: u8) -> String {
+    return Card::card_from_int(&card).short_name();
+}
+
+fn cards_text(cards: &[u8]) -> String {
+    return format!("[{}]", cards.iter().map(|card| card_name(*card)).collect::<Vec<String>>().join(" "));
+}
+
+fn parse_cards(text: &str) -> Result<Vec<u8>, HistoryError> {
+    // cards in brackets, e.g. "[Ah Kd]"
+    let inner: &str = text.trim().strip_prefix('[').and_then(|t| t.strip_suffix(']'))
+        .ok_or_else(|| parse_error("expected cards", text))?;
+    return inner.split_whitespace()
+        .map(|name| Card::card_from_short_name(name).map(|card| card.to_int()).ok_or_else(|| parse_error("unknown card", name)))
+        .collect();
+}
+
+fn category_text(category: &HandCategory) -> &'static str {
+    match category {
+        HandCategory::HIGHCARD => "high card",
+        HandCategory::PAIR => "a pair",
+        HandCategory::TWOPAIR => "two pair",
+        HandCategory::TRIPS => "three of a kind",
+        HandCategory::STRAIGHT => "a straight",
+        HandCategory::FLUSH => "a flush",
+        HandCategory::FULLHOUSE => "a full house",
+        HandCategory::QUADS => "four of a kind",
+        HandCategory::STRAIGHTFLUSH => "a straight flush",
+        HandCategory::ROYALFLUSH => "a Royal Flush",
+    }
+}
+
+fn street_name(turn: &Turn) -> &'static str {
+    match turn {
+        Turn::HOLE => "Flop",
+        Turn::FLOP => "Flop",
+        Turn::TURN => "Turn",
+        Turn::RIVER => "River",
+        Turn::THIRD => "3rd Street",
+        Turn::FOURTH => "4th Street",
+        Turn::FIFTH => "5th Street",
+        Turn::SIXTH => "6th Street",
+        Turn::SEVENTH => "7th Street",
+        Turn::DRAW1 => "First Draw",
+        Turn::DRAW2 => "Second Draw",
+        Turn::DRAW3 => "Third Draw",
+    }
+}
+
+fn pot_label(idx: usize, n_pots: usize) -> String {
+    match (idx, n_pots) {
+        (_, 1) => return "pot".to_string(),
+        (0, _) => return "main pot".to_string(),
+        (idx, _) => return format!("side pot-{}", idx),
+    }
+}
+
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    // days since 1970-01-01 of a proleptic gregorian date
+    let year: i64 = if month <= 2 {year - 1} else {year};
+    let era: i64 = year.div_euclid(400);
+    let year_of_era: u64 = (year - era * 400) as u64;
+    let day_of_year: u64 = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era: u64 = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    return era * 146097 + day_of_era as i64 - 719468;
+}
+
+fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let days: i64 = days + 719468;
+    let era: i64 = days.div_euclid(146097);
+    let day_of_era: u64 = (days - era * 146097) as u64;
+    let year_of_era: u64 = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year: u64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: u64 = (5 * day_of_year + 2) / 153;
+    let day: u64 = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month: u64 = if month_index < 10 {month_index + 3} else {month_index - 9};
+    let year: i64 = year_of_era as i64 + era * 400 + if month <= 2 {1} else {0};
+    return (year, month, day);
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86400) as i64);
+    let seconds: u64 = timestamp % 86400;
+    return format!("{}/{:02}/{:02} {}:{:02}:{:02} UTC", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60);
+}
+
+fn parse_timestamp(text: &str) -> Option<u64> {
+    // "2026/10/19 8:05:00 UTC"
+    let mut parts = text.split_whitespace();
+    let date: Vec<u64> = parts.next()?.split('/').map(|part| part.parse().ok()).collect::<Option<Vec<u64>>>()?;
+    let time: Vec<u64> = parts.next()?.split(':').map(|part| part.parse().ok()).collect::<Option<Vec<u64>>>()?;
+    if date.len() != 3 || time.len() != 3 {return None}
+    let days: i64 = days_from_civil(date[0] as i64, date[1], date[2]);
+    return Some(days as u64 * 86400 + time[0] * 3600 + time[1] * 60 + time[2]);
+}
+
+impl HandHistory {
+
+    pub fn to_pokerstars(&self) -> String {
+        let (players, stacks, blinds) = match self.events.first() {
+            Some(Message::HandStartMessage { players, stacks, blinds, .. }) => (players, stacks, *blinds),
+            _ => panic!("hand {} doesn't start with a HandStartMessage", self.hand_id),
+        };
+        let n_players: usize = players.len();
+        // the small blind sits after the button, heads up the button posts the small blind
+        let button: usize = if n_players == 2 {0} else {n_players - 1};
+
+        let mut lines: Vec<String> = vec![
+            format!("PokerStars Hand #{}: Hold'em No Limit ({}/{}) - {}", self.hand_id, blinds[0], blinds[1], format_timestamp(self.timestamp)),
+            format!("Table '{}' {}-max Seat #{} is the button", self.table, n_players, button + 1),
+        ];
+        for (idx, name) in players.iter().enumerate() {
+            lines.push(format!("Seat {}: {} ({} in chips)", idx + 1, name, stacks[idx]));
+        }
+
+        let mut dealt: Vec<String> = vec![];
+        let mut street_bets: Vec<u32> = vec![0; n_players];
+        let mut folded: Vec<Option<Turn>> = vec![None; n_players];
+        let mut board: Vec<u8> = vec![];
+        for msg in self.events.iter() {
+            match msg {
+                Message::DealMessage { player, cards: Some(cards) } => {
+                    dealt.push(format!("Dealt to {} {}", players[*player], cards_text(cards)));
+                },
+                Message::ActionMessage { player, action, amount, all_in, turn, blind, .. } => {
+                    let name: &String = &players[*player];
+                    let text: String = match action {
+                        Action::POST => {
+                            // antes and dead blinds don't count toward the player's bet
+                            if !matches!(blind, Some(BlindType::Ante | BlindType::Dead)) {street_bets[*player] += amount}
+                            match blind {
+                                Some(BlindType::Ante) => format!("posts the ante {}", amount),
+                                Some(BlindType::Little) => format!("posts small blind {}", amount),
+                                Some(BlindType::Straddle) => format!("posts straddle {}", amount),
+                                Some(BlindType::Dead) => format!("posts dead blind {}", amount),
+                                _ => format!("posts big blind {}", amount),
+                            }
+                        },
+                        Action::FOLD => {
+                            folded[*player] = Some(turn.clone());
+                            "folds".to_string()
+                        },
+                        Action::CHECK => "checks".to_string(),
+                        Action::CALL => {
+                            street_bets[*player] += amount;
+                            format!("calls {}", amount)
+                        },
+                        Action::BET => {
+                            street_bets[*player] += amount;
+                            format!("bets {}", amount)
+                        },
+                        Action::RAISE => {
+                            let current_bet: u32 = *street_bets.iter().max().unwrap();
+                            street_bets[*player] += amount;
+                            format!("raises {} to {}", street_bets[*player] - current_bet, street_bets[*player])
+                        },
+                    };
+                    let all_in_text: &str = if *all_in {" and is all-in"} else {""};
+                    if *action != Action::POST && !dealt.is_empty() {
+                        lines.push("*** HOLE CARDS ***".to_string());
+                        lines.append(&mut dealt);
+                    }
+                    lines.push(format!("{}: {}{}", name, text, all_in_text));
+                },
+                Message::BoardMessage { turn, cards } => {
+                    street_bets = vec![0; n_players];
+                    let (previous, new) = cards.split_at(board.len());
+                    let header: String = match turn {
+                        Turn::FLOP => format!("*** FLOP *** {}", cards_text(new)),
+                        Turn::TURN => format!("*** TURN *** {} {}", cards_text(previous), cards_text(new)),
+                        _ => format!("*** RIVER *** {} {}", cards_text(previous), cards_text(new)),
+                    };
+                    if !dealt.is_empty() {
+                        lines.push("*** HOLE CARDS ***".to_string());
+                        lines.append(&mut dealt);
+                    }
+                    lines.push(header);
+                    board = cards.clone();
+                },
+                Message::EndOfRoundMessage { showdown, mucked, pots, runs, .. } => {
+                    if !dealt.is_empty() {
+                        lines.push("*** HOLE CARDS ***".to_string());
+                        lines.append(&mut dealt);
+                    }
+                    if !showdown.is_empty() || !mucked.is_empty() {
+                        lines.push("*** SHOW DOWN ***".to_string());
+                        for hand in showdown.iter() {
+                            lines.push(format!("{}: shows {} ({})", players[hand.player], cards_text(&hand.cards), category_text(&hand.hand_category)));
+                        }
+                        for idx in mucked.iter() {
+                            lines.push(format!("{}: mucks hand", players[*idx]));
+                        }
+                    }
+                    let mut won: Vec<u32> = vec![0; n_players];
+                    for (pot_idx, pot) in pots.iter().enumerate() {
+                        for (winner, amount) in pot.winners.iter().zip(pot.winnings.iter()) {
+                            won[*winner] += amount;
+                            lines.push(format!("{} collected {} from {}", players[*winner], amount, pot_label(pot_idx, pots.len())));
+                        }
+                    }
+
+                    lines.push("*** SUMMARY ***".to_string());
+                    lines.push(format!("Total pot {} | Rake {}", pots.iter().map(|pot| pot.amount).sum::<u32>(), pots.iter().map(|pot| pot.rake).sum::<u32>()));
+                    // a board run more than once lists every run, the streets above are the first run
+                    if runs.len() > 1 {
+                        for (idx, run) in runs.iter().enumerate() {
+                            lines.push(format!("Run {} Board {}", idx + 1, cards_text(run)));
+                        }
+                    }
+                    else if !board.is_empty() {
+                        lines.push(format!("Board {}", cards_text(&board)));
+                    }
+                    for (idx, name) in players.iter().enumerate() {
+                        let mut seat: String = format!("Seat {}: {}", idx + 1, name);
+                        if idx == button {seat.push_str(" (button)")}
+                        if idx == 0 && blinds[0] > 0 {seat.push_str(" (small blind)")}
+                        if idx == 1 && blinds[1] > 0 {seat.push_str(" (big blind)")}
+                        let shown: Option<&ShowdownHand> = showdown.iter().find(|hand| hand.player == idx);
+                        let result: String = match (&folded[idx], shown) {
+                            (Some(Turn::HOLE), _) => "folded before Flop".to_string(),
+                            (Some(turn), _) => format!("folded on the {}", street_name(turn)),
+                            (None, Some(hand)) if won[idx] > 0 => format!("showed {} and won ({}) with {}", cards_text(&hand.cards), won[idx], category_text(&hand.hand_category)),
+                            (None, Some(hand)) => format!("showed {} and lost with {}", cards_text(&hand.cards), category_text(&hand.hand_category)),
+                            (None, None) if won[idx] > 0 => format!("collected ({})", won[idx]),
+                            (None, None) => "mucked".to_string(),
+                        };
+                        lines.push(format!("{} {}", seat, result));
+                    }
+                },
+                _ => (),
+            }
+        }
+        return lines.join("\n") + "\n";
+    }
+
+    pub fn from_pokerstars(text: &str) -> Result<HandHistory, HistoryError> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+
+        let header: &str = lines.next().ok_or_else(|| HistoryError::Parse("empty hand history".to_string()))?;
+        let rest: &str = header.strip_prefix("PokerStars Hand #").ok_or_else(|| parse_error("expected a PokerStars hand", header))?;
+        let (hand_id, rest) = rest.split_once(": ").ok_or_else(|| parse_error("expected a hand id", header))?;
+        let hand_id: u64 = hand_id.parse().map_err(|_| parse_error("invalid hand id", header))?;
+        let (stakes, date) = rest.split_once(" - ").ok_or_else(|| parse_error("expected a date", header))?;
+        let stakes: &str = stakes.rsplit_once('(').and_then(|(_, stakes)| stakes.strip_suffix(')'))
+            .ok_or_else(|| parse_error("expected blinds", header))?;
+        let (small, big) = stakes.split_once('/').ok_or_else(|| parse_error("expected blinds", header))?;
+        let blinds: [u32; 2] = [
+            small.parse().map_err(|_| parse_error("invalid small blind", header))?,
+            big.parse().map_err(|_| parse_error("invalid big blind", header))?,
+        ];
+        let timestamp: u64 = parse_timestamp(date).ok_or_else(|| parse_error("invalid date", header))?;
+
+        let table_line: &str = lines.next().ok_or_else(|| HistoryError::Parse("missing table".to_string()))?;
+        let table: &str = table_line.strip_prefix("Table '").and_then(|rest| rest.rsplit_once('\'')).map(|(table, _)| table)
+            .ok_or_else(|| parse_error("expected a table", table_line))?;
+
+        let mut players: Vec<String> = vec![];
+        let mut stacks: Vec<u32> = vec![];
+        let mut deals: Vec<Option<[u8; 2]>> = vec![];
+        let mut actions: Vec<Message> = vec![];
+        let mut ante: u32 = 0;
+        let mut turn: Turn = Turn::HOLE;
+        let mut board: Vec<u8> = vec![];
+        let mut bets: Vec<u32> = vec![];
+        let mut street_bets: Vec<u32> = vec![];
+        let mut shown: Vec<(usize, [u8; 2])> = vec![];
+        let mut mucked: Vec<usize> = vec![];
+        let mut pots: Vec<(String, PotResult)> = vec![];
+        let mut rake: u32 = 0;
+        let mut summary: bool = false;
+
+        // the longest player name that starts the line, names can contain spaces and colons
+        let find_player = |players: &Vec<String>, line: &str| -> Option<usize> {
+            return players.iter().enumerate()
+                .filter(|(_, name)| line.starts_with(name.as_str()))
+                .max_by_key(|(_, name)| name.len())
+                .map(|(idx, _)| idx);
+        };
+
+        for line in lines {
+            if line == "*** SUMMARY ***" {summary = true}
+            if summary {
+                // only the rake isn't worked out again from the hand
+                if let Some((_, raked)) = line.strip_prefix("Total pot ").and_then(|rest| rest.split_once(" | Rake ")) {
+                    rake = raked.parse().map_err(|_| parse_error("invalid rake", line))?;
+                }
+                continue;
+            }
+            if let Some(seat) = line.strip_prefix("Seat ") && actions.is_empty() {
+                let (_, seat) = seat.split_once(": ").ok_or_else(|| parse_error("expected a seat", line))?;
+                let (name, chips) = seat.rsplit_once(" (").ok_or_else(|| parse_error("expected chips", line))?;
+                let chips: u32 = chips.strip_suffix(" in chips)").and_then(|chips| chips.parse().ok())
+                    .ok_or_else(|| parse_error("invalid chips", line))?;
+                players.push(name.to_string());
+                stacks.push(chips);
+                deals.push(None);
+                bets.push(0);
+                street_bets.push(0);
+            }
+            else if line == "*** HOLE CARDS ***" || line == "*** SHOW DOWN ***" {}
+            else if let Some(cards) = line.strip_prefix("*** FLOP *** ") {
+                board = parse_cards(cards)?;
+                turn = Turn::FLOP;
+                street_bets = vec![0; players.len()];
+                actions.push(Message::BoardMessage { turn: turn.clone(), cards: board.clone() });
+            }
+            else if let Some(cards) = line.strip_prefix("*** TURN *** ").or_else(|| line.strip_prefix("*** RIVER *** ")) {
+                let new: &str = cards.rsplit_once(" [").map(|(_, new)| new).ok_or_else(|| parse_error("expected cards", line))?;
+                board.append(&mut parse_cards(&format!("[{}", new))?);
+                turn = if line.starts_with("*** TURN") {Turn::TURN} else {Turn::RIVER};
+                street_bets = vec![0; players.len()];
+                actions.push(Message::BoardMessage { turn: turn.clone(), cards: board.clone() });
+            }
+            else if let Some(rest) = line.strip_prefix("Dealt to ") {
+                let player: usize = find_player(&players, rest).ok_or_else(|| parse_error("unknown player", line))?;
+                let cards: Vec<u8> = parse_cards(&rest[players[player].len()..])?;
+                deals[player] = Some(cards.try_into().map_err(|_| parse_error("expected two hole cards", line))?);
+            }
+            else {
+                let player: usize = find_player(&players, line).ok_or_else(|| parse_error("unknown player", line))?;
+                let rest: &str = &line[players[player].len()..];
+                if let Some(rest) = rest.strip_prefix(" collected ") {
+                    let (amount, label) = rest.split_once(" from ").ok_or_else(|| parse_error("expected a pot", line))?;
+                    let amount: u32 = amount.parse().map_err(|_| parse_error("invalid amount", line))?;
+                    if pots.last().is_none_or(|(last, _)| last != label) {
+                        pots.push((label.to_string(), PotResult { amount: 0, winners: vec![], winnings: vec![], rake: 0 }));
+                    }
+                    let pot: &mut PotResult = &mut pots.last_mut().unwrap().1;
+                    pot.amount += amount;
+                    pot.winners.push(player);
+                    pot.winnings.push(amount);
+                    continue;
+                }
+                let rest: &str = rest.strip_prefix(": ").ok_or_else(|| parse_error("expected an action", line))?;
+                if let Some(cards) = rest.strip_prefix("shows ") {
+                    let cards: &str = cards.split_once(']').map(|(cards, _)| cards).ok_or_else(|| parse_error("expected cards", line))?;
+                    let cards: Vec<u8> = parse_cards(&format!("{}]", cards))?;
+                    shown.push((player, cards.try_into().map_err(|_| parse_error("expected two hole cards", line))?));
+                    continue;
+                }
+                if rest == "mucks hand" {
+                    mucked.push(player);
+                    continue;
+                }
+
+                let (rest, all_in) = match rest.strip_suffix(" and is all-in") {
+                    Some(rest) => (rest, true),
+                    None => (rest, false),
+                };
+                let words: Vec<&str> = rest.split_whitespace().collect();
+                let number = |word: &str| -> Result<u32, HistoryError> {
+                    return word.parse().map_err(|_| parse_error("invalid amount", line));
+                };
+                let (action, amount, blind): (Action, u32, Option<BlindType>) = match words.as_slice() {
+                    ["posts", "the", "ante", amount] => {
+                        ante = number(amount)?;
+                        (Action::POST, ante, Some(BlindType::Ante))
+                    },
+                    ["posts", "dead", "blind", amount] => (Action::POST, number(amount)?, Some(BlindType::Dead)),
+                    ["posts", blind @ ("small" | "big" | "straddle"), ..] => {
+                        let (btype, amount): (BlindType, &str) = match (*blind, words.as_slice()) {
+                            ("small", [_, _, "blind", amount]) => (BlindType::Little, amount),
+                            ("big", [_, _, "blind", amount]) => (BlindType::Big, amount),
+                            ("straddle", [_, _, amount]) => (BlindType::Straddle, amount),
+                            _ => return Err(parse_error("unknown blind", line)),
+                        };
+                        street_bets[player] += number(amount)?;
+                        (Action::POST, number(amount)?, Some(btype))
+                    },
+                    ["folds"] => (Action::FOLD, 0, None),
+                    ["checks"] => (Action::CHECK, 0, None),
+                    ["calls", amount] => (Action::CALL, number(amount)?, None),
+                    ["bets", amount] => (Action::BET, number(amount)?, None),
+                    ["raises", _, "to", total] => (Action::RAISE, number(total)? - street_bets[player], None),
+                    _ => return Err(parse_error("unknown action", line)),
+                };
+                if action != Action::POST {street_bets[player] += amount}
+                bets[player] += amount;
+                actions.push(Message::ActionMessage {
+                    player,
+                    action,
+                    amount,
+                    all_in,
+                    current_pot: bets.iter().sum(),
+                    turn: turn.clone(),
+                    blind,
+                });
+            }
+        }
+
+        // the rake is taken from the main pot first, histories only have the total
+        if let Some((_, pot)) = pots.first_mut() {
+            pot.amount += rake;
+            pot.rake = rake;
+        }
+
+        // results are worked out again from the cards and the chips collected
+        let mut won: Vec<u32> = vec![0; players.len()];
+        for (_, pot) in pots.iter() {
+            for (winner, amount) in pot.winners.iter().zip(pot.winnings.iter()) {
+                won[*winner] += amount;
+            }
+        }
+        let mut showdown: Vec<ShowdownHand> = vec![];
+        for (player, cards) in shown.iter() {
+            let final_cards: [u8; 5] = board.as_slice().try_into().map_err(|_| HistoryError::Parse("showdown without a full board".to_string()))?;
+            showdown.push(ShowdownHand {
+                player: *player,
+                cards: cards.to_vec(),
+                hand_category: hand_category(cards, &final_cards),
+                hand_score: score_hand(cards, &final_cards),
+                hand_rank: 0,
+            });
+        }
+        let shown_scores: Vec<f64> = showdown.iter().map(|hand| hand.hand_score).collect();
+        for hand in showdown.iter_mut() {
+            let mut better: Vec<f64> = shown_scores.iter().copied().filter(|score| *score > hand.hand_score).collect();
+            better.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            better.dedup();
+            hand.hand_rank = better.len() + 1;
+        }
+
+        let mut events: Vec<Message> = vec![Message::HandStartMessage { players: players.clone(), stacks: stacks.clone(), blinds, ante }];
+        for (player, cards) in deals.iter().enumerate() {
+            events.push(Message::DealMessage { player, cards: cards.map(|cards| cards.to_vec()) });
+        }
+        events.append(&mut actions);
+        events.push(Message::EndOfRoundMessage {
+            winnings: (0..players.len()).map(|idx| won[idx] as i32 - bets[idx] as i32).collect(),
+            board,
+            showdown,
+            mucked,
+            pots: pots.into_iter().map(|(_, pot)| pot).collect(),
+            stacks: (0..players.len()).map(|idx| stacks[idx] - bets[idx] + won[idx]).collect(),
+            time_charge: 0,
+            runs: vec![],
+            all_in_ev: vec![],
+        });
+        return Ok(HandHistory { hand_id, table: table.to_string(), timestamp, events });
+    }
+}
+
+pub fn parse_hand_histories(text: &str) -> Result<Vec<HandHistory>, HistoryError> {
+    // hands in a file are separated by blank lines
+    let mut hands: Vec<HandHistory> = vec![];
+    let mut current: Vec<&str> = vec![];
+    for line in text.lines().chain([""]) {
+        if line.starts_with("PokerStars Hand #") && !current.is_empty() {
+            hands.push(HandHistory::from_pokerstars(&current.join("\n"))?);
+            current.clear();
+        }
+        if !line.trim().is_empty() {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        hands.push(HandHistory::from_pokerstars(&current.join("\n"))?);
+    }
+    return Ok(hands);
+}
+
+pub struct HandHistoryWriter {
+    writer: Box<dyn Write>,
+    table: String,
+    next_hand_id: u64,
+    current: Option<HandHistory>,
+}
+
+impl HandHistoryWriter {
+
+    pub fn new(writer: Box<dyn Write>, table: &str) -> HandHistoryWriter {
+        return HandHistoryWriter { writer, table: table.to_string(), next_hand_id: 1, current: None }
+    }
+
+    pub fn record(&mut self, msg: &Message) -> std::io::Result<()> {
+        // collects the events of a hand and writes it out when the hand ends
+        match msg {
+            Message::HandStartMessage { .. } => {
+                let timestamp: u64 = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+                self.current = Some(HandHistory { hand_id: self.next_hand_id, table: self.table.clone(), timestamp, events: vec![msg.clone()] });
+                self.next_hand_id += 1;
+            },
+            Message::DealMessage { .. } | Message::ActionMessage { .. } | Message::BoardMessage { .. } => {
+                if let Some(hand) = self.current.as_mut() {
+                    hand.events.push(msg.clone());
+                }
+            },
+            Message::EndOfRoundMessage { .. } => {
+                if let Some(mut hand) = self.current.take() {
+                    hand.events.push(msg.clone());
+                    write!(self.writer, "{}\n\n", hand.to_pokerstars())?;
+                    self.writer.flush()?;
+                }
+            },
+            _ => (),
+        }
+        return Ok(());
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pokersim::coms::tests as coms_tests;
+    use crate::pokersim::dealer::Dealer;
+    use crate::pokersim::games::{play_board_game, AllIn, BoardGame, Betting, HandEvaluation, Posting, Rake};
+    use crate::pokersim::rules::{AnteType, DeckType, Straddle};
+    use crate::pokersim::player::{Play, ScriptedPlayer};
+    use crate::pokersim::recorder::tests::SharedBuffer;
+
+    fn example_hand() -> HandHistory {
+        let board: [u8; 5] = [27, 2, 40, 5, 46];
+        let events: Vec<Message> = vec![
+            Message::HandStartMessage { players: vec!["alice".to_string(), "bob".to_string(), "carol".to_string()], stacks: vec![1000, 500, 1000], blinds: [5, 10], ante: 0 },
+            Message::DealMessage { player: 0, cards: Some(vec![13, 12]) },
+            Message::DealMessage { player: 1, cards: Some(vec![1, 14]) },
+            Message::DealMessage { player: 2, cards: Some(vec![20, 34]) },
+            Message::ActionMessage { player: 0, action: Action::POST, amount: 5, all_in: false, current_pot: 5, turn: Turn::HOLE, blind: Some(BlindType::Little) },
+            Message::ActionMessage { player: 1, action: Action::POST, amount: 10, all_in: false, current_pot: 15, turn: Turn::HOLE, blind: Some(BlindType::Big) },
+            Message::ActionMessage { player: 2, action: Action::CALL, amount: 10, all_in: false, current_pot: 25, turn: Turn::HOLE, blind: None },
+            Message::ActionMessage { player: 0, action: Action::RAISE, amount: 25, all_in: false, current_pot: 50, turn: Turn::HOLE, blind: None },
+            Message::ActionMessage { player: 1, action: Action::CALL, amount: 20, all_in: false, current_pot: 70, turn: Turn::HOLE, blind: None },
+            Message::ActionMessage { player: 2, action: Action::FOLD, amount: 0, all_in: false, current_pot: 70, turn: Turn::HOLE, blind: None },
+            Message::BoardMessage { turn: Turn::FLOP, cards: board[..3].to_vec() },
+            Message::ActionMessage { player: 0, action: Action::CHECK, amount: 0, all_in: false, current_pot: 70, turn: Turn::FLOP, blind: None },
+            Message::ActionMessage { player: 1, action: Action::BET, amount: 470, all_in: true, current_pot: 540, turn: Turn::FLOP, blind: None },
+            Message::ActionMessage { player: 0, action: Action::CALL, amount: 470, all_in: false, current_pot: 1010, turn: Turn::FLOP, blind: None },
+            Message::BoardMessage { turn: Turn::TURN, cards: board[..4].to_vec() },
+            Message::BoardMessage { turn: Turn::RIVER, cards: board.to_vec() },
+            Message::EndOfRoundMessage {
+                winnings: vec![-500, 510, -10],
+                board: board.to_vec(),
+                showdown: vec![
+                    ShowdownHand { player: 1, cards: vec![1, 14], hand_category: HandCategory::QUADS, hand_score: score_hand(&[1, 14], &board), hand_rank: 1 },
+                    ShowdownHand { player: 0, cards: vec![13, 12], hand_category: HandCategory::PAIR, hand_score: score_hand(&[13, 12], &board), hand_rank: 2 },
+                ],
+                mucked: vec![],
+                pots: vec![PotResult { amount: 1010, winners: vec![1], winnings: vec![1010], rake: 0 }],
+                stacks: vec![500, 1010, 990],
+                time_charge: 0,
+                runs: vec![],
+                all_in_ev: vec![],
+            },
+        ];
+        return HandHistory { hand_id: 42, table: "table_1".to_string(), timestamp: 1_792_400_000, events };
+    }
+
+    #[test]
+    pub fn test_cards_text() {
+        assert_eq!(cards_text(&[13, 14, 35, 52]), "[As 2h Td Ac]");
+        assert_eq!(parse_cards("[As 2h Td Ac]").unwrap(), vec![13, 14, 35, 52]);
+        assert!(parse_cards("[As 1h]").is_err());
+    }
+
+    #[test]
+    pub fn test_timestamp() {
+        assert_eq!(format_timestamp(0), "1970/01/01 0:00:00 UTC");
+        assert_eq!(format_timestamp(1_792_400_000), "2026/10/19 8:53:20 UTC");
+        assert_eq!(parse_timestamp("2026/10/19 8:53:20 UTC"), Some(1_792_400_000));
+        assert_eq!(parse_timestamp("2024/02/29 23:59:59 UTC"), Some(1_709_251_199));
+    }
+
+    #[test]
+    pub fn test_write_hand() {
+        let hand = example_hand();
+        let text: String = hand.to_pokerstars();
+        let expected: &str = "\
+PokerStars Hand #42: Hold'em No Limit (5/10) - 2026/10/19 8:53:20 UTC
+Table 'table_1' 3-max Seat #3 is the button
+Seat 1: alice (1000 in chips)
+Seat 2: bob (500 in chips)
+Seat 3: carol (1000 in chips)
+alice: posts small blind 5
+bob: posts big blind 10
+*** HOLE CARDS ***
+Dealt to alice [As Ks]
+Dealt to bob [2s 2h]
+Dealt to carol [8h 9d]
+carol: calls 10
+alice: raises 20 to 30
+bob: calls 20
+carol: folds
+*** FLOP *** [2d 3s 2c]
+alice: checks
+bob: bets 470 and is all-in
+alice: calls 470
+*** TURN *** [2d 3s 2c] [6s]
+*** RIVER *** [2d 3s 2c 6s] [8c]
+*** SHOW DOWN ***
+bob: shows [2s 2h] (four of a kind)
+alice: shows [As Ks] (a pair)
+bob collected 1010 from pot
+*** SUMMARY ***
+Total pot 1010 | Rake 0
+Board [2d 3s 2c 6s 8c]
+Seat 1: alice (small blind) showed [As Ks] and lost with a pair
+Seat 2: bob (big blind) showed [2s 2h] and won (1010) with four of a kind
+Seat 3: carol (button) folded before Flop
+";
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    pub fn test_parse_hand() {
+        let hand = example_hand();
+        assert_eq!(HandHistory::from_pokerstars(&hand.to_pokerstars()).unwrap(), hand);
+        match hand.events.last().unwrap() {
+            Message::EndOfRoundMessage { winnings, stacks, .. } => {
+                assert_eq!(*winnings, vec![-500, 510, -10]);
+                assert_eq!(*stacks, vec![500, 1010, 990]);
+            },
+            msg => panic!("expected an EndOfRoundMessage, got {:?}", msg),
+        }
+        assert!(HandHistory::from_pokerstars("Full Tilt Poker Game #1").is_err());
+
+        // the rake is read back from the summary
+        let mut raked = example_hand();
+        if let Some(Message::EndOfRoundMessage { winnings, pots, stacks, .. }) = raked.events.last_mut() {
+            *winnings = vec![-500, 460, -10];
+            pots[0] = PotResult { amount: 1010, winners: vec![1], winnings: vec![960], rake: 50 };
+            *stacks = vec![500, 960, 990];
+        }
+        let text: String = raked.to_pokerstars();
+        assert!(text.contains("bob collected 960 from pot\n"));
+        assert!(text.contains("Total pot 1010 | Rake 50\n"));
+        assert_eq!(HandHistory::from_pokerstars(&text).unwrap(), raked);
+
+        // every run of the board is listed in the summary
+        let mut run_twice = example_hand();
+        if let Some(Message::EndOfRoundMessage { board, runs, .. }) = run_twice.events.last_mut() {
+            *runs = vec![board.clone(), [&board[..3], &[3, 4]].concat()];
+        }
+        let text: String = run_twice.to_pokerstars();
+        assert!(text.contains("Run 1 Board [2d 3s 2c 6s 8c]\nRun 2 Board [2d 3s 2c 4s 5s]\n"));
+        assert!(!text.contains("\nBoard"));
+    }
+
+    fn record_hands(name: &str, seats: Vec<(&str, u32, Vec<Play>)>, game: &BoardGame) {
+        // hands played by the engine are written out and read back to the same events
+        let mut zmq_conn = coms_tests::test_server(name);
+        let buffer = SharedBuffer::default();
+        zmq_conn.write_hand_history(HandHistoryWriter::new(Box::new(buffer.clone()), "table_1"));
+        let table = coms_tests::spawn_table(&zmq_conn, vec![], vec![]);
+        zmq_conn.wait_for_clients(&[TABLE_IDENTITY.to_string()]).unwrap();
+
+        let mut dealer = Dealer::new();
+        let mut players: Vec<ScriptedPlayer> = vec![];
+        for (name, chips, plays) in seats {
+            let mut player: ScriptedPlayer = ScriptedPlayer::new(chips, plays);
+            player.name = name.to_string();
+            players.push(player);
+        }
+        for _ in 0..3 {
+            play_board_game(&mut dealer, &mut players, [5, 10], 1, game, Some(&zmq_conn));
+        }
+        zmq_conn.send_recv_message(Message::EndOfGameMessage, "");
+        table.join().unwrap();
+
+        let text: String = buffer.text();
+        let hands: Vec<HandHistory> = parse_hand_histories(&text).unwrap();
+        assert_eq!(hands.len(), 3);
+        // players are notified of everything but the hand start, deals and board cards
+        let notified: Vec<Message> = players[0].notifications.clone();
+        let events: Vec<Message> = hands.iter().flat_map(|hand| hand.events.clone())
+            .filter(|msg| matches!(msg, Message::ActionMessage { .. } | Message::EndOfRoundMessage { .. }))
+            .collect();
+        assert_eq!(events, notified);
+        for (idx, hand) in hands.iter().enumerate() {
+            assert_eq!(hand.hand_id, idx as u64 + 1);
+            assert_eq!(hand.table, "table_1");
+            assert_eq!(HandHistory::from_pokerstars(&hand.to_pokerstars()).unwrap(), *hand);
+        }
+    }
+
+    #[test]
+    pub fn test_record_holdem_nl() {
+        let seats = vec![("alice", 1000, vec![Play::Bet(100)]), ("bob", 400, vec![Play::Fold]), ("carol", 1000, vec![Play::Bet(0), Play::Bet(50)])];
+        let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(), posting: Posting::default(), all_in: AllIn::RunOnce };
+        record_hands("test_record_holdem_nl", seats, &game);
+    }
+
+    #[test]
+    pub fn test_record_straddles() {
+        // straddles, big blind antes and dead blinds are written as posts of their own
+        let seats = vec![("alice", 1000, vec![Play::Bet(100)]), ("bob", 400, vec![Play::Fold]), ("carol", 1000, vec![]), ("dave", 1000, vec![Play::Bet(0), Play::Bet(50)])];
+        let posting = Posting { straddle: Straddle::Utg, ante_type: AnteType::BigBlind, missed_blinds: vec![3] };
+        let game = BoardGame { hole_cards: 2, deck: DeckType::Standard, betting: Betting::NoLimit, evaluation: HandEvaluation::Holdem, button_blind: false, rake: Rake::default(), posting, all_in: AllIn::RunOnce };
+        record_hands("test_record_straddles", seats, &game);
+
+        let text: String = HandHistory { hand_id: 1, table: "table_1".to_string(), timestamp: 0, events: vec![
+            Message::HandStartMessage { players: vec!["a".to_string(), "b".to_string(), "c".to_string(), "d".to_string()], stacks: vec![100; 4], blinds: [5, 10], ante: 10 },
+            Message::ActionMessage { player: 1, action: Action::POST, amount: 10, all_in: false, current_pot: 10, turn: Turn::HOLE, blind: Some(BlindType::Ante) },
+            Message::ActionMessage { player: 2, action: Action::POST, amount: 20, all_in: false, current_pot: 30, turn: Turn::HOLE, blind: Some(BlindType::Straddle) },
+            Message::ActionMessage { player: 3, action: Action::POST, amount: 5, all_in: false, current_pot: 35, turn: Turn::HOLE, blind: Some(BlindType::Dead) },
+        ] }.to_pokerstars();
+        assert!(text.contains("b: posts the ante 10\nc: posts straddle 20\nd: posts dead blind 5\n"));
+    }
+}
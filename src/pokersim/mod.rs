@@ -5,3 +5,14 @@ pub mod games;
 pub mod hand_eval;
 pub mod coms;
 pub mod driver;
+pub mod pots;
+pub mod observer;
+pub mod history;
+pub mod hand_log;
+pub mod recorder;
+pub mod acpc;
+pub mod rules;
+pub mod tournament;
+pub mod mtt;
+pub mod cash;
+pub mod equity;
@@ -0,0 +1,394 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::thread;
+use super::coms::{Message, Standing};
+use super::dealer::Dealer;
+use super::player::HoldemPlayer;
+use super::games::{HandResult, TableEvents};
+use super::recorder::EventBuffer;
+use super::rules::{GameRules, play_hand_with_missed_blinds};
+use super::tournament::{Tournament, TournamentConfig, TournamentSummary};
+
+
+// Multi-table tournaments: players are spread over tables of table_size seats and every table plays
+// a hand each round. After a round busted players are knocked out, tables are broken as soon as the
+// other tables have room for their players, and players are moved until the tables differ by at most one player.
+// Blind levels count rounds
+
+pub struct Table<T> {
+    // tables count from 1 and keep their number when other tables break
+    pub id: usize,
+    // seats in clockwise order, None is an empty seat
+    pub seats: Vec<Option<T>>,
+    pub button: usize,
+    dealer: Dealer,
+}
+
+impl<T: HoldemPlayer> Table<T> {
+
+    pub fn new(id: usize, n_seats: usize, dealer: Dealer) -> Table<T> {
+        return Table { id, seats: (0..n_seats).map(|_| None).collect(), button: 0, dealer }
+    }
+
+    pub fn n_players(&self) -> usize {
+        return self.seats.iter().filter(|seat| seat.is_some()).count();
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = &T> {
+        return self.seats.iter().flatten();
+    }
+
+    fn hand_order(&self) -> Vec<usize> {
+        // occupied seats starting after the button: small blind, big blind, ..., button
+        let n_seats: usize = self.seats.len();
+        return (1..=n_seats).map(|offset| (self.button + offset) % n_seats).filter(|seat| self.seats[*seat].is_some()).collect();
+    }
+
+    pub fn play_hand(&mut self, rules: &GameRules, events: Option<&dyn TableEvents>) -> HandResult {
+        return self.play_hand_with_missed_blinds(rules, &[], events);
+    }
+
+    pub fn play_hand_with_missed_blinds(&mut self, rules: &GameRules, missed_seats: &[usize], events: Option<&dyn TableEvents>) -> HandResult {
+        // the players in missed_seats post the blinds they missed. The result is by seat, empty seats win nothing
+        let order: Vec<usize> = self.hand_order();
+        let mut players: Vec<T> = order.iter().map(|seat| self.seats[*seat].take().unwrap()).collect();
+        let missed: Vec<usize> = missed_seats.iter().filter_map(|seat| order.iter().position(|other| other == seat)).collect();
+        let result: HandResult = play_hand_with_missed_blinds(&mut self.dealer, &mut players, rules, &missed, events);
+        for (seat, player) in order.iter().zip(players) {
+            self.seats[*seat] = Some(player);
+        }
+        // the small blind gets the button
+        self.button = order[0];
+
+        let mut winnings: Vec<i32> = vec![0; self.seats.len()];
+        let mut all_in_ev: Option<Vec<f64>> = result.all_in_ev.as_ref().map(|_| vec![0.0; self.seats.len()]);
+        for (idx, seat) in order.iter().enumerate() {
+            winnings[*seat] = result.winnings[idx];
+            if let (Some(seat_ev), Some(ev)) = (all_in_ev.as_mut(), result.all_in_ev.as_ref()) {
+                seat_ev[*seat] = ev[idx];
+            }
+        }
+        return HandResult { winnings, all_in_ev };
+    }
+
+    pub fn hands_until_big_blind(&self, seat: usize) -> usize {
+        // hands a player sitting down in this empty seat plays before posting the big blind
+        let n_seats: usize = self.seats.len();
+        let order: Vec<usize> = (1..=n_seats).map(|offset| (self.button + offset) % n_seats)
+            .filter(|other| *other == seat || self.seats[*other].is_some())
+            .collect();
+        let idx: usize = order.iter().position(|other| *other == seat).unwrap();
+        return (idx + order.len() - 1) % order.len();
+    }
+
+    fn worst_seat(&self) -> usize {
+        // moved players take the worst position, the empty seat that posts the big blind soonest
+        let empty = (0..self.seats.len()).filter(|seat| self.seats[*seat].is_none());
+        return empty.min_by_key(|seat| (self.hands_until_big_blind(*seat), *seat)).unwrap();
+    }
+
+    fn next_big_blind(&self) -> usize {
+        // players are moved away from the big blind, so nobody skips it
+        let order: Vec<usize> = self.hand_order();
+        return order[1.min(order.len() - 1)];
+    }
+}
+
+pub struct RoundResult {
+    pub eliminated: Vec<Standing>,
+    // TableMoveMessages of the players moved after the round
+    pub moves: Vec<Message>,
+    // LevelMessage when the next round starts a new level
+    pub level: Option<Message>,
+}
+
+pub struct MultiTableTournament<T> {
+    pub tables: Vec<Table<T>>,
+    table_size: usize,
+    rules: GameRules,
+    tournament: Tournament,
+}
+
+impl<T: HoldemPlayer> MultiTableTournament<T> {
+
+    pub fn new(players: Vec<T>, table_size: usize, rules: &GameRules, config: &TournamentConfig, dealer: &mut Dealer) -> MultiTableTournament<T> {
+        // players are dealt around the tables in order, the last player at each table starts on the button
+        let n_players: usize = players.len();
+        let n_tables: usize = n_players.div_ceil(table_size);
+        let mut tables: Vec<Table<T>> = (0..n_tables).map(|idx| Table::new(idx + 1, table_size, dealer.fork())).collect();
+        for (idx, player) in players.into_iter().enumerate() {
+            tables[idx % n_tables].seats[idx / n_tables] = Some(player);
+        }
+        for table in tables.iter_mut() {
+            table.button = table.seats.iter().rposition(|seat| seat.is_some()).unwrap_or(0);
+        }
+        return MultiTableTournament { tables, table_size, rules: rules.clone(), tournament: Tournament::new(config, rules, n_players) }
+    }
+
+    pub fn n_players(&self) -> usize {
+        return self.tables.iter().map(|table| table.n_players()).sum();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.n_players() < 2;
+    }
+
+    pub fn level_message(&self) -> Message {
+        return self.tournament.level_message();
+    }
+
+    pub fn broadcast(&mut self, msg: &Message) {
+        // notifies every player still in the tournament
+        for table in self.tables.iter_mut() {
+            for player in table.seats.iter_mut().flatten() {
+                player.notify(msg);
+            }
+        }
+    }
+
+    fn stacks(&self) -> HashMap<String, u32> {
+        return self.tables.iter().flat_map(|table| table.players()).map(|player| (player.name(), player.chips())).collect();
+    }
+
+    pub fn play_round(&mut self, events: Option<&dyn TableEvents>) -> RoundResult {
+        // tables play one after another, players sharing a connection can only be asked one at a time
+        let rules: GameRules = self.tournament.rules(&self.rules);
+        let stacks: HashMap<String, u32> = self.stacks();
+        for table in self.tables.iter_mut().filter(|table| table.n_players() > 1) {
+            table.play_hand(&rules, events);
+        }
+        return self.end_round(&stacks);
+    }
+
+    fn end_round(&mut self, stacks: &HashMap<String, u32>) -> RoundResult {
+        let new_level: bool = self.tournament.end_hand();
+        // players who lost every chip are out, a short stack posts its forced bets all in.
+        // Busted players are ranked by their stacks at the start of the round
+        let mut busted: Vec<(String, u32)> = vec![];
+        for table in self.tables.iter_mut() {
+            for seat in table.seats.iter_mut() {
+                if seat.as_ref().is_some_and(|player| player.chips() == 0) {
+                    let name: String = seat.take().unwrap().name();
+                    busted.push((name.clone(), stacks[&name]));
+                }
+            }
+        }
+        let eliminated: Vec<Standing> = self.tournament.eliminate(&busted, self.n_players());
+        let moves: Vec<Message> = self.balance();
+
+        let level: Option<Message> = match new_level && !self.is_finished() {
+            true => Some(self.tournament.level_message()),
+            false => None,
+        };
+        if let Some(msg) = &level {
+            self.broadcast(msg);
+        }
+        return RoundResult { eliminated, moves, level };
+    }
+
+    fn balance(&mut self) -> Vec<Message> {
+        let mut moves: Vec<Message> = vec![];
+        self.tables.retain(|table| table.n_players() > 0);
+        loop {
+            let n_tables: usize = self.tables.len();
+            if n_tables < 2 {return moves}
+
+            if self.n_players() <= (n_tables - 1) * self.table_size {
+                // the table with the fewest players breaks, the highest numbered one on a tie.
+                // Its players take the worst seats at the tables with the fewest players
+                let idx: usize = (0..n_tables).min_by_key(|idx| (self.tables[*idx].n_players(), Reverse(self.tables[*idx].id))).unwrap();
+                let mut broken: Table<T> = self.tables.remove(idx);
+                for seat in broken.hand_order() {
+                    let player: T = broken.seats[seat].take().unwrap();
+                    let to: usize = (0..self.tables.len()).min_by_key(|idx| (self.tables[*idx].n_players(), self.tables[*idx].id)).unwrap();
+                    moves.push(self.seat_player(player, broken.id, to));
+                }
+                continue;
+            }
+
+            let smallest: usize = (0..n_tables).min_by_key(|idx| (self.tables[*idx].n_players(), self.tables[*idx].id)).unwrap();
+            let biggest: usize = (0..n_tables).max_by_key(|idx| (self.tables[*idx].n_players(), Reverse(self.tables[*idx].id))).unwrap();
+            if self.tables[biggest].n_players() - self.tables[smallest].n_players() <= 1 {return moves}
+            let seat: usize = self.tables[biggest].next_big_blind();
+            let player: T = self.tables[biggest].seats[seat].take().unwrap();
+            let from_table: usize = self.tables[biggest].id;
+            moves.push(self.seat_player(player, from_table, smallest));
+        }
+    }
+
+    fn seat_player(&mut self, mut player: T, from_table: usize, to: usize) -> Message {
+        let table: &mut Table<T> = &mut self.tables[to];
+        let seat: usize = table.worst_seat();
+        let msg = Message::TableMoveMessage { player: player.name(), from_table, to_table: table.id, seat };
+        player.notify(&msg);
+        table.seats[seat] = Some(player);
+        return msg;
+    }
+
+    pub fn winner(&self) -> Option<String> {
+        return self.tables.iter().flat_map(|table| table.players()).next().map(|player| player.name());
+    }
+
+    pub fn finish(&mut self) -> TournamentSummary {
+        let winner: Option<String> = self.winner();
+        return self.tournament.finish(winner, &self.rules.variant);
+    }
+}
+
+impl<T: HoldemPlayer + Send> MultiTableTournament<T> {
+
+    pub fn play_round_concurrently(&mut self, events: Option<&dyn TableEvents>) -> RoundResult {
+        // every table plays its hand on its own thread, for players that don't share a connection.
+        // The tables' events are passed on table by table once every hand is done
+        let rules: GameRules = self.tournament.rules(&self.rules);
+        let stacks: HashMap<String, u32> = self.stacks();
+        let buffers: Vec<EventBuffer> = thread::scope(|scope| {
+            let hands: Vec<_> = self.tables.iter_mut().filter(|table| table.n_players() > 1).map(|table| {
+                let rules: &GameRules = &rules;
+                scope.spawn(move || {
+                    let buffer = EventBuffer::new();
+                    table.play_hand(rules, Some(&buffer));
+                    buffer
+                })
+            }).collect();
+            hands.into_iter().map(|hand| hand.join().unwrap()).collect()
+        });
+        if let Some(events) = events {
+            for buffer in buffers {
+                buffer.replay(events);
+            }
+        }
+        return self.end_round(&stacks);
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pokersim::games::HOLDEM_NL;
+    use crate::pokersim::player::ScriptedPlayer;
+    use crate::pokersim::history::{parse_hand_histories, HandHistoryWriter};
+    use crate::pokersim::recorder::HandRecorder;
+    use crate::pokersim::recorder::tests::SharedBuffer;
+    use crate::pokersim::tournament::BlindLevel;
+
+    fn test_players(n_players: usize, chips: u32) -> Vec<ScriptedPlayer> {
+        return (0..n_players).map(|idx| ScriptedPlayer { name: format!("player_{}", idx), ..ScriptedPlayer::new(chips, vec![]) }).collect();
+    }
+
+    fn test_tournament(n_players: usize, table_size: usize) -> MultiTableTournament<ScriptedPlayer> {
+        let rules = GameRules::new(HOLDEM_NL).unwrap();
+        return MultiTableTournament::new(test_players(n_players, 100), table_size, &rules, &TournamentConfig::default(), &mut Dealer::with_seed(4));
+    }
+
+    fn table_sizes<T: HoldemPlayer>(mtt: &MultiTableTournament<T>) -> Vec<(usize, usize)> {
+        return mtt.tables.iter().map(|table| (table.id, table.n_players())).collect();
+    }
+
+    fn bust(mtt: &mut MultiTableTournament<ScriptedPlayer>, names: &[&str]) -> RoundResult {
+        let stacks: HashMap<String, u32> = mtt.stacks();
+        for table in mtt.tables.iter_mut() {
+            for player in table.seats.iter_mut().flatten() {
+                if names.contains(&player.name.as_str()) {player.chips = 0}
+            }
+        }
+        return mtt.end_round(&stacks);
+    }
+
+    #[test]
+    pub fn test_seating() {
+        let mtt = test_tournament(10, 4);
+        assert_eq!(table_sizes(&mtt), vec![(1, 4), (2, 3), (3, 3)]);
+        let names: Vec<String> = mtt.tables[1].players().map(|player| player.name.clone()).collect();
+        assert_eq!(names, vec!["player_1", "player_4", "player_7"]);
+        // the first seat posts the small blind of the first hand
+        assert_eq!(mtt.tables[1].button, 2);
+        assert_eq!(mtt.tables[1].hand_order(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    pub fn test_worst_seat() {
+        let mut mtt = test_tournament(6, 6);
+        let table: &mut Table<ScriptedPlayer> = &mut mtt.tables[0];
+        // players in seats 0, 2, 3 and 5 with the button on seat 5
+        table.seats[1] = None;
+        table.seats[4] = None;
+        // seat 1 posts the big blind next hand, seat 4 after two hands
+        assert_eq!(table.hands_until_big_blind(1), 0);
+        assert_eq!(table.hands_until_big_blind(4), 2);
+        assert_eq!(table.worst_seat(), 1);
+        assert_eq!(table.next_big_blind(), 2);
+        table.button = 0;
+        // seat 1 is between the button and the small blind, seat 4 posts the big blind after one hand
+        assert_eq!(table.worst_seat(), 4);
+    }
+
+    #[test]
+    pub fn test_break_and_balance() {
+        let mut mtt = test_tournament(12, 4);
+        assert_eq!(table_sizes(&mtt), vec![(1, 4), (2, 4), (3, 4)]);
+
+        // table 1 is two players short, table 2 sends its next big blind
+        let result: RoundResult = bust(&mut mtt, &["player_0", "player_3"]);
+        assert_eq!(result.eliminated.iter().map(|standing| standing.finishing_position).collect::<Vec<_>>(), vec![11, 11]);
+        assert_eq!(result.moves, vec![Message::TableMoveMessage { player: "player_4".to_string(), from_table: 2, to_table: 1, seat: 0 }]);
+        assert_eq!(table_sizes(&mtt), vec![(1, 3), (2, 3), (3, 4)]);
+        let moved: &ScriptedPlayer = mtt.tables[0].players().find(|player| player.name == "player_4").unwrap();
+        assert!(moved.notifications.contains(&result.moves[0]));
+
+        // table 3 is down to two players who fit at the other tables
+        let result: RoundResult = bust(&mut mtt, &["player_2", "player_5"]);
+        assert_eq!(result.moves.len(), 2);
+        assert_eq!(table_sizes(&mtt), vec![(1, 4), (2, 4)]);
+
+        // the final table, table 2 breaks on a tie
+        let result: RoundResult = bust(&mut mtt, &["player_6", "player_9", "player_7", "player_10"]);
+        assert_eq!(result.moves.len(), 2);
+        assert_eq!(table_sizes(&mtt), vec![(1, 4)]);
+    }
+
+    #[test]
+    pub fn test_short_stack_stays() {
+        // a player who can't cover the blinds keeps playing, only an empty stack is knocked out
+        let mut mtt = test_tournament(4, 4);
+        mtt.tables[0].seats[0].as_mut().unwrap().chips = 1;
+        let result: RoundResult = bust(&mut mtt, &[]);
+        assert!(result.eliminated.is_empty());
+        assert_eq!(mtt.stacks()["player_0"], 1);
+        assert_eq!(mtt.stacks().values().sum::<u32>(), 301);
+    }
+
+    #[test]
+    pub fn test_play_concurrently() {
+        let rules = GameRules::new(HOLDEM_NL).unwrap();
+        let config = TournamentConfig {
+            levels: vec![BlindLevel { blinds: [1, 2], ante: 0, hands: 3 }, BlindLevel { blinds: [25, 50], ante: 10, hands: 0 }],
+            buy_in: 10,
+            payouts: vec![50, 30, 20],
+            ..Default::default()
+        };
+        let mut mtt = MultiTableTournament::new(test_players(9, 100), 4, &rules, &config, &mut Dealer::with_seed(6));
+        // every table's hands are recorded, one hand after the other
+        let history = SharedBuffer::default();
+        let mut recorder = HandRecorder::new();
+        recorder.write_hand_history(HandHistoryWriter::new(Box::new(history.clone()), "mtt"));
+        let mut n_rounds: usize = 0;
+        let mut n_hands: usize = 0;
+        while !mtt.is_finished() && n_rounds < 1000 {
+            n_hands += mtt.tables.iter().filter(|table| table.n_players() > 1).count();
+            let result: RoundResult = mtt.play_round_concurrently(Some(&recorder));
+            if n_rounds == 2 {
+                assert_eq!(result.level, Some(Message::LevelMessage { level: 2, blinds: [25, 50], ante: 10, hands: 0 }));
+            }
+            // chips only leave the tournament with the players who lost them all
+            assert_eq!(mtt.stacks().values().sum::<u32>(), 900);
+            n_rounds += 1;
+        }
+        let summary: TournamentSummary = mtt.finish();
+        assert_eq!(summary.standings.len(), 9);
+        assert_eq!(summary.standings[0].finishing_position, 1);
+        assert_eq!(summary.standings.iter().map(|standing| standing.payout).sum::<u32>(), 90);
+        assert_eq!(parse_hand_histories(&history.text()).unwrap().len(), n_hands);
+    }
+}
@@ -0,0 +1,201 @@
+use super::coms::*;
+use zmq;
+
+
+// Observers connect a SUB socket to the publisher. Every event is sent as [topic, message]
+// with topic "<table>/<message_type>", so observers subscribe to a table with the "<table>/" prefix.
+
+#[derive(Clone, Debug)]
+pub struct ObserverConfig {
+    pub endpoint: Endpoint,
+    pub table: String,
+    // publish every player's hole cards when they are dealt
+    pub god_view: bool,
+}
+
+pub struct ZmqPublisher {
+    socket: zmq::Socket,
+    endpoint: String,
+    table: String,
+    god_view: bool,
+}
+
+impl ZmqPublisher {
+
+    pub fn bind(ctx: &zmq::Context, config: &ObserverConfig) -> Result<ZmqPublisher, ComsError> {
+        let socket = ctx.socket(zmq::PUB)?;
+        // slow observers drop events instead of holding up the game
+        socket.set_sndhwm(10_000)?;
+        socket.set_linger(0)?;
+        socket.bind(&config.endpoint.to_string())?;
+        let endpoint: String = socket.get_last_endpoint()?.unwrap_or_default();
+        return Ok(ZmqPublisher { socket, endpoint, table: config.table.clone(), god_view: config.god_view })
+    }
+
+    pub fn endpoint(&self) -> &str {
+        return &self.endpoint;
+    }
+
+    pub fn topic(&self, msg: &Message) -> String {
+        return format!("{}/{}", self.table, msg.message_type());
+    }
+
+    pub fn publish(&self, msg: &Message) {
+        // hole cards are only published in god view
+        let payload: Vec<u8> = match msg {
+            Message::DealMessage { player, .. } if !self.god_view => {
+                Encoding::JSON.encode(&Message::DealMessage { player: *player, cards: None })
+            },
+            _ => Encoding::JSON.encode(msg),
+        };
+        // never block the game on observers, events are dropped when the send queue is full
+        let _ = self.socket.send_multipart([self.topic(msg).as_bytes(), &payload], zmq::DONTWAIT);
+    }
+}
+
+pub struct ZmqSubscriber {
+    socket: zmq::Socket,
+    // (table, message_type) pairs, ZMQ only filters on the topic prefix so events are matched again here
+    subscriptions: Vec<(Option<String>, Option<String>)>,
+}
+
+impl ZmqSubscriber {
+
+    pub fn connect(ctx: &zmq::Context, endpoint: &str) -> ZmqSubscriber {
+        let socket = ctx.socket(zmq::SUB).unwrap();
+        socket.set_linger(0).unwrap();
+        socket.connect(endpoint).unwrap();
+        return ZmqSubscriber { socket, subscriptions: vec![] }
+    }
+
+    pub fn subscribe(&mut self, table: Option<&str>, message_type: Option<&str>) {
+        // subscribe to a table, an event type, both or everything
+        let prefix: String = match (table, message_type) {
+            (Some(table), Some(message_type)) => format!("{}/{}", table, message_type),
+            (Some(table), None) => format!("{}/", table),
+            (None, _) => "".to_string(),
+        };
+        self.socket.set_subscribe(prefix.as_bytes()).unwrap();
+        self.subscriptions.push((table.map(str::to_string), message_type.map(str::to_string)));
+    }
+
+    fn is_subscribed(&self, table: &str, message_type: &str) -> bool {
+        return self.subscriptions.iter().any(|(sub_table, sub_type)| {
+            sub_table.as_ref().is_none_or(|t| t == table) && sub_type.as_ref().is_none_or(|t| t == message_type)
+        });
+    }
+
+    pub fn recv_event(&self) -> (String, Message) {
+        // returns the table and message of the next event
+        loop {
+            let mut frames: Vec<Vec<u8>> = self.socket.recv_multipart(0).unwrap();
+            let payload: Vec<u8> = frames.pop().unwrap();
+            let topic: String = String::from_utf8(frames.swap_remove(0)).unwrap();
+            let (table, message_type) = topic.rsplit_once('/').unwrap();
+            if self.is_subscribed(table, message_type) {
+                return (table.to_string(), Encoding::JSON.decode(&payload).unwrap());
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pokersim::coms::tests as coms_tests;
+    use crate::pokersim::driver::run_game;
+    use crate::pokersim::player::DecisionClock;
+
+    fn observer_config(name: &str, god_view: bool) -> ObserverConfig {
+        return ObserverConfig { endpoint: Endpoint::Inproc(name.to_string()), table: "table_1".to_string(), god_view }
+    }
+
+    fn probe() -> Message {
+        // an event games never publish that every subscription in these tests matches
+        return Message::BoardMessage { turn: Turn::HOLE, cards: vec![] };
+    }
+
+    fn wait_for_subscription(subscriber: &ZmqSubscriber, publish: impl Fn(&Message)) {
+        // PUB drops events until the subscription reaches it, so probes are published until one arrives
+        loop {
+            publish(&probe());
+            if subscriber.socket.poll(zmq::POLLIN, 10).unwrap() > 0 {return}
+        }
+    }
+
+    fn recv_event(subscriber: &ZmqSubscriber) -> (String, Message) {
+        // the next event after the probes, events arrive in the order they were published
+        loop {
+            let event: (String, Message) = subscriber.recv_event();
+            if event.1 != probe() {return event}
+        }
+    }
+
+    #[test]
+    pub fn test_observe_game() {
+        let mut zmq_conn = coms_tests::test_server("test_observe_game");
+        zmq_conn.bind_publisher(&observer_config("test_observe_game_events", false)).unwrap();
+        let mut subscriber = ZmqSubscriber::connect(&zmq_conn.context(), zmq_conn.publisher_endpoint().unwrap());
+        subscriber.subscribe(Some("table_1"), None);
+        wait_for_subscription(&subscriber, |msg| zmq_conn.publish(msg));
+
+        let names: Vec<String> = vec!["player1".to_string(), "player2".to_string()];
+        let table = coms_tests::spawn_table(&zmq_conn, names.clone(), vec![30, 30]);
+        let bots: Vec<_> = names.iter().map(|name| coms_tests::spawn_calling_bot(&zmq_conn, name)).collect();
+        run_game(&zmq_conn, &DecisionClock::default());
+
+        let mut events: Vec<Message> = vec![];
+        loop {
+            let (table, msg) = recv_event(&subscriber);
+            assert_eq!(table, "table_1");
+            if msg == Message::EndOfGameMessage {break}
+            events.push(msg);
+        }
+        let types: Vec<&str> = events.iter().map(|msg| msg.message_type()).collect();
+        for message_type in ["HandStartMessage", "DealMessage", "ActionMessage", "BoardMessage", "EndOfRoundMessage", "EliminationMessage"] {
+            assert!(types.contains(&message_type), "no {} published", message_type);
+        }
+        // hole cards are hidden without god view
+        for msg in events.iter() {
+            if let Message::DealMessage { cards, .. } = msg {
+                assert_eq!(*cards, None);
+            }
+        }
+        assert!(events.contains(&Message::EliminationMessage { player: "player1".to_string(), finishing_position: 1 })
+            || events.contains(&Message::EliminationMessage { player: "player2".to_string(), finishing_position: 1 }));
+
+        for bot in bots {
+            bot.join().unwrap();
+        }
+        table.join().unwrap();
+    }
+
+    #[test]
+    pub fn test_god_view() {
+        let ctx = zmq::Context::new();
+        let publisher = ZmqPublisher::bind(&ctx, &observer_config("test_god_view", true)).unwrap();
+        let mut subscriber = ZmqSubscriber::connect(&ctx, publisher.endpoint());
+        subscriber.subscribe(None, None);
+        wait_for_subscription(&subscriber, |msg| publisher.publish(msg));
+
+        let deal = Message::DealMessage { player: 1, cards: Some(vec![3, 40]) };
+        publisher.publish(&deal);
+        assert_eq!(recv_event(&subscriber), ("table_1".to_string(), deal));
+    }
+
+    #[test]
+    pub fn test_subscribe_by_event_type() {
+        let ctx = zmq::Context::new();
+        let publisher = ZmqPublisher::bind(&ctx, &observer_config("test_subscribe_by_event_type", false)).unwrap();
+        let mut subscriber = ZmqSubscriber::connect(&ctx, publisher.endpoint());
+        subscriber.subscribe(None, Some("BoardMessage"));
+        wait_for_subscription(&subscriber, |msg| publisher.publish(msg));
+
+        let board = Message::BoardMessage { turn: Turn::FLOP, cards: vec![1, 2, 3] };
+        publisher.publish(&Message::DealMessage { player: 0, cards: Some(vec![3, 40]) });
+        publisher.publish(&board);
+        assert_eq!(publisher.topic(&board), "table_1/BoardMessage");
+        assert_eq!(recv_event(&subscriber), ("table_1".to_string(), board));
+    }
+}
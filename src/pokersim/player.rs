@@ -1,16 +1,54 @@
+use serde::{Serialize, Deserialize};
 use super::coms::*;
+use super::acpc::AcpcConnection;
+use std::collections::VecDeque;
 use std::fmt;
+use std::time::{Duration, Instant};
+
+
+#[derive(Clone, Debug, Default)]
+pub struct DecisionClock {
+    // time a player has for each decision, None waits forever
+    pub decision_timeout: Option<Duration>,
+    // extra time a player can draw from across a game once a decision runs over
+    pub time_bank: Duration,
+    // consecutive timeouts before a player is sat out, None never sits a player out
+    pub max_timeouts: Option<u32>,
+}
+
+// where players read the time to measure their decisions, tests use a fake to make time banks deterministic
+pub trait TimeSource {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemTime;
+
+impl TimeSource for SystemTime {
+    fn now(&self) -> Instant {
+        return Instant::now();
+    }
+}
 
 
 #[derive(Default)]
 pub struct Player<'a> {
     name: String,
     chips: u32,
-    hand: Option<[u8; 2]>,
+    hand: Option<Vec<u8>>,
     // position counts from 1, contains [player_position, n_players]
     position: Option<[usize; 2]>,
     pot_contrib: u32,
     zmq_reply_socket: Option<&'a ZmqSocketReply>,
+    // ACPC agents play over their own TCP connection instead of zmq
+    acpc: Option<AcpcConnection>,
+    clock: DecisionClock,
+    // the system time when None
+    time_source: Option<Box<dyn TimeSource>>,
+    time_bank: Duration,
+    timeouts: u32,
+    consecutive_timeouts: u32,
+    sitting_out: bool,
 }
 
 impl fmt::Debug for Player<'_> {    
@@ -21,6 +59,9 @@ impl fmt::Debug for Player<'_> {
          .field("hand", &self.hand)
          .field("position", &self.position)
          .field("pot_contribution", &self.pot_contrib)
+         .field("time_bank", &self.time_bank)
+         .field("timeouts", &self.timeouts)
+         .field("sitting_out", &self.sitting_out)
          .finish()
     }
 
@@ -28,18 +69,121 @@ impl fmt::Debug for Player<'_> {
 
 impl<'a> Player<'a> {
     pub fn new(name: String, chips: u32, zmq_reply_socket: Option<&'a ZmqSocketReply>) -> Player<'a> {
-        Self{ name, chips, hand: None, position: None, pot_contrib: 0, zmq_reply_socket: zmq_reply_socket }
+        Self{ name, chips, zmq_reply_socket, ..Default::default() }
+    }
+
+    pub fn new_acpc(name: String, chips: u32, acpc: AcpcConnection) -> Player<'a> {
+        Self{ name, chips, acpc: Some(acpc), ..Default::default() }
+    }
+
+    pub fn set_clock(&mut self, clock: DecisionClock) {
+        self.time_bank = clock.time_bank;
+        self.clock = clock;
+    }
+
+    pub fn set_time_source(&mut self, time_source: Box<dyn TimeSource>) {
+        self.time_source = Some(time_source);
+    }
+
+    fn now(&self) -> Instant {
+        match &self.time_source {
+            Some(time_source) => return time_source.now(),
+            None => return SystemTime.now(),
+        }
+    }
+
+    pub fn time_bank(&self) -> Duration {
+        return self.time_bank;
+    }
+
+    pub fn timeouts(&self) -> u32 {
+        return self.timeouts;
+    }
+
+    pub fn is_sitting_out(&self) -> bool {
+        return self.sitting_out;
+    }
+
+    pub fn sit_out(&mut self) {
+        self.sitting_out = true;
+    }
+
+    pub fn sit_in(&mut self) {
+        self.sitting_out = false;
+        self.consecutive_timeouts = 0;
+    }
+
+    fn decision_time(&self) -> Option<Duration> {
+        return self.clock.decision_timeout.map(|t| t + self.time_bank);
+    }
+
+    fn record_decision(&mut self, elapsed: Duration) {
+        // time over the per decision timeout is taken out of the time bank
+        self.consecutive_timeouts = 0;
+        if let Some(timeout) = self.clock.decision_timeout {
+            self.time_bank = self.time_bank.saturating_sub(elapsed.saturating_sub(timeout));
+        }
+    }
+
+    fn record_timeout(&mut self) {
+        self.time_bank = Duration::ZERO;
+        self.timeouts += 1;
+        self.consecutive_timeouts += 1;
+        match self.clock.max_timeouts {
+            Some(max) if self.consecutive_timeouts >= max => self.sitting_out = true,
+            _ => (),
+        }
+    }
+
+    fn record_bad_reply(&mut self, reason: &str) {
+        // a reply that can't be decoded or isn't the one asked for counts like a timeout
+        println!("Player {} sent a bad reply: {}", self.name, reason);
+        self.record_timeout();
+    }
+
+    fn play_acpc(&mut self, min_bet: u32, shared_cards: &[u8]) -> Play {
+        let timeout: Option<Duration> = self.decision_time();
+        let start: Instant = self.now();
+        let acpc: &mut AcpcConnection = self.acpc.as_mut().unwrap();
+        match acpc.play(shared_cards, min_bet, timeout) {
+            Ok(play) => {
+                self.record_decision(self.now() - start);
+                return play;
+            },
+            Err(ComsError::Timeout(_)) => {
+                self.record_timeout();
+                return Play::default_action(min_bet);
+            },
+            Err(_) => {
+                self.record_timeout();
+                self.sitting_out = true;
+                return Play::default_action(min_bet);
+            },
+        }
     }
 
     pub fn msg_header(&self) -> String {
         return self.name.to_string();
     }
+
+    pub fn chips(&self) -> u32 {
+        return self.chips;
+    }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum BlindType {
     Big,
     Little,
     Ante,
+    // forced bet of the player with the worst up card in stud games
+    BringIn,
+    // the house's charge for the hand, it doesn't go in the pot
+    TimeCharge,
+    // two big blinds posted before the cards are dealt, the straddler acts last before the flop
+    Straddle,
+    // a missed small blind posted by a returning player, it doesn't count toward their bet
+    Dead,
 }
 
 pub struct Blind {
@@ -52,29 +196,69 @@ pub enum Play {
     Fold,
 }
 
+impl Play {
+    pub fn default_action(min_bet: u32) -> Play {
+        // played for players that time out or sit out: check if possible, otherwise fold
+        match min_bet {
+            0 => Play::Bet(0),
+            _ => Play::Fold,
+        }
+    }
+}
+
+// a player's choice between cash game hands
+#[derive(Clone, Debug, PartialEq)]
+pub struct CashRequest {
+    pub add_chips: u32,
+    pub sit_out: bool,
+}
+
 
 pub trait HoldemPlayer {
-    fn recieve_cards(&mut self, cards: [u8; 2]) -> ();
+    fn name(&self) -> String;
+    fn recieve_cards(&mut self, cards: Vec<u8>) -> ();
     // blind also handles antes
     fn blind(&mut self, blind: Blind) -> u32;
-    fn show(&self) -> [u8; 2];
+    fn show(&self) -> Vec<u8>;
     fn best_hand(&self, shared_cards: &Vec<u8>) -> [u8; 5];
-    fn play(&mut self, current_players: &Vec<usize>, current_position: usize, current_pot: u32, min_bet: u32, shared_cards: &Vec<u8>, turn: &Turn) -> Play;
+    // min_bet calls, max_bet is the largest bet the rules allow
+    #[allow(clippy::too_many_arguments)]
+    fn play(&mut self, current_players: &Vec<usize>, current_position: usize, current_pot: u32, min_bet: u32, min_raise: u32, max_bet: u32, shared_cards: &Vec<u8>, turn: &Turn) -> Play;
     fn bet(&mut self, shared_cards: &Vec<u8>, min_call: u32) -> u32;
+    // cards to throw away in draw games, an empty Vec stands pat
+    fn discard(&mut self, turn: &Turn) -> Vec<u8>;
+    // chips to add and whether to sit out between cash game hands
+    fn cash_request(&mut self, stack: u32, buy_in: [u32; 2], sitting_out: bool) -> CashRequest;
+    // rebuys and top ups between hands
+    fn add_chips(&mut self, amount: u32) -> ();
     fn fold(&mut self) -> ();
     fn assign_position(&mut self, player_position: usize, n_players: usize) -> ();
     fn end_round(&mut self, winnings: Option<u32>) -> ();
     fn pot_contribution(&self) -> u32;
+    fn chips(&self) -> u32;
+    // send a message that doesn't expect a reply
+    fn notify(&mut self, msg: &Message) -> ();
 }
 
 impl<'a> HoldemPlayer for Player<'a> {
-    fn recieve_cards(&mut self, cards: [u8; 2]) {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+    fn recieve_cards(&mut self, cards: Vec<u8>) {
+        if let Some(acpc) = self.acpc.as_mut() {
+            acpc.new_hand(&cards);
+        }
         self.hand = Some(cards);
     }
     fn blind(&mut self, blind: Blind) -> u32 {
-        if self.chips < blind.amount {panic!("Player {} has lost", self.name)}
-        self.chips -= blind.amount;
-        return blind.amount
+        // a short stack posts what it has
+        let amount: u32 = blind.amount.min(self.chips);
+        if let (Some(acpc), BlindType::Ante) = (self.acpc.as_mut(), &blind.btype) {
+            acpc.hand.ante = amount;
+        }
+        self.chips -= amount;
+        if !matches!(blind.btype, BlindType::TimeCharge) {self.pot_contrib += amount}
+        return amount
     }
     fn bet(&mut self, _shared_cards: &Vec<u8>, min_call: u32) -> u32 {
 
@@ -83,9 +267,9 @@ impl<'a> HoldemPlayer for Player<'a> {
         self.chips -= min_call;
         return min_call;
     }
-    fn show(&self) -> [u8; 2] {
-        match self.hand {
-            Some(hand) => return hand,
+    fn show(&self) -> Vec<u8> {
+        match &self.hand {
+            Some(hand) => return hand.clone(),
             None => panic!("No Cards"),
         }
     }
@@ -95,6 +279,8 @@ impl<'a> HoldemPlayer for Player<'a> {
         current_position: usize,
         current_pot: u32,
         min_bet: u32,
+        min_raise: u32,
+        max_bet: u32,
         shared_cards: &Vec<u8>,
         turn: &Turn,
     ) -> Play {
@@ -102,44 +288,163 @@ impl<'a> HoldemPlayer for Player<'a> {
         //      Investigate moving to stateless play call and maybe RPC?
         if self.hand.is_none() {panic!("Player {} can't play, player has no cards", self.name)}
 
-        let play: Message;
-        match &self.zmq_reply_socket {
-            Some(conn) => {
-                let msg = Message::TurnMessage { 
-                    current_players: current_players.clone(),
-                    current_position: current_position,
-                    current_pot: current_pot,
-                    min_bet: min_bet,
-                    max_bet: self.chips,
-                    shared_cards: shared_cards.clone(),
-                    turn: turn.clone()
-                };
-                play = conn.send_recv_message(msg, &self.msg_header());
+        if self.sitting_out {return Play::default_action(min_bet)}
+        if self.acpc.is_some() {return self.play_acpc(min_bet, shared_cards)}
+
+        let conn = match self.zmq_reply_socket {
+            Some(conn) => conn,
+            None => panic!("Can't play without zmq connection"),
+        };
+        let msg = Message::TurnMessage { 
+            current_players: current_players.clone(),
+            current_position,
+            current_pot,
+            min_bet,
+            min_raise,
+            max_bet,
+            shared_cards: shared_cards.clone(),
+            turn: turn.clone()
+        };
+
+        let start: Instant = self.now();
+        let play: Message = match conn.try_send_recv_message_timeout(msg, &self.msg_header(), self.decision_time()) {
+            Ok(play) => play,
+            Err(ComsError::Timeout(_)) => {
+                self.record_timeout();
+                return Play::default_action(min_bet);
             },
-            None => panic!("Can't play without zmq connection")
-        }
+            Err(ComsError::Disconnected(_)) => {
+                self.record_timeout();
+                self.sitting_out = true;
+                return Play::default_action(min_bet);
+            },
+            Err(err) => {
+                self.record_bad_reply(&err.to_string());
+                return Play::default_action(min_bet);
+            },
+        };
+
         match play {
             Message::PlayMessage{play_type, bet_amount} => {
+                self.record_decision(self.now() - start);
                 match play_type {
                     Plays::FOLD => {return Play::Fold},
                     Plays::BET => {return Play::Bet(bet_amount)},
                 };
             },
-            _ => panic!("Expected a PlayMessage in Player.play()")
+            msg => {
+                self.record_bad_reply(&format!("expected a PlayMessage, recieved {:?}", msg));
+                return Play::default_action(min_bet);
+            },
         }
 
     }
+    fn discard(&mut self, turn: &Turn) -> Vec<u8> {
+        let cards: Vec<u8> = match &self.hand {
+            Some(hand) => hand.clone(),
+            None => panic!("Player {} can't draw, player has no cards", self.name),
+        };
+        // players that can't be asked stand pat
+        if self.sitting_out || self.acpc.is_some() {return vec![]}
+
+        let conn = match self.zmq_reply_socket {
+            Some(conn) => conn,
+            None => panic!("Can't draw without zmq connection"),
+        };
+        let msg = Message::DrawMessage { cards, turn: turn.clone() };
+        // clients on a protocol without draw games stand pat
+        if conn.protocol_version(&self.name) < msg.min_protocol_version() {return vec![]}
+
+        let start: Instant = self.now();
+        let reply: Message = match conn.try_send_recv_message_timeout(msg, &self.msg_header(), self.decision_time()) {
+            Ok(reply) => reply,
+            Err(ComsError::Timeout(_)) => {
+                self.record_timeout();
+                return vec![];
+            },
+            Err(ComsError::Disconnected(_)) => {
+                self.record_timeout();
+                self.sitting_out = true;
+                return vec![];
+            },
+            Err(err) => {
+                self.record_bad_reply(&err.to_string());
+                return vec![];
+            },
+        };
+
+        match reply {
+            Message::DiscardMessage { cards } => {
+                self.record_decision(self.now() - start);
+                return cards;
+            },
+            msg => {
+                self.record_bad_reply(&format!("expected a DiscardMessage, recieved {:?}", msg));
+                return vec![];
+            },
+        }
+    }
+    fn cash_request(&mut self, stack: u32, buy_in: [u32; 2], sitting_out: bool) -> CashRequest {
+        // players that can't be asked stay as they are
+        let unchanged = CashRequest { add_chips: 0, sit_out: sitting_out };
+        if self.acpc.is_some() {return unchanged}
+
+        let conn = match self.zmq_reply_socket {
+            Some(conn) => conn,
+            None => panic!("Can't ask for a cash request without zmq connection"),
+        };
+        let msg = Message::CashOptionsMessage { stack, buy_in, sitting_out };
+        // clients on a protocol without cash games aren't asked
+        if conn.protocol_version(&self.name) < msg.min_protocol_version() {return unchanged}
+
+        let start: Instant = self.now();
+        let reply: Message = match conn.try_send_recv_message_timeout(msg, &self.msg_header(), self.decision_time()) {
+            Ok(reply) => reply,
+            Err(ComsError::Timeout(_)) => {
+                self.record_timeout();
+                return unchanged;
+            },
+            Err(ComsError::Disconnected(_)) => {
+                self.record_timeout();
+                self.sitting_out = true;
+                return CashRequest { add_chips: 0, sit_out: true };
+            },
+            Err(err) => {
+                self.record_bad_reply(&err.to_string());
+                return unchanged;
+            },
+        };
+
+        match reply {
+            Message::CashRequestMessage { add_chips, sit_out } => {
+                self.record_decision(self.now() - start);
+                // players coming back play their own hands again
+                if sit_out {self.sit_out()} else {self.sit_in()}
+                return CashRequest { add_chips, sit_out };
+            },
+            msg => {
+                self.record_bad_reply(&format!("expected a CashRequestMessage, recieved {:?}", msg));
+                return unchanged;
+            },
+        }
+    }
+    fn add_chips(&mut self, amount: u32) {
+        self.chips += amount;
+    }
     fn fold(&mut self) -> () {
         self.hand = None;
     }
     fn best_hand(&self, shared_cards: &Vec<u8>) -> [u8; 5] {
-        match self.hand {
+        match &self.hand {
             Some(hand) => return [hand[0], hand[1], shared_cards[0], shared_cards[1], shared_cards[2]],
             None => panic!("Player {} has no cards", self.name)
         }
     }
     fn assign_position(&mut self, player_position: usize, n_players: usize) -> () {
         self.position = Some([player_position, n_players]);
+        if let Some(acpc) = self.acpc.as_mut() {
+            acpc.assign_position(player_position, n_players);
+        }
     }
     fn end_round(&mut self, winnings: Option<u32>) -> () {
         self.pot_contrib = 0;
@@ -154,24 +459,123 @@ impl<'a> HoldemPlayer for Player<'a> {
     fn pot_contribution(&self) -> u32 {
         return self.pot_contrib;
     }
+    fn chips(&self) -> u32 {
+        return self.chips;
+    }
+    fn notify(&mut self, msg: &Message) -> () {
+        if let Some(acpc) = self.acpc.as_mut() {
+            acpc.notify(msg);
+        }
+        // players that left or negotiated an older protocol don't get notifications
+        if let Some(conn) = self.zmq_reply_socket && conn.protocol_version(&self.name) >= msg.min_protocol_version() {
+            let _ = conn.try_send_message(msg, &self.msg_header());
+        }
+    }
 }
 
 
+// a player that makes scripted decisions, used to replay logged hands and in tests
+#[derive(Default)]
+pub struct ScriptedPlayer {
+    pub name: String,
+    pub chips: u32,
+    pub hand: Option<Vec<u8>>,
+    pub pot_contrib: u32,
+    // scripted plays, the player calls once these run out
+    pub plays: VecDeque<Play>,
+    pub n_turns: usize,
+    pub notifications: Vec<Message>,
+    // scripted discards, the player stands pat once these run out
+    pub discards: VecDeque<Vec<u8>>,
+    // scripted cash game requests, the player stays as they are once these run out
+    pub cash_requests: VecDeque<CashRequest>,
+}
+
+impl ScriptedPlayer {
+    pub fn new(chips: u32, plays: Vec<Play>) -> ScriptedPlayer {
+        return ScriptedPlayer { chips, plays: plays.into(), ..Default::default() }
+    }
+}
+
+impl HoldemPlayer for ScriptedPlayer {
+    fn name(&self) -> String {
+        return self.name.clone();
+    }
+    fn recieve_cards(&mut self, cards: Vec<u8>) {
+        self.hand = Some(cards);
+    }
+    fn blind(&mut self, blind: Blind) -> u32 {
+        let amount: u32 = blind.amount.min(self.chips);
+        self.chips -= amount;
+        if !matches!(blind.btype, BlindType::TimeCharge) {self.pot_contrib += amount}
+        return amount;
+    }
+    fn show(&self) -> Vec<u8> {
+        match &self.hand {
+            Some(hand) => return hand.clone(),
+            None => panic!("No Cards"),
+        }
+    }
+    fn best_hand(&self, shared_cards: &Vec<u8>) -> [u8; 5] {
+        let hand: Vec<u8> = self.show();
+        return [hand[0], hand[1], shared_cards[0], shared_cards[1], shared_cards[2]];
+    }
+    fn play(&mut self, _current_players: &Vec<usize>, _current_position: usize, _current_pot: u32, min_bet: u32, _min_raise: u32, _max_bet: u32, _shared_cards: &Vec<u8>, _turn: &Turn) -> Play {
+        self.n_turns += 1;
+        return self.plays.pop_front().unwrap_or(Play::Bet(min_bet));
+    }
+    fn bet(&mut self, _shared_cards: &Vec<u8>, min_call: u32) -> u32 {
+        self.chips -= min_call;
+        self.pot_contrib += min_call;
+        return min_call;
+    }
+    fn discard(&mut self, _turn: &Turn) -> Vec<u8> {
+        return self.discards.pop_front().unwrap_or_default();
+    }
+    fn cash_request(&mut self, _stack: u32, _buy_in: [u32; 2], sitting_out: bool) -> CashRequest {
+        return self.cash_requests.pop_front().unwrap_or(CashRequest { add_chips: 0, sit_out: sitting_out });
+    }
+    fn add_chips(&mut self, amount: u32) {
+        self.chips += amount;
+    }
+    fn fold(&mut self) {
+        self.hand = None;
+    }
+    fn assign_position(&mut self, _player_position: usize, _n_players: usize) {}
+    fn end_round(&mut self, winnings: Option<u32>) {
+        self.hand = None;
+        self.pot_contrib = 0;
+        self.chips += winnings.unwrap_or(0);
+    }
+    fn pot_contribution(&self) -> u32 {
+        return self.pot_contrib;
+    }
+    fn chips(&self) -> u32 {
+        return self.chips;
+    }
+    fn notify(&mut self, msg: &Message) {
+        self.notifications.push(msg.clone());
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::pokersim::coms::tests as coms_tests;
+    use std::cell::Cell;
+    use std::rc::Rc;
 
     pub fn test_recieve_cards() {
         let zmq_conn = zmq_init();
         let mut player = Player::new("test".to_string(), 10, Some(&zmq_conn));
-        let cards: [u8; 2] = [10, 15];
+        let cards: Vec<u8> = vec![10, 15];
         player.recieve_cards(cards)
     }
 
     pub fn test_play() {
         let zmq_conn = zmq_init();
         let mut player = Player::new("test".to_string(), 10, Some(&zmq_conn));
-        let cards: [u8; 2] = [20, 30];
+        let cards: Vec<u8> = vec![20, 30];
         player.recieve_cards(cards);
 
         let mut shared_cards: Vec<u8> = [2, 3, 4].to_vec();
@@ -180,15 +584,15 @@ pub mod tests {
         let current_pot: u32 = 10_000;
         let min_bet: u32 = 1_000;
         let turn = Turn::FLOP;
-        let _play1 = player.play(&players, current_position, current_pot, min_bet, &shared_cards, &turn);
+        let _play1 = player.play(&players, current_position, current_pot, min_bet, 10_000, 10_000, &shared_cards, &turn);
         assert_eq!(player.chips, 9);
 
         shared_cards.push(5);
-        let _play2 = player.play(&players, current_position, current_pot, min_bet, &shared_cards, &turn);
+        let _play2 = player.play(&players, current_position, current_pot, min_bet, 10_000, 10_000, &shared_cards, &turn);
         assert_eq!(player.chips, 8);
 
         shared_cards.push(6);
-        let _play3 = player.play(&players, current_position, current_pot, min_bet, &shared_cards, &turn);
+        let _play3 = player.play(&players, current_position, current_pot, min_bet, 10_000, 10_000, &shared_cards, &turn);
         assert_eq!(player.chips, 8);
         assert!(player.hand.is_none());
     }
@@ -199,4 +603,201 @@ pub mod tests {
         let player2 = Player::new("test_player2".to_string(), 10_000, Some(&zmq_conn));
     }
 
+    fn turn_msg_args() -> (Vec<usize>, Vec<u8>) {
+        return (vec![0, 1], vec![]);
+    }
+
+    // every reading is one step after the last, so every decision takes exactly one step
+    struct FakeTime {
+        now: Cell<Instant>,
+        step: Duration,
+        readings: Rc<Cell<usize>>,
+    }
+
+    impl TimeSource for FakeTime {
+        fn now(&self) -> Instant {
+            self.readings.set(self.readings.get() + 1);
+            let now: Instant = self.now.get();
+            self.now.set(now + self.step);
+            return now;
+        }
+    }
+
+    fn fake_time(step: Duration) -> (Box<FakeTime>, Rc<Cell<usize>>) {
+        let readings: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        return (Box::new(FakeTime { now: Cell::new(Instant::now()), step, readings: readings.clone() }), readings);
+    }
+
+    #[test]
+    pub fn test_play_timeout() {
+        let zmq_conn = coms_tests::test_server("test_play_timeout");
+        let name: String = "slow_player".to_string();
+        // connected but never replies
+        let _client = ZmqClient::connect_with_context(&zmq_conn.context(), zmq_conn.endpoint(), &name);
+        zmq_conn.wait_for_clients(&[name.clone()]).unwrap();
+
+        let mut player = Player::new(name, 1_000, Some(&zmq_conn));
+        player.set_clock(DecisionClock {
+            decision_timeout: Some(Duration::from_millis(10)),
+            time_bank: Duration::from_millis(20),
+            max_timeouts: Some(2),
+        });
+        let (time_source, readings) = fake_time(Duration::from_millis(5));
+        player.set_time_source(time_source);
+        player.recieve_cards(vec![20, 30]);
+        let (players, shared_cards) = turn_msg_args();
+
+        // first timeout uses up the time bank and checks
+        let play = player.play(&players, 0, 0, 0, 10_000, 10_000, &shared_cards, &Turn::HOLE);
+        assert!(matches!(play, Play::Bet(0)));
+        assert_eq!(player.time_bank(), Duration::ZERO);
+        assert_eq!(player.timeouts(), 1);
+        assert!(!player.is_sitting_out());
+
+        // second consecutive timeout folds facing a bet and sits the player out
+        let play = player.play(&players, 0, 0, 100, 10_000, 10_000, &shared_cards, &Turn::HOLE);
+        assert!(matches!(play, Play::Fold));
+        assert_eq!(player.timeouts(), 2);
+        assert!(player.is_sitting_out());
+
+        // sitting out players aren't asked to play, so their decision isn't timed
+        let asked: usize = readings.get();
+        let play = player.play(&players, 0, 0, 0, 10_000, 10_000, &shared_cards, &Turn::HOLE);
+        assert!(matches!(play, Play::Bet(0)));
+        assert_eq!(readings.get(), asked);
+        assert_eq!(player.timeouts(), 2);
+
+        player.sit_in();
+        assert!(!player.is_sitting_out());
+    }
+
+    #[test]
+    pub fn test_bad_reply() {
+        let zmq_conn = coms_tests::test_server("test_bad_reply");
+        let name: String = "bad_player".to_string();
+        let client = ZmqClient::connect_with_context(&zmq_conn.context(), zmq_conn.endpoint(), &name);
+        zmq_conn.wait_for_clients(&[name.clone()]).unwrap();
+
+        // replies with the wrong message to every request
+        let bot = std::thread::spawn(move || {
+            for _ in 0..3 {
+                client.recv_message();
+                client.send_message(&Message::StatusMessage { status: Status::OK });
+            }
+        });
+
+        let mut player = Player::new(name, 1_000, Some(&zmq_conn));
+        player.set_clock(DecisionClock { max_timeouts: Some(2), ..DecisionClock::default() });
+        player.recieve_cards(vec![20, 30]);
+        let (players, shared_cards) = turn_msg_args();
+
+        // a bad reply checks or folds like a timeout and counts against the player
+        let play = player.play(&players, 0, 0, 0, 10_000, 10_000, &shared_cards, &Turn::HOLE);
+        assert!(matches!(play, Play::Bet(0)));
+        assert_eq!(player.timeouts(), 1);
+        assert_eq!(player.cash_request(500, [100, 1_000], false), CashRequest { add_chips: 0, sit_out: false });
+        assert_eq!(player.timeouts(), 2);
+        assert!(player.is_sitting_out());
+
+        player.sit_in();
+        player.recieve_cards(vec![10, 20, 30, 40, 50]);
+        assert!(player.discard(&Turn::DRAW1).is_empty());
+        assert_eq!(player.timeouts(), 3);
+        bot.join().unwrap();
+    }
+
+    #[test]
+    pub fn test_time_bank() {
+        let zmq_conn = coms_tests::test_server("test_time_bank");
+        let name: String = "thinking_player".to_string();
+        let client = ZmqClient::connect_with_context(&zmq_conn.context(), zmq_conn.endpoint(), &name);
+        zmq_conn.wait_for_clients(&[name.clone()]).unwrap();
+
+        // replies to the first turn and never to the second, staying connected until the test is done
+        let (done, wait_done) = std::sync::mpsc::channel::<()>();
+        let bot = std::thread::spawn(move || {
+            client.recv_message();
+            client.send_message(&Message::PlayMessage { play_type: Plays::BET, bet_amount: 100 });
+            client.recv_message();
+            wait_done.recv().unwrap();
+        });
+
+        let mut player = Player::new(name, 1_000, Some(&zmq_conn));
+        player.set_clock(DecisionClock {
+            decision_timeout: Some(Duration::from_millis(200)),
+            time_bank: Duration::from_millis(400),
+            max_timeouts: None,
+        });
+        // every decision takes 500ms, 300ms over the decision timeout
+        let (time_source, _) = fake_time(Duration::from_millis(500));
+        player.set_time_source(time_source);
+        player.recieve_cards(vec![20, 30]);
+        let (players, shared_cards) = turn_msg_args();
+
+        let play = player.play(&players, 0, 0, 100, 10_000, 10_000, &shared_cards, &Turn::HOLE);
+        assert!(matches!(play, Play::Bet(100)));
+        assert_eq!(player.timeouts(), 0);
+        assert_eq!(player.time_bank(), Duration::from_millis(100));
+
+        // the remaining time bank only extends the next decision by 100ms
+        assert_eq!(player.decision_time(), Some(Duration::from_millis(300)));
+        let play = player.play(&players, 0, 0, 100, 10_000, 10_000, &shared_cards, &Turn::HOLE);
+        assert!(matches!(play, Play::Fold));
+        assert_eq!(player.timeouts(), 1);
+        assert_eq!(player.time_bank(), Duration::ZERO);
+        assert!(!player.is_sitting_out());
+        done.send(()).unwrap();
+        bot.join().unwrap();
+    }
+
+    #[test]
+    pub fn test_discard() {
+        let zmq_conn = coms_tests::test_server("test_discard");
+        let name: String = "drawing_player".to_string();
+        let client = ZmqClient::connect_with_context(&zmq_conn.context(), zmq_conn.endpoint(), &name);
+        zmq_conn.wait_for_clients(&[name.clone()]).unwrap();
+
+        let bot = std::thread::spawn(move || {
+            match client.recv_message() {
+                Message::DrawMessage { cards, turn: Turn::DRAW1 } => client.send_message(&Message::DiscardMessage { cards: cards[..2].to_vec() }),
+                msg => panic!("expected a DrawMessage, got {:?}", msg),
+            }
+        });
+
+        let mut player = Player::new(name, 1_000, Some(&zmq_conn));
+        player.recieve_cards(vec![10, 20, 30, 40, 50]);
+        assert_eq!(player.discard(&Turn::DRAW1), vec![10, 20]);
+        bot.join().unwrap();
+
+        // sitting out players stand pat
+        player.sit_out();
+        assert!(player.discard(&Turn::DRAW2).is_empty());
+    }
+
+    #[test]
+    pub fn test_notify_protocol_version() {
+        let zmq_conn = coms_tests::test_server("test_notify_protocol_version");
+        let name: String = "old_player".to_string();
+        let client = ZmqClient::connect_with_context(&zmq_conn.context(), zmq_conn.endpoint(), &name);
+        client.send_message(&Message::HelloMessage { protocol_version: 2, game_variants: vec!["holdem_nl".to_string()], encodings: vec![Encoding::JSON] });
+        zmq_conn.wait_for_clients(&[name.clone()]).unwrap();
+        zmq_conn.negotiate(&name, "holdem_nl", Some(Duration::from_secs(5))).unwrap();
+        assert!(matches!(client.recv_message(), Message::WelcomeMessage { protocol_version: 2, .. }));
+
+        // protocol version 2 clients don't know about ActionMessage or any message added after it
+        let mut player = Player::new(name, 1_000, Some(&zmq_conn));
+        player.notify(&Message::ActionMessage { player: 0, action: Action::CHECK, amount: 0, all_in: false, current_pot: 0, turn: Turn::FLOP, blind: None });
+        player.notify(&Message::UpCardsMessage { turn: Turn::THIRD, up_cards: vec![vec![5], vec![]] });
+        player.notify(&Message::DrawResultMessage { player: 0, n_cards: 2, turn: Turn::DRAW1 });
+        player.notify(&Message::LevelMessage { level: 2, blinds: [10, 20], ante: 0, hands: 0 });
+        player.notify(&Message::TableMoveMessage { player: "old_player".to_string(), from_table: 2, to_table: 1, seat: 3 });
+        // they aren't asked to draw and stand pat
+        player.recieve_cards(vec![10, 20, 30, 40, 50]);
+        assert!(player.discard(&Turn::DRAW1).is_empty());
+        // or asked to rebuy between cash game hands
+        assert_eq!(player.cash_request(10, [50, 100], false), CashRequest { add_chips: 0, sit_out: false });
+        player.notify(&Message::EndOfGameMessage);
+        assert_eq!(client.recv_message(), Message::EndOfGameMessage);
+    }
+
 }
@@ -0,0 +1,111 @@
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pot {
+    pub amount: u32,
+    // players still in the hand who can win this pot
+    pub eligible: Vec<usize>,
+}
+
+pub fn side_pots(bets: &[u32], in_hand: &[usize]) -> Vec<Pot> {
+    // split the total bets into a main pot and side pots, one for each all in level.
+    // bets are the total each player put in this hand, in_hand are the players that haven't folded
+    let mut levels: Vec<u32> = in_hand.iter().map(|&idx| bets[idx]).filter(|&bet| bet > 0).collect();
+    levels.sort();
+    levels.dedup();
+
+    let mut pots: Vec<Pot> = vec![];
+    let mut prev_level: u32 = 0;
+    for level in levels {
+        let amount: u32 = bets.iter().map(|&bet| bet.min(level) - bet.min(prev_level)).sum();
+        let eligible: Vec<usize> = in_hand.iter().copied().filter(|&idx| bets[idx] >= level).collect();
+        pots.push(Pot{ amount, eligible });
+        prev_level = level;
+    }
+
+    // folded players can have bet more than anyone left in the hand
+    let remainder: u32 = bets.iter().sum::<u32>() - pots.iter().map(|pot| pot.amount).sum::<u32>();
+    match pots.last_mut() {
+        Some(pot) => pot.amount += remainder,
+        None => pots.push(Pot{ amount: remainder, eligible: in_hand.to_vec() }),
+    }
+    return pots;
+}
+
+pub fn split_pot(amount: u32, winners: &[usize]) -> Vec<u32> {
+    // split a pot evenly, odd chips go to the first winners in seat order
+    let n_winners: u32 = winners.len() as u32;
+    let remainder: u32 = amount % n_winners;
+    return (0..n_winners).map(|idx| amount / n_winners + if idx < remainder {1} else {0}).collect();
+}
+
+pub fn pot_winners(pot: &Pot, scores: &[Option<f64>], lows: &[Option<[u8; 5]>]) -> (Vec<usize>, Vec<usize>) {
+    // the best high hands and the best lows of the players eligible for a pot, in seat order.
+    // lows is empty when no eligible player has a low, a pot with one eligible player goes to them
+    if pot.eligible.len() == 1 {return (pot.eligible.clone(), vec![])}
+    let best: f64 = pot.eligible.iter().filter_map(|idx| scores[*idx]).fold(f64::MIN, f64::max);
+    let high: Vec<usize> = pot.eligible.iter().copied().filter(|idx| scores[*idx] == Some(best)).collect();
+    let low: Vec<usize> = match pot.eligible.iter().filter_map(|idx| lows[*idx]).min() {
+        Some(best_low) => pot.eligible.iter().copied().filter(|idx| lows[*idx] == Some(best_low)).collect(),
+        None => vec![],
+    };
+    return (high, low);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_single_pot() {
+        let pots = side_pots(&[100, 100, 100], &[0, 1, 2]);
+        assert_eq!(pots, vec![Pot{ amount: 300, eligible: vec![0, 1, 2] }]);
+
+        // folded players' bets stay in the pot
+        let pots = side_pots(&[100, 50, 100], &[0, 2]);
+        assert_eq!(pots, vec![Pot{ amount: 250, eligible: vec![0, 2] }]);
+    }
+
+    #[test]
+    pub fn test_side_pots() {
+        // player 1 is all in for 50, player 2 all in for 80
+        let pots = side_pots(&[100, 50, 80, 100], &[0, 1, 2, 3]);
+        assert_eq!(pots, vec![
+            Pot{ amount: 200, eligible: vec![0, 1, 2, 3] },
+            Pot{ amount: 90, eligible: vec![0, 2, 3] },
+            Pot{ amount: 40, eligible: vec![0, 3] },
+        ]);
+
+        // a folded player who bet more than the all in player
+        let pots = side_pots(&[30, 100, 100, 0], &[0, 2]);
+        assert_eq!(pots, vec![
+            Pot{ amount: 90, eligible: vec![0, 2] },
+            Pot{ amount: 140, eligible: vec![2] },
+        ]);
+        assert_eq!(pots.iter().map(|pot| pot.amount).sum::<u32>(), 230);
+    }
+
+    #[test]
+    pub fn test_no_bets() {
+        let pots = side_pots(&[0, 0], &[0, 1]);
+        assert_eq!(pots, vec![Pot{ amount: 0, eligible: vec![0, 1] }]);
+    }
+
+    #[test]
+    pub fn test_split_pot() {
+        assert_eq!(split_pot(300, &[0, 2]), vec![150, 150]);
+        assert_eq!(split_pot(301, &[0, 2]), vec![151, 150]);
+        assert_eq!(split_pot(302, &[0, 1, 2]), vec![101, 101, 100]);
+    }
+
+    #[test]
+    pub fn test_pot_winners() {
+        let pot = Pot{ amount: 300, eligible: vec![0, 2, 3] };
+        let scores: Vec<Option<f64>> = vec![Some(5.0), Some(9.0), Some(5.0), Some(2.0)];
+        assert_eq!(pot_winners(&pot, &scores, &[None; 4]), (vec![0, 2], vec![]));
+
+        // the lowest low wins, player 1 folded and isn't eligible
+        let lows: Vec<Option<[u8; 5]>> = vec![None, Some([1, 2, 3, 4, 5]), Some([1, 2, 3, 4, 6]), Some([1, 2, 3, 5, 7])];
+        assert_eq!(pot_winners(&pot, &scores, &lows), (vec![0, 2], vec![2]));
+        assert_eq!(pot_winners(&Pot{ amount: 300, eligible: vec![1] }, &[None; 4], &lows), (vec![1], vec![]));
+    }
+}
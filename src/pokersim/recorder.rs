@@ -0,0 +1,170 @@
+use std::cell::RefCell;
+use super::coms::Message;
+use super::games::{AllIn, Rake, TableEvents};
+use super::history::HandHistoryWriter;
+use super::hand_log::HandLogWriter;
+
+
+// Writes the hand histories and hand logs of a table's hands. ZmqSocketReply records through one,
+// games played without a connection can be passed one directly as their TableEvents.
+// EventBuffer holds a table's events while it plays on its own thread.
+
+#[derive(Default)]
+pub struct HandRecorder {
+    hand_history: RefCell<Option<HandHistoryWriter>>,
+    hand_log: RefCell<Option<HandLogWriter>>,
+}
+
+impl HandRecorder {
+
+    pub fn new() -> HandRecorder {
+        return HandRecorder::default();
+    }
+
+    pub fn write_hand_history(&mut self, writer: HandHistoryWriter) {
+        self.hand_history = RefCell::new(Some(writer));
+    }
+
+    pub fn write_hand_log(&mut self, writer: HandLogWriter) {
+        self.hand_log = RefCell::new(Some(writer));
+    }
+}
+
+// the events of a table playing on its own thread, passed on once its hand is done
+enum TableEvent {
+    Publish(Message),
+    Deck(u64, Vec<u8>),
+    Rake(Rake),
+    AllIn(AllIn),
+    EndHand(Message),
+}
+
+#[derive(Default)]
+pub struct EventBuffer {
+    events: RefCell<Vec<TableEvent>>,
+}
+
+impl EventBuffer {
+
+    pub fn new() -> EventBuffer {
+        return EventBuffer::default();
+    }
+
+    pub fn replay(self, events: &dyn TableEvents) {
+        for event in self.events.into_inner() {
+            match event {
+                TableEvent::Publish(msg) => events.publish(&msg),
+                TableEvent::Deck(seed, deck) => events.log_deck(seed, &deck),
+                TableEvent::Rake(rake) => events.log_rake(&rake),
+                TableEvent::AllIn(all_in) => events.log_all_in(all_in),
+                TableEvent::EndHand(msg) => events.end_hand(&msg),
+            }
+        }
+    }
+}
+
+impl TableEvents for EventBuffer {
+    fn publish(&self, msg: &Message) {
+        self.events.borrow_mut().push(TableEvent::Publish(msg.clone()));
+    }
+
+    fn log_deck(&self, seed: u64, deck: &[u8]) {
+        self.events.borrow_mut().push(TableEvent::Deck(seed, deck.to_vec()));
+    }
+
+    fn log_rake(&self, rake: &Rake) {
+        self.events.borrow_mut().push(TableEvent::Rake(rake.clone()));
+    }
+
+    fn log_all_in(&self, all_in: AllIn) {
+        self.events.borrow_mut().push(TableEvent::AllIn(all_in));
+    }
+
+    fn end_hand(&self, msg: &Message) {
+        self.events.borrow_mut().push(TableEvent::EndHand(msg.clone()));
+    }
+}
+
+impl TableEvents for HandRecorder {
+    fn publish(&self, msg: &Message) {
+        if let Some(writer) = self.hand_history.borrow_mut().as_mut() && let Err(err) = writer.record(msg) {
+            println!("Could not write the hand history: {}", err);
+        }
+        if let Some(writer) = self.hand_log.borrow_mut().as_mut() && let Err(err) = writer.record(msg) {
+            println!("Could not write the hand log: {}", err);
+        }
+    }
+
+    fn log_deck(&self, seed: u64, deck: &[u8]) {
+        // the deck order is only logged, never published
+        if let Some(writer) = self.hand_log.borrow_mut().as_mut() {
+            writer.record_deck(seed, deck);
+        }
+    }
+
+    fn log_rake(&self, rake: &Rake) {
+        if let Some(writer) = self.hand_log.borrow_mut().as_mut() {
+            writer.record_rake(rake);
+        }
+    }
+
+    fn log_all_in(&self, all_in: AllIn) {
+        if let Some(writer) = self.hand_log.borrow_mut().as_mut() {
+            writer.record_all_in(all_in);
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pokersim::dealer::Dealer;
+    use crate::pokersim::games::holdem_nl;
+    use crate::pokersim::player::{Play, ScriptedPlayer};
+    use crate::pokersim::history::{parse_hand_histories, HandHistory};
+    use crate::pokersim::hand_log::replay_hand_log;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    // a writer tests can read back after a hand history or hand log writer is done with it
+    #[derive(Clone, Default)]
+    pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        pub fn text(&self) -> String {
+            return String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+        fn flush(&mut self) -> std::io::Result<()> {return Ok(())}
+    }
+
+    #[test]
+    pub fn test_record_without_connection() {
+        // hands played in process are recorded without a zmq connection
+        let (history, log) = (SharedBuffer::default(), SharedBuffer::default());
+        let mut recorder = HandRecorder::new();
+        recorder.write_hand_history(HandHistoryWriter::new(Box::new(history.clone()), "table_1"));
+        recorder.write_hand_log(HandLogWriter::new(Box::new(log.clone())));
+
+        let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(1000, vec![Play::Bet(30)]), ScriptedPlayer::new(1000, vec![])];
+        for (idx, player) in players.iter_mut().enumerate() {
+            player.name = format!("player_{}", idx);
+        }
+        let mut dealer = Dealer::with_seed(2);
+        for _ in 0..2 {
+            holdem_nl(&mut dealer, &mut players, [5, 10], 0, Some(&recorder));
+        }
+
+        let hands: Vec<HandHistory> = parse_hand_histories(&history.text()).unwrap();
+        assert_eq!(hands.len(), 2);
+        assert_eq!(hands[1].hand_id, 2);
+        assert_eq!(replay_hand_log(log.text().as_bytes()).unwrap(), 2);
+    }
+}
@@ -0,0 +1,302 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use super::dealer::{Dealer, standard_deck, short_deck, kuhn_deck, leduc_deck};
+use super::games::*;
+use super::player::HoldemPlayer;
+
+
+// The rules of a game and the registry of variants pokersim can deal.
+// Every hand of a game is played through play_hand with the same rules
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum AnteType {
+    // every player antes
+    #[default]
+    PerPlayer,
+    // the big blind antes for the whole table
+    BigBlind,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Straddle {
+    #[default]
+    Off,
+    // the player after the big blind posts two big blinds and acts last before the flop
+    Utg,
+    // the button posts two big blinds, play before the flop starts after the button and ends with it
+    Button,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeckType {
+    Standard,
+    // 36 cards without the twos to fives
+    ShortDeck,
+    Kuhn,
+    Leduc,
+}
+
+impl DeckType {
+    pub fn cards(&self) -> Vec<u8> {
+        match self {
+            DeckType::Standard => return standard_deck(),
+            DeckType::ShortDeck => return short_deck(),
+            DeckType::Kuhn => return kuhn_deck(),
+            DeckType::Leduc => return leduc_deck(),
+        }
+    }
+}
+
+// how a variant deals its hands, betting, blinds and antes come from the rules
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameKind {
+    Board { hole_cards: usize, evaluation: HandEvaluation, button_blind: bool },
+    Draw { n_draws: usize, evaluation: HandEvaluation },
+    Stud { evaluation: HandEvaluation },
+    // Kuhn and Leduc are dealt with fixed bets and antes
+    Kuhn,
+    Leduc,
+}
+
+pub const VARIANTS: [(&str, GameKind); 13] = [
+    (HOLDEM_NL, GameKind::Board { hole_cards: 2, evaluation: HandEvaluation::Holdem, button_blind: false }),
+    (HOLDEM_LIMIT, GameKind::Board { hole_cards: 2, evaluation: HandEvaluation::Holdem, button_blind: false }),
+    (HOLDEM_PL, GameKind::Board { hole_cards: 2, evaluation: HandEvaluation::Holdem, button_blind: false }),
+    (OMAHA, GameKind::Board { hole_cards: 4, evaluation: HandEvaluation::Omaha, button_blind: false }),
+    (PLO5, GameKind::Board { hole_cards: 5, evaluation: HandEvaluation::Omaha, button_blind: false }),
+    (OMAHA_HILO, GameKind::Board { hole_cards: 4, evaluation: HandEvaluation::OmahaHiLo, button_blind: false }),
+    (SHORT_DECK, GameKind::Board { hole_cards: 2, evaluation: HandEvaluation::ShortDeck, button_blind: true }),
+    (SEVEN_CARD_STUD, GameKind::Stud { evaluation: HandEvaluation::Holdem }),
+    (RAZZ, GameKind::Stud { evaluation: HandEvaluation::Razz }),
+    (FIVE_CARD_DRAW, GameKind::Draw { n_draws: 1, evaluation: HandEvaluation::Holdem }),
+    (TRIPLE_DRAW, GameKind::Draw { n_draws: 3, evaluation: HandEvaluation::DeuceToSeven }),
+    (KUHN, GameKind::Kuhn),
+    (LEDUC, GameKind::Leduc),
+];
+
+pub fn game_kind(variant: &str) -> Option<GameKind> {
+    return VARIANTS.iter().find(|(name, _)| *name == variant).map(|(_, kind)| *kind);
+}
+
+pub fn variant_names() -> Vec<&'static str> {
+    return VARIANTS.iter().map(|(name, _)| *name).collect();
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RulesError {
+    UnknownVariant(String),
+    Invalid { variant: String, reason: String },
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::UnknownVariant(variant) => write!(f, "unknown game variant {}, pokersim deals {:?}", variant, variant_names()),
+            RulesError::Invalid { variant, reason } => write!(f, "invalid rules for {}: {}", variant, reason),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameRules {
+    pub variant: String,
+    pub betting: Betting,
+    // [little, big], the button blind of short deck is the big blind
+    pub blinds: [u32; 2],
+    pub ante: u32,
+    pub ante_type: AnteType,
+    // stud games only, posted by the lowest up card
+    pub bring_in: u32,
+    pub straddle: Straddle,
+    pub max_seats: usize,
+    pub deck: DeckType,
+    // the house's share of every hand, rules written before rakes deal without one
+    #[serde(default)]
+    pub rake: Rake,
+    // cash game players who missed the big blind while sitting out post it and a dead small blind
+    // to come back at once, instead of waiting for the big blind
+    #[serde(default)]
+    pub post_missed_blinds: bool,
+    // board games can run the board more than once or settle by equity when players are all in before the river
+    #[serde(default)]
+    pub all_in: AllIn,
+}
+
+impl Default for GameRules {
+    fn default() -> GameRules {
+        // the game pokersim has always dealt, no limit hold'em with antes and no blinds
+        return GameRules { blinds: [0, 0], ante: 10, ..GameRules::new(HOLDEM_NL).unwrap() }
+    }
+}
+
+impl GameRules {
+
+    pub fn new(variant: &str) -> Result<GameRules, RulesError> {
+        // the usual rules of a variant, fields can be changed before the game starts
+        if game_kind(variant).is_none() {return Err(RulesError::UnknownVariant(variant.to_string()))}
+        let limit = |bets: [u32; 2]| Betting::FixedLimit { bets, raise_cap: Some(4), heads_up_uncapped: true };
+        let rules = GameRules {
+            variant: variant.to_string(),
+            betting: Betting::NoLimit,
+            blinds: [1, 2],
+            ante: 0,
+            ante_type: AnteType::PerPlayer,
+            bring_in: 0,
+            straddle: Straddle::Off,
+            max_seats: 10,
+            deck: DeckType::Standard,
+            rake: Rake::default(),
+            post_missed_blinds: false,
+            all_in: AllIn::RunOnce,
+        };
+        match variant {
+            HOLDEM_LIMIT | OMAHA_HILO => return Ok(GameRules { betting: limit([2, 4]), ..rules }),
+            HOLDEM_PL | OMAHA => return Ok(GameRules { betting: Betting::PotLimit, ..rules }),
+            FIVE_CARD_DRAW => return Ok(GameRules { betting: Betting::PotLimit, max_seats: 6, ..rules }),
+            PLO5 => return Ok(GameRules { betting: Betting::PotLimit, max_seats: 9, ..rules }),
+            SHORT_DECK => return Ok(GameRules { blinds: [0, 2], ante: 1, max_seats: 9, deck: DeckType::ShortDeck, ..rules }),
+            SEVEN_CARD_STUD | RAZZ => return Ok(GameRules { betting: Betting::FixedLimit { bets: [4, 8], raise_cap: Some(4), heads_up_uncapped: false }, blinds: [0, 0], ante: 1, bring_in: 2, max_seats: 7, ..rules }),
+            TRIPLE_DRAW => return Ok(GameRules { betting: limit([2, 4]), max_seats: 6, ..rules }),
+            // Kuhn and Leduc always play these rules, Leduc bets 2 before and 4 after the board card
+            KUHN => return Ok(GameRules { betting: Betting::FixedLimit { bets: [1, 1], raise_cap: Some(1), heads_up_uncapped: false }, blinds: [0, 0], ante: 1, max_seats: 2, deck: DeckType::Kuhn, ..rules }),
+            LEDUC => return Ok(GameRules { betting: Betting::FixedLimit { bets: [2, 4], raise_cap: Some(2), heads_up_uncapped: false }, blinds: [0, 0], ante: 1, max_seats: 2, deck: DeckType::Leduc, ..rules }),
+            _ => return Ok(rules),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), RulesError> {
+        let kind: GameKind = game_kind(&self.variant).ok_or_else(|| RulesError::UnknownVariant(self.variant.clone()))?;
+        let invalid = |reason: String| Err(RulesError::Invalid { variant: self.variant.clone(), reason });
+
+        if self.blinds[0] > self.blinds[1] {return invalid(format!("blinds {:?} must be [little, big]", self.blinds))}
+        if self.max_seats < 2 {return invalid(format!("{} seats, games need at least 2", self.max_seats))}
+        // straddles, big blind antes and missed blinds need a little and a big blind
+        let both_blinds: bool = matches!(kind, GameKind::Board { button_blind: false, .. }) && self.blinds[0] > 0;
+        if self.straddle != Straddle::Off && !both_blinds {return invalid("only board games with a little and big blind can be straddled".to_string())}
+        if self.straddle != Straddle::Off && matches!(self.betting, Betting::FixedLimit { .. }) {return invalid("fixed limit games can't be straddled".to_string())}
+        if self.ante_type == AnteType::BigBlind && !both_blinds {return invalid("big blind antes need a little and big blind".to_string())}
+        if self.post_missed_blinds && !both_blinds {return invalid("missed blinds are only posted in board games with a little and big blind".to_string())}
+        if !(0.0..=100.0).contains(&self.rake.percent) {return invalid(format!("a rake of {}% isn't a share of the pot", self.rake.percent))}
+        if !self.all_in.is_run_once() && !matches!(kind, GameKind::Board { .. }) {return invalid("only board games can run the board more than once or pay by equity".to_string())}
+        if self.all_in == AllIn::RunTimes(0) {return invalid("the board has to be run at least once".to_string())}
+
+        let betting_ok: bool = match self.variant.as_str() {
+            HOLDEM_NL | SHORT_DECK => self.betting == Betting::NoLimit,
+            HOLDEM_PL | PLO5 => self.betting == Betting::PotLimit,
+            HOLDEM_LIMIT => matches!(self.betting, Betting::FixedLimit { .. }),
+            _ => true,
+        };
+        if !betting_ok {return invalid(format!("{} can't be played with {:?} betting", self.variant, self.betting))}
+
+        let n_cards: usize = self.deck.cards().len();
+        let dealt: usize = match kind {
+            GameKind::Board { hole_cards, .. } => hole_cards * self.max_seats + 5,
+            GameKind::Draw { .. } => 5 * self.max_seats,
+            GameKind::Stud { .. } => 7 * self.max_seats,
+            GameKind::Kuhn | GameKind::Leduc => 0,
+        };
+        if dealt > n_cards {return invalid(format!("{} seats need {} cards, the deck has {}", self.max_seats, dealt, n_cards))}
+
+        match kind {
+            GameKind::Board { button_blind: true, .. } if self.blinds[0] > 0 => return invalid("only the button posts a blind".to_string()),
+            GameKind::Stud { .. } if self.blinds != [0, 0] => return invalid("stud games have a bring in instead of blinds".to_string()),
+            GameKind::Kuhn | GameKind::Leduc if *self != GameRules::new(&self.variant)? => return invalid("the rules of research games can't be changed".to_string()),
+            _ => return Ok(()),
+        }
+    }
+
+    pub fn forced_bets(&self) -> u32 {
+        // chips a player needs to be dealt in
+        return self.ante + self.blinds[1].max(self.bring_in) + self.rake.time_charge;
+    }
+}
+
+pub fn play_hand<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, rules: &GameRules, events: Option<&dyn TableEvents>) -> HandResult {
+    // plays one hand of the rules' variant
+    return play_hand_with_missed_blinds(dealer, players, rules, &[], events);
+}
+
+pub fn play_hand_with_missed_blinds<T: HoldemPlayer>(dealer: &mut Dealer, players: &mut Vec<T>, rules: &GameRules, missed_blinds: &[usize], events: Option<&dyn TableEvents>) -> HandResult {
+    // missed_blinds are the players, by their seat in this hand, who post the blinds they missed
+    if let Err(err) = rules.validate() {panic!("{}", err)}
+    if players.len() > rules.max_seats {panic!("{} players can't play at a table of {} seats", players.len(), rules.max_seats)}
+
+    match game_kind(&rules.variant).unwrap() {
+        GameKind::Board { hole_cards, evaluation, button_blind } => {
+            let posting = Posting { straddle: rules.straddle, ante_type: rules.ante_type, missed_blinds: missed_blinds.to_vec() };
+            let game = BoardGame { hole_cards, deck: rules.deck, betting: rules.betting.clone(), evaluation, button_blind, rake: rules.rake.clone(), posting, all_in: rules.all_in };
+            return play_board_game(dealer, players, rules.blinds, rules.ante, &game, events);
+        },
+        GameKind::Draw { n_draws, evaluation } => {
+            let game = DrawGame { n_draws, deck: rules.deck, betting: rules.betting.clone(), evaluation, rake: rules.rake.clone() };
+            return play_draw_game(dealer, players, rules.blinds, rules.ante, &game, events);
+        },
+        GameKind::Stud { evaluation } => return play_stud(dealer, players, rules.ante, rules.bring_in, &rules.betting, &evaluation, rules.deck, &rules.rake, events),
+        GameKind::Kuhn => return kuhn(dealer, players, events),
+        GameKind::Leduc => return leduc(dealer, players, events),
+    }
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pokersim::coms::Message;
+    use crate::pokersim::player::ScriptedPlayer;
+
+    #[test]
+    pub fn test_variant_rules() {
+        for variant in variant_names() {
+            let rules = GameRules::new(variant).unwrap();
+            assert_eq!(rules.variant, variant);
+            assert_eq!(rules.validate(), Ok(()), "{}", variant);
+        }
+        assert_eq!(GameRules::default().validate(), Ok(()));
+        assert_eq!(GameRules::new("mississippi_stud"), Err(RulesError::UnknownVariant("mississippi_stud".to_string())));
+
+        let invalid = |rules: GameRules| matches!(rules.validate(), Err(RulesError::Invalid { .. }));
+        assert!(invalid(GameRules { betting: Betting::PotLimit, ..GameRules::new(HOLDEM_NL).unwrap() }));
+        assert!(invalid(GameRules { blinds: [4, 2], ..GameRules::new(OMAHA).unwrap() }));
+        // ten players need 55 cards in five card Omaha
+        assert!(invalid(GameRules { max_seats: 10, ..GameRules::new(PLO5).unwrap() }));
+        assert!(invalid(GameRules { blinds: [1, 2], ..GameRules::new(RAZZ).unwrap() }));
+        assert!(invalid(GameRules { blinds: [1, 2], ..GameRules::new(SHORT_DECK).unwrap() }));
+        assert!(invalid(GameRules { ante: 2, ..GameRules::new(KUHN).unwrap() }));
+        assert!(invalid(GameRules { straddle: Straddle::Utg, ..GameRules::new(HOLDEM_LIMIT).unwrap() }));
+        assert!(invalid(GameRules { straddle: Straddle::Button, ..GameRules::new(SHORT_DECK).unwrap() }));
+        assert!(invalid(GameRules { ante: 2, ante_type: AnteType::BigBlind, ..GameRules::new(TRIPLE_DRAW).unwrap() }));
+        assert!(invalid(GameRules { post_missed_blinds: true, ..GameRules::default() }));
+        let straddled = GameRules { straddle: Straddle::Utg, ante: 2, ante_type: AnteType::BigBlind, post_missed_blinds: true, ..GameRules::new(OMAHA).unwrap() };
+        assert_eq!(straddled.validate(), Ok(()));
+
+        assert!(invalid(GameRules { rake: Rake { percent: 120.0, ..Rake::default() }, ..GameRules::new(HOLDEM_NL).unwrap() }));
+        assert!(invalid(GameRules { rake: Rake { time_charge: 1, ..Rake::default() }, ..GameRules::new(LEDUC).unwrap() }));
+        assert!(invalid(GameRules { all_in: AllIn::RunTimes(0), ..GameRules::new(HOLDEM_NL).unwrap() }));
+        assert!(invalid(GameRules { all_in: AllIn::PayEquity, ..GameRules::new(RAZZ).unwrap() }));
+        assert_eq!(GameRules { all_in: AllIn::RunTimes(2), ..GameRules::new(OMAHA_HILO).unwrap() }.validate(), Ok(()));
+
+        let rules = GameRules::new(SEVEN_CARD_STUD).unwrap();
+        assert_eq!(rules.forced_bets(), 3);
+        // the time charge is taken before every hand
+        assert_eq!(GameRules { rake: Rake { time_charge: 2, ..Rake::default() }, ..rules }.forced_bets(), 5);
+    }
+
+    #[test]
+    pub fn test_play_hand() {
+        // every variant plays through its rules, chips are only moved between the players
+        let mut dealer = Dealer::with_seed(17);
+        for variant in variant_names() {
+            let rules = GameRules::new(variant).unwrap();
+            let mut players: Vec<ScriptedPlayer> = vec![ScriptedPlayer::new(100, vec![]), ScriptedPlayer::new(100, vec![])];
+            play_hand(&mut dealer, &mut players, &rules, None);
+            assert_eq!(players.iter().map(|player| player.chips).sum::<u32>(), 200, "{}", variant);
+            assert!(matches!(players[0].notifications.last(), Some(Message::EndOfRoundMessage { .. })), "{}", variant);
+            assert_eq!(dealer.deck.len(), rules.deck.cards().len(), "{}", variant);
+        }
+
+        // the dealer goes back to the full deck after a short deck hand
+        play_hand(&mut dealer, &mut vec![ScriptedPlayer::new(100, vec![]), ScriptedPlayer::new(100, vec![])], &GameRules::new(SHORT_DECK).unwrap(), None);
+        play_hand(&mut dealer, &mut vec![ScriptedPlayer::new(100, vec![]), ScriptedPlayer::new(100, vec![])], &GameRules::new(HOLDEM_NL).unwrap(), None);
+        assert_eq!(dealer.deck.len(), 52);
+    }
+}
@@ -0,0 +1,250 @@
+use serde::{Serialize, Deserialize};
+use std::fs::File;
+use super::coms::{Message, Standing};
+use super::pots::split_pot;
+use super::rules::GameRules;
+
+
+// Single table tournaments: blinds and antes go up with a level schedule, players are knocked out
+// when they have lost every chip and the prize pool is paid out by finishing position
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlindLevel {
+    pub blinds: [u32; 2],
+    pub ante: u32,
+    // hands played at this level, the last level is played until the tournament ends
+    pub hands: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TournamentConfig {
+    // blinds and antes of the game rules are used for the whole tournament without levels
+    pub levels: Vec<BlindLevel>,
+    pub buy_in: u32,
+    // percent of the prize pool paid to each finishing position, starting with the winner
+    pub payouts: Vec<u32>,
+    // the final standings are written to this file as JSON when set
+    pub summary: Option<String>,
+    // seats at each table of a multi-table tournament, every player sits at one table without it
+    pub table_size: Option<usize>,
+}
+
+impl TournamentConfig {
+    pub fn validate(&self, rules: &GameRules) -> Result<(), String> {
+        if !self.payouts.is_empty() && self.payouts.iter().sum::<u32>() != 100 {
+            return Err(format!("payouts {:?} don't add up to 100 percent", self.payouts));
+        }
+        if let Some(table_size) = self.table_size && (table_size < 2 || table_size > rules.max_seats) {
+            return Err(format!("tables of {} seats, {} is played with 2 to {} seats", table_size, rules.variant, rules.max_seats));
+        }
+        for (idx, level) in self.levels.iter().enumerate() {
+            let level_rules = GameRules { blinds: level.blinds, ante: level.ante, ..rules.clone() };
+            level_rules.validate().map_err(|err| format!("level {}: {}", idx + 1, err))?;
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TournamentSummary {
+    pub variant: String,
+    pub entrants: usize,
+    pub prize_pool: u32,
+    pub hands: usize,
+    // the level the tournament ended at, counted from 1
+    pub level: usize,
+    pub standings: Vec<Standing>,
+}
+
+pub struct Tournament {
+    levels: Vec<BlindLevel>,
+    level_idx: usize,
+    hands_at_level: usize,
+    hands: usize,
+    entrants: usize,
+    prize_pool: u32,
+    payouts: Vec<u32>,
+    // finishing positions of the players knocked out so far
+    standings: Vec<Standing>,
+}
+
+impl Tournament {
+
+    pub fn new(config: &TournamentConfig, rules: &GameRules, entrants: usize) -> Tournament {
+        let levels: Vec<BlindLevel> = match config.levels.is_empty() {
+            true => vec![BlindLevel { blinds: rules.blinds, ante: rules.ante, hands: 0 }],
+            false => config.levels.clone(),
+        };
+        let prize_pool: u32 = config.buy_in * entrants as u32;
+        return Tournament {
+            levels,
+            level_idx: 0,
+            hands_at_level: 0,
+            hands: 0,
+            entrants,
+            prize_pool,
+            payouts: prize_amounts(prize_pool, &config.payouts),
+            standings: vec![],
+        }
+    }
+
+    pub fn level(&self) -> &BlindLevel {
+        return &self.levels[self.level_idx];
+    }
+
+    pub fn level_number(&self) -> usize {
+        return self.level_idx + 1;
+    }
+
+    pub fn level_message(&self) -> Message {
+        // the last level is played until the end, it's announced with 0 hands
+        let hands: usize = if self.level_idx + 1 < self.levels.len() {self.level().hands} else {0};
+        return Message::LevelMessage { level: self.level_number(), blinds: self.level().blinds, ante: self.level().ante, hands };
+    }
+
+    pub fn rules(&self, rules: &GameRules) -> GameRules {
+        // the game rules with the blinds and ante of the current level
+        return GameRules { blinds: self.level().blinds, ante: self.level().ante, ..rules.clone() };
+    }
+
+    pub fn end_hand(&mut self) -> bool {
+        // counts a played hand, returns true when the next hand starts a new level
+        self.hands += 1;
+        self.hands_at_level += 1;
+        if self.level_idx + 1 < self.levels.len() && self.hands_at_level >= self.level().hands {
+            self.level_idx += 1;
+            self.hands_at_level = 0;
+            return true;
+        }
+        return false;
+    }
+
+    pub fn eliminate(&mut self, busted: &[(String, u32)], n_remaining: usize) -> Vec<Standing> {
+        // busted players in seat order with the chips they started the hand with, the bigger stack finishes higher.
+        // Players who started with the same stack share a finishing position and split its payouts
+        let mut knocked_out: Vec<Standing> = vec![];
+        for (idx, (name, stack)) in busted.iter().enumerate() {
+            let n_bigger: usize = busted.iter().filter(|(_, other)| other > stack).count();
+            let tied: Vec<usize> = (0..busted.len()).filter(|other| busted[*other].1 == *stack).collect();
+            let finishing_position: usize = n_remaining + n_bigger + 1;
+            let shares: Vec<u32> = split_pot(self.payout_total(finishing_position, tied.len()), &tied);
+            let payout: u32 = shares[tied.iter().position(|other| *other == idx).unwrap()];
+            knocked_out.push(Standing { player: name.clone(), finishing_position, payout });
+        }
+        knocked_out.sort_by_key(|standing| standing.finishing_position);
+        self.standings.extend(knocked_out.iter().cloned());
+        return knocked_out;
+    }
+
+    fn payout_total(&self, finishing_position: usize, n_positions: usize) -> u32 {
+        // payouts of the positions a group of tied players takes up
+        return (finishing_position..finishing_position + n_positions).map(|position| self.payouts.get(position - 1).copied().unwrap_or(0)).sum();
+    }
+
+    pub fn finish(&mut self, winner: Option<String>, variant: &str) -> TournamentSummary {
+        // the winner takes first place, standings are sorted by finishing position
+        if let Some(winner) = winner {
+            self.standings.push(Standing { player: winner, finishing_position: 1, payout: self.payout_total(1, 1) });
+        }
+        let mut standings: Vec<Standing> = self.standings.clone();
+        standings.sort_by_key(|standing| standing.finishing_position);
+        return TournamentSummary {
+            variant: variant.to_string(),
+            entrants: self.entrants,
+            prize_pool: self.prize_pool,
+            hands: self.hands,
+            level: self.level_number(),
+            standings,
+        }
+    }
+}
+
+pub fn prize_amounts(prize_pool: u32, payouts: &[u32]) -> Vec<u32> {
+    // chips paid to each finishing position, the rounding remainder goes to the winner
+    let mut amounts: Vec<u32> = payouts.iter().map(|percent| (prize_pool as u64 * *percent as u64 / 100) as u32).collect();
+    let remainder: u32 = prize_pool - amounts.iter().sum::<u32>();
+    if let Some(first) = amounts.first_mut() {
+        *first += remainder;
+    }
+    return amounts;
+}
+
+pub fn write_summary(path: &str, summary: &TournamentSummary) -> std::io::Result<()> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, summary)?;
+    return Ok(());
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pokersim::games::HOLDEM_NL;
+
+    fn levels() -> Vec<BlindLevel> {
+        return vec![
+            BlindLevel { blinds: [5, 10], ante: 0, hands: 2 },
+            BlindLevel { blinds: [10, 20], ante: 2, hands: 1 },
+            BlindLevel { blinds: [20, 40], ante: 5, hands: 1 },
+        ];
+    }
+
+    #[test]
+    pub fn test_blind_levels() {
+        let rules = GameRules::new(HOLDEM_NL).unwrap();
+        let config = TournamentConfig { levels: levels(), ..Default::default() };
+        assert_eq!(config.validate(&rules), Ok(()));
+        let mut tournament = Tournament::new(&config, &rules, 4);
+
+        assert_eq!(tournament.rules(&rules).blinds, [5, 10]);
+        assert!(!tournament.end_hand());
+        assert!(tournament.end_hand());
+        assert_eq!(tournament.level_number(), 2);
+        assert_eq!(tournament.level_message(), Message::LevelMessage { level: 2, blinds: [10, 20], ante: 2, hands: 1 });
+        assert_eq!((tournament.rules(&rules).blinds, tournament.rules(&rules).ante), ([10, 20], 2));
+        assert!(tournament.end_hand());
+        // the last level lasts until the end
+        for _ in 0..10 {
+            assert!(!tournament.end_hand());
+        }
+        assert_eq!(tournament.level(), &levels()[2]);
+        assert_eq!(tournament.level_message(), Message::LevelMessage { level: 3, blinds: [20, 40], ante: 5, hands: 0 });
+
+        // without levels the blinds of the rules are kept
+        let mut tournament = Tournament::new(&TournamentConfig::default(), &rules, 4);
+        assert!(!tournament.end_hand());
+        assert_eq!(tournament.rules(&rules), rules);
+
+        let invalid = TournamentConfig { levels: vec![BlindLevel { blinds: [20, 10], ante: 0, hands: 1 }], ..Default::default() };
+        assert!(invalid.validate(&rules).is_err());
+        assert!(TournamentConfig { payouts: vec![60, 30], ..Default::default() }.validate(&rules).is_err());
+        assert!(TournamentConfig { table_size: Some(11), ..Default::default() }.validate(&rules).is_err());
+    }
+
+    #[test]
+    pub fn test_standings() {
+        let rules = GameRules::new(HOLDEM_NL).unwrap();
+        let config = TournamentConfig { buy_in: 100, payouts: vec![50, 30, 20], ..Default::default() };
+        let mut tournament = Tournament::new(&config, &rules, 5);
+        assert_eq!(prize_amounts(500, &config.payouts), vec![250, 150, 100]);
+        assert_eq!(prize_amounts(100, &[50, 25, 25]), vec![50, 25, 25]);
+        assert_eq!(prize_amounts(10, &[67, 33]), vec![7, 3]);
+
+        // the bigger stack at the start of the hand finishes higher
+        let out: Vec<Standing> = tournament.eliminate(&[("a".to_string(), 50), ("b".to_string(), 80)], 3);
+        assert_eq!(out, vec![
+            Standing { player: "b".to_string(), finishing_position: 4, payout: 0 },
+            Standing { player: "a".to_string(), finishing_position: 5, payout: 0 },
+        ]);
+        // equal stacks share second place and split second and third prize
+        let out: Vec<Standing> = tournament.eliminate(&[("c".to_string(), 200), ("d".to_string(), 200)], 1);
+        assert_eq!(out.iter().map(|standing| (standing.finishing_position, standing.payout)).collect::<Vec<_>>(), vec![(2, 125), (2, 125)]);
+
+        let summary: TournamentSummary = tournament.finish(Some("e".to_string()), HOLDEM_NL);
+        assert_eq!(summary.prize_pool, 500);
+        assert_eq!(summary.entrants, 5);
+        assert_eq!(summary.standings[0], Standing { player: "e".to_string(), finishing_position: 1, payout: 250 });
+        assert_eq!(summary.standings.iter().map(|standing| standing.payout).sum::<u32>(), 500);
+        assert_eq!(summary.standings.last().unwrap().player, "a");
+    }
+}